@@ -19,6 +19,11 @@ where
         Self(map)
     }
 
+    #[inline]
+    pub fn as_inner_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
     /// Get the bit `n`
     #[inline]
     pub fn get_bit(&self, n: U256) -> bool {
@@ -1,7 +1,18 @@
-use std::borrow::Cow;
+use core::time::Duration;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 use defuse_crypto::PublicKey;
-use near_sdk::{near, AccountIdRef};
+use near_sdk::{near, AccountId, AccountIdRef};
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::{
+    intents::IntentKind,
+    tokens::{TokenAmounts, TokenId},
+    Deadline, DefuseError, Result,
+};
 
 #[must_use = "make sure to `.emit()` this event"]
 #[near(serializers = [json])]
@@ -38,3 +49,272 @@ impl<'a, T> AccountEvent<'a, T> {
 pub struct PublicKeyEvent<'a> {
     pub public_key: Cow<'a, PublicKey>,
 }
+
+#[must_use = "make sure to `.emit()` this event"]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct ThresholdChangedEvent {
+    pub threshold: u16,
+}
+
+/// Restricts what can be signed with a public key, e.g. for short-lived
+/// session keys. Keys without scope have full access to the account.
+///
+/// NOTE: allowing account management intents (e.g. `add_public_key`)
+/// effectively grants full access to the account.
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyScope {
+    /// Kinds of intents allowed to be signed with the key
+    pub intents: BTreeSet<IntentKind>,
+
+    /// If set, only balances of these tokens can be changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<BTreeSet<TokenId>>,
+
+    /// Maximum amounts of tokens allowed to be spent within a single
+    /// signed payload. Tokens not listed here are not limited.
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    #[serde(default, skip_serializing_if = "TokenAmounts::is_empty")]
+    pub max_amounts: TokenAmounts,
+
+    /// The key can't be used after this deadline
+    pub expires_at: Deadline,
+}
+
+impl PublicKeyScope {
+    #[inline]
+    pub fn is_intent_allowed(&self, kind: IntentKind) -> bool {
+        self.intents.contains(&kind)
+    }
+
+    #[inline]
+    pub fn is_token_allowed(&self, token_id: &TokenId) -> bool {
+        self.tokens
+            .as_ref()
+            .is_none_or(|tokens| tokens.contains(token_id))
+    }
+
+    /// Returns whether `amount` of `token_id` is allowed to be spent
+    #[inline]
+    pub fn is_amount_allowed(&self, token_id: &TokenId, amount: u128) -> bool {
+        self.is_token_allowed(token_id)
+            && self
+                .max_amounts
+                .get(token_id)
+                .is_none_or(|max| amount <= *max)
+    }
+}
+
+/// Temporarily disables all public keys of the account except
+/// `recovery_keys`, e.g. when a session key is suspected to be leaked.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockdown {
+    /// The only public keys allowed to sign intents during lockdown.
+    /// Threshold of the account still applies to them.
+    pub recovery_keys: BTreeSet<PublicKey>,
+
+    /// Lockdown is lifted after this deadline
+    pub expires_at: Deadline,
+}
+
+impl Lockdown {
+    /// Maximum duration of a single lockdown
+    pub const MAX_DURATION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+    /// Minimum time since a public key was added to the account
+    /// before it can be used as a recovery key, so that a leaked key
+    /// can't add another one and lock the owner out with it
+    pub const MIN_RECOVERY_KEY_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        !self.expires_at.has_expired()
+    }
+
+    /// Returns whether `public_key` is allowed to sign intents,
+    /// assuming the lockdown is still active
+    #[inline]
+    pub fn is_key_allowed(&self, public_key: &PublicKey) -> bool {
+        self.recovery_keys.contains(public_key)
+    }
+}
+
+/// Accounts allowed to jointly recover access to the account when
+/// all its public keys were lost, by replacing them with a new one.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Guardians {
+    /// Empty set means that the account has no guardians
+    pub guardians: BTreeSet<AccountId>,
+
+    /// Minimum number of distinct guardians required to approve
+    /// the same public key for recovery
+    pub threshold: u16,
+
+    /// Delay in seconds between recovery getting enough approvals and
+    /// the moment it can be finalized. The owner can cancel recovery
+    /// during this period. Can't be less than [`Self::MIN_DELAY_SECS`].
+    pub delay_secs: u32,
+}
+
+impl Guardians {
+    pub const MAX_GUARDIANS: usize = 16;
+
+    /// Minimum delay, so that the owner has time to notice and cancel
+    /// recovery approved by colluding guardians
+    pub const MIN_DELAY_SECS: u32 = 24 * 60 * 60;
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.guardians.is_empty()
+    }
+
+    #[inline]
+    pub fn is_guardian(&self, account_id: &AccountIdRef) -> bool {
+        self.guardians.contains(account_id)
+    }
+
+    /// Returns whether guardians are valid for given account
+    #[inline]
+    pub fn is_valid(&self, account_id: &AccountIdRef) -> bool {
+        self.guardians.len() <= Self::MAX_GUARDIANS
+            && !self.is_guardian(account_id)
+            && (self.is_empty()
+                || (self.threshold > 0
+                    && usize::from(self.threshold) <= self.guardians.len()
+                    && self.delay_secs >= Self::MIN_DELAY_SECS))
+    }
+
+    #[inline]
+    pub const fn delay(&self) -> Duration {
+        Duration::from_secs(self.delay_secs as u64)
+    }
+}
+
+/// Recovery of the account being approved by its guardians
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recovery {
+    /// Public key each guardian approved to replace account keys with
+    pub approvals: BTreeMap<AccountId, PublicKey>,
+
+    /// Set once enough guardians approved the same public key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled: Option<ScheduledRecovery>,
+}
+
+impl Recovery {
+    /// Records approval of `public_key` by `guardian_id`, who can change
+    /// their mind until recovery gets scheduled.
+    /// Returns whether the recovery got scheduled by this approval.
+    pub fn approve(
+        &mut self,
+        guardians: &Guardians,
+        guardian_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<bool> {
+        if !guardians.is_guardian(&guardian_id) {
+            return Err(DefuseError::NotGuardian);
+        }
+        if self.scheduled.is_some() {
+            return Err(DefuseError::RecoveryScheduled);
+        }
+        self.approvals.insert(guardian_id, public_key);
+
+        let approvals = self
+            .approvals
+            .iter()
+            .filter(|(guardian_id, pk)| guardians.is_guardian(guardian_id) && **pk == public_key)
+            .count();
+        if approvals < usize::from(guardians.threshold) {
+            return Ok(false);
+        }
+        self.scheduled = Some(ScheduledRecovery {
+            public_key,
+            executable_at: Deadline::timeout(guardians.delay()),
+        });
+        Ok(true)
+    }
+
+    /// Returns public key to recover the account with,
+    /// if the recovery is scheduled and its delay has passed
+    #[inline]
+    pub fn ready(&self) -> Option<PublicKey> {
+        self.scheduled
+            .as_ref()
+            .filter(|scheduled| scheduled.executable_at.has_expired())
+            .map(|scheduled| scheduled.public_key)
+    }
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledRecovery {
+    /// All public keys of the account will be replaced with this one
+    pub public_key: PublicKey,
+
+    /// Recovery can't be finalized before this deadline
+    pub executable_at: Deadline,
+}
+
+#[must_use = "make sure to `.emit()` this event"]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct RecoveryApprovedEvent<'a> {
+    pub guardian_id: Cow<'a, AccountIdRef>,
+    pub public_key: Cow<'a, PublicKey>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_approve() {
+        let [g1, g2, g3]: [AccountId; 3] =
+            ["g1.near", "g2.near", "g3.near"].map(|g| g.parse().unwrap());
+        let guardians = Guardians {
+            guardians: [g1.clone(), g2.clone(), g3.clone()].into(),
+            threshold: 2,
+            delay_secs: Guardians::MIN_DELAY_SECS,
+        };
+        assert!(guardians.is_valid(AccountIdRef::new_or_panic("user.near")));
+        assert!(!guardians.is_valid(&g1));
+        assert!(!Guardians {
+            delay_secs: Guardians::MIN_DELAY_SECS - 1,
+            ..guardians.clone()
+        }
+        .is_valid(AccountIdRef::new_or_panic("user.near")));
+
+        let [pk1, pk2] = [[1; 32], [2; 32]].map(PublicKey::Ed25519);
+
+        let mut recovery = Recovery::default();
+        assert!(matches!(
+            recovery.approve(&guardians, "user.near".parse().unwrap(), pk1),
+            Err(DefuseError::NotGuardian)
+        ));
+        assert!(!recovery.approve(&guardians, g1.clone(), pk1).unwrap());
+        // approvals of different keys don't add up
+        assert!(!recovery.approve(&guardians, g2.clone(), pk2).unwrap());
+        // guardians can change their mind
+        assert!(recovery.approve(&guardians, g1, pk2).unwrap());
+        assert_eq!(recovery.scheduled.as_ref().unwrap().public_key, pk2);
+        // delay hasn't passed yet
+        assert_eq!(recovery.ready(), None);
+
+        assert!(matches!(
+            recovery.approve(&guardians, g3, pk1),
+            Err(DefuseError::RecoveryScheduled)
+        ));
+    }
+}
@@ -3,8 +3,13 @@ use core::{
     time::Duration,
 };
 
+use std::io;
+
 use chrono::{DateTime, Utc};
-use near_sdk::near;
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    near,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[near(serializers=[json])]
@@ -64,3 +69,68 @@ impl AddAssign<Duration> for Deadline {
         self.0 += rhs;
     }
 }
+
+/// Borsh representation is `(seconds, nanoseconds)` since UNIX epoch,
+/// so that [`Deadline`] can be stored on-chain.
+impl BorshSerialize for Deadline {
+    #[inline]
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        BorshSerialize::serialize(
+            &(self.0.timestamp(), self.0.timestamp_subsec_nanos()),
+            writer,
+        )
+    }
+}
+
+impl BorshDeserialize for Deadline {
+    #[inline]
+    fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let (secs, nsecs): (i64, u32) = BorshDeserialize::deserialize_reader(reader)?;
+        DateTime::from_timestamp(secs, nsecs)
+            .map(Self)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid timestamp"))
+    }
+}
+
+#[cfg(all(feature = "abi", not(target_arch = "wasm32")))]
+mod abi {
+    use std::collections::BTreeMap;
+
+    use near_sdk::borsh::{
+        schema::{Declaration, Definition},
+        BorshSchema,
+    };
+
+    use super::*;
+
+    impl BorshSchema for Deadline {
+        fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+            <(i64, u32)>::add_definitions_recursively(definitions);
+        }
+
+        fn declaration() -> Declaration {
+            <(i64, u32)>::declaration()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::borsh;
+
+    use super::*;
+
+    #[test]
+    fn borsh_roundtrip() {
+        for deadline in [
+            Deadline::now(),
+            Deadline::MAX,
+            Deadline(DateTime::<Utc>::MIN_UTC),
+        ] {
+            assert_eq!(
+                borsh::from_slice::<Deadline>(&borsh::to_vec(&deadline).unwrap()).unwrap(),
+                deadline
+            );
+        }
+    }
+}
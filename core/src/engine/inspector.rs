@@ -2,7 +2,12 @@ use impl_tools::autoimpl;
 use near_sdk::{AccountIdRef, CryptoHash};
 
 use crate::{
-    intents::{token_diff::TokenDiff, tokens::Transfer},
+    intents::{
+        htlc::{HtlcLock, HtlcRefund},
+        limit_order::LimitOrder,
+        token_diff::TokenDiff,
+        tokens::{BatchTransfer, Transfer, Withdrawal},
+    },
     tokens::TokenAmounts,
     Deadline,
 };
@@ -17,11 +22,60 @@ pub trait Inspector {
         transfer: &Transfer,
         intent_hash: CryptoHash,
     );
+    fn on_batch_transfer(
+        &mut self,
+        sender_id: &AccountIdRef,
+        batch: &BatchTransfer,
+        intent_hash: CryptoHash,
+    );
     fn on_token_diff(
         &mut self,
         owner_id: &AccountIdRef,
         token_diff: &TokenDiff,
         fees_collected: &TokenAmounts,
+        referral_fees: &TokenAmounts,
+        intent_hash: CryptoHash,
+    );
+    fn on_referral_not_registered(
+        &mut self,
+        owner_id: &AccountIdRef,
+        referral: &AccountIdRef,
+        intent_hash: CryptoHash,
+    );
+
+    fn on_withdraw(
+        &mut self,
+        owner_id: &AccountIdRef,
+        withdrawal: &Withdrawal<'_>,
+        intent_hash: CryptoHash,
+    );
+
+    fn on_limit_order_placed(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order: &LimitOrder,
+        intent_hash: CryptoHash,
+    );
+    fn on_limit_order_cancelled(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash);
+
+    fn on_htlc_locked(
+        &mut self,
+        sender_id: &AccountIdRef,
+        lock: &HtlcLock,
+        intent_hash: CryptoHash,
+    );
+    fn on_htlc_claimed(
+        &mut self,
+        receiver_id: &AccountIdRef,
+        sender_id: &AccountIdRef,
+        hash_lock: CryptoHash,
+        preimage: &[u8],
+        intent_hash: CryptoHash,
+    );
+    fn on_htlc_refunded(
+        &mut self,
+        sender_id: &AccountIdRef,
+        refund: &HtlcRefund,
         intent_hash: CryptoHash,
     );
 
@@ -3,19 +3,30 @@ mod state;
 
 pub use self::{inspector::*, state::*};
 
-use defuse_crypto::{Payload, SignedPayload};
+use std::collections::BTreeSet;
+
+use defuse_crypto::{Payload, PublicKey};
+use near_sdk::CryptoHash;
 
 use crate::{
+    accounts::Lockdown,
     intents::{DefuseIntents, ExecutableIntent},
-    payload::{multi::MultiPayload, DefusePayload, ExtractDefusePayload},
+    payload::{
+        multi::UnsignedMultiPayload, multisig::MultiSigPayload, DefusePayload, ExtractDefusePayload,
+    },
     DefuseError, Result,
 };
 
-use self::deltas::{Deltas, Transfers};
+use self::{
+    cached::CachedState,
+    deltas::{Deltas, Transfers},
+};
 
 pub struct Engine<S, I> {
     pub state: Deltas<S>,
     pub inspector: I,
+    /// Public keys the payload being executed was signed with
+    pub(crate) signer_public_keys: BTreeSet<PublicKey>,
 }
 
 impl<S, I> Engine<S, I>
@@ -28,66 +39,166 @@ where
         Self {
             state: Deltas::new(state),
             inspector,
+            signer_public_keys: BTreeSet::new(),
         }
     }
 
     pub fn execute_signed_intents(
         mut self,
-        signed: impl IntoIterator<Item = MultiPayload>,
+        signed: impl IntoIterator<Item = impl Into<MultiSigPayload>>,
     ) -> Result<Transfers> {
         for signed in signed {
-            self.execute_signed_intent(signed)?;
+            self.execute_signed_intent(signed.into())?;
         }
         self.finalize()
     }
 
-    fn execute_signed_intent(&mut self, signed: MultiPayload) -> Result<()> {
-        // verify signed payload and get public key
-        let public_key = signed.verify().ok_or(DefuseError::InvalidSignature)?;
+    /// Executes a single signed intent. Make sure to call
+    /// [`.finalize()`](Self::finalize) after the last one.
+    pub fn execute_signed_intent(&mut self, signed: MultiSigPayload) -> Result<()> {
+        // signatures made under different standards can't be over
+        // the same payload
+        if !signed.is_single_standard() {
+            return Err(DefuseError::MixedPayloadStandards);
+        }
 
-        // calculate intent hash
-        let hash = signed.hash();
+        // verify signed payload, calculate intent hash and get public keys
+        let (hash, public_keys) = signed.verify().ok_or(DefuseError::InvalidSignature)?;
 
         // extract NEP-413 payload
+        let payload: DefusePayload<DefuseIntents> = signed.extract_defuse_payload()?;
+
+        self.execute_payload(payload, hash, &public_keys, true)
+    }
+
+    /// Executes payload signed with given public keys, which are
+    /// assumed to be already verified. If `commit_nonce` is not set,
+    /// the nonce is only checked to be unused.
+    fn execute_payload(
+        &mut self,
+        payload: DefusePayload<DefuseIntents>,
+        hash: CryptoHash,
+        public_keys: &BTreeSet<PublicKey>,
+        commit_nonce: bool,
+    ) -> Result<()> {
+        self.check_payload(&payload)?;
+
         let DefusePayload::<DefuseIntents> {
             signer_id,
-            verifying_contract,
-            deadline,
             nonce,
             message: intents,
-        } = signed.extract_defuse_payload()?;
+            ..
+        } = payload;
+
+        // make sure the account has all these public keys
+        if !public_keys
+            .iter()
+            .all(|public_key| self.state.has_public_key(&signer_id, public_key))
+        {
+            return Err(DefuseError::PublicKeyNotExist);
+        }
 
-        // check recipient
-        if verifying_contract != *self.state.verifying_contract() {
-            return Err(DefuseError::WrongVerifyingContract);
+        // during lockdown only recovery keys are allowed to sign
+        if let Some(lockdown) = self.state.lockdown(&signer_id).filter(Lockdown::is_active) {
+            self.inspector.on_deadline(lockdown.expires_at);
+            if !public_keys
+                .iter()
+                .all(|public_key| lockdown.is_key_allowed(public_key))
+            {
+                return Err(DefuseError::PublicKeyLockedDown);
+            }
         }
 
-        self.inspector.on_deadline(deadline);
-        // make sure message is still valid
-        if deadline.has_expired() {
-            return Err(DefuseError::DeadlineExpired);
+        // make sure there are enough distinct signatures
+        if public_keys.len() < usize::from(self.state.threshold(&signer_id)) {
+            return Err(DefuseError::InsufficientSignatures);
         }
 
-        // make sure the account has this public key
-        if !self.state.has_public_key(&signer_id, &public_key) {
-            return Err(DefuseError::PublicKeyNotExist);
+        // get scopes of restricted public keys, if any
+        let scopes: Vec<_> = public_keys
+            .iter()
+            .filter_map(|public_key| self.state.public_key_scope(&signer_id, public_key))
+            .collect();
+        for scope in &scopes {
+            self.inspector.on_deadline(scope.expires_at);
+            if scope.expires_at.has_expired() {
+                return Err(DefuseError::DeadlineExpired);
+            }
+        }
+        if !intents.intents.iter().all(|intent| {
+            scopes
+                .iter()
+                .all(|scope| scope.is_intent_allowed(intent.kind()))
+        }) {
+            return Err(DefuseError::PublicKeyScopeViolated);
         }
 
         // commit nonce
-        if !self.state.commit_nonce(signer_id.clone(), nonce) {
+        if commit_nonce {
+            if !self.state.commit_nonce(signer_id.clone(), nonce) {
+                return Err(DefuseError::NonceUsed);
+            }
+        } else if self.state.is_nonce_used(&signer_id, nonce) {
             return Err(DefuseError::NonceUsed);
         }
 
+        self.state.restrict(signer_id.clone(), scopes);
+        self.signer_public_keys.clone_from(public_keys);
         intents.execute_intent(&signer_id, self, hash)?;
+        self.signer_public_keys.clear();
+        self.state.unrestrict();
         self.inspector.on_intent_executed(&signer_id, hash);
 
         Ok(())
     }
 
+    /// Checks that don't depend on signatures
+    fn check_payload(&mut self, payload: &DefusePayload<DefuseIntents>) -> Result<()> {
+        // check recipient
+        if payload.verifying_contract != *self.state.verifying_contract() {
+            return Err(DefuseError::WrongVerifyingContract);
+        }
+
+        // locked accounts can't sign intents
+        if self.state.is_account_locked(&payload.signer_id) {
+            return Err(DefuseError::AccountLocked);
+        }
+
+        self.inspector.on_deadline(payload.deadline);
+        // make sure message is still valid
+        if payload.deadline.has_expired() {
+            return Err(DefuseError::DeadlineExpired);
+        }
+
+        Ok(())
+    }
+
     #[inline]
-    fn finalize(self) -> Result<Transfers> {
+    pub fn finalize(self) -> Result<Transfers> {
         self.state
             .finalize()
             .map_err(DefuseError::InvariantViolated)
     }
 }
+
+impl<W, I> Engine<CachedState<W>, I>
+where
+    W: StateView,
+    I: Inspector,
+{
+    /// Simulates a single unsigned intent as if it was signed by
+    /// `signer_id` with given `public_keys`. All checks are performed
+    /// except for signature verification. Intent hash is calculated
+    /// under the payload standard, so it equals to the hash of the
+    /// signed one. The nonce is checked but not committed, so the same
+    /// nonce can be reused across simulated payloads.
+    pub fn simulate_unsigned_intent(
+        &mut self,
+        payload: UnsignedMultiPayload,
+        public_keys: &BTreeSet<PublicKey>,
+    ) -> Result<()> {
+        let hash = payload.hash();
+        let payload: DefusePayload<DefuseIntents> = payload.extract_defuse_payload()?;
+        self.execute_payload(payload, hash, public_keys, false)
+    }
+}
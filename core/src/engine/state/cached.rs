@@ -1,16 +1,18 @@
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
 };
 
 use defuse_bitmap::{U248, U256};
 use defuse_crypto::PublicKey;
-use near_sdk::{AccountId, AccountIdRef};
+use near_sdk::{AccountId, AccountIdRef, CryptoHash};
 
 use crate::{
-    fees::Pips,
+    accounts::{Guardians, Lockdown, PublicKeyScope, Recovery},
+    fees::{FeeMode, Pips},
+    intents::{htlc::Htlc, limit_order::LimitOrderState, token_diff::TokenDeltas},
     tokens::{TokenAmounts, TokenId},
-    DefuseError, Nonce, Nonces, Result,
+    Deadline, DefuseError, Nonce, Nonces, Result,
 };
 
 use super::{State, StateView};
@@ -19,6 +21,8 @@ use super::{State, StateView};
 pub struct CachedState<W: StateView> {
     view: W,
     accounts: CachedAccounts,
+    /// Overrides fee for all tokens
+    fee: Option<Pips>,
 }
 
 impl<W> CachedState<W>
@@ -30,8 +34,68 @@ where
         Self {
             view,
             accounts: CachedAccounts::new(),
+            fee: None,
         }
     }
+
+    /// Overrides current balance of `token_id` of `account_id`
+    pub fn override_balance(&mut self, account_id: AccountId, token_id: TokenId, amount: u128) {
+        self.accounts
+            .get_or_create(account_id)
+            .token_amounts
+            .insert(token_id, amount);
+    }
+
+    /// Overrides fee for all tokens and pairs
+    #[inline]
+    pub fn override_fee(&mut self, fee: Pips) {
+        self.fee = Some(fee);
+    }
+
+    /// Starts recording changes, so that they can be discarded with
+    /// [`.rollback()`](Self::rollback). Changes made before are kept.
+    #[inline]
+    pub fn checkpoint(&mut self) {
+        self.accounts.checkpoint();
+    }
+
+    /// Discards changes made since the last
+    /// [`.checkpoint()`](Self::checkpoint)
+    #[inline]
+    pub fn rollback(&mut self) {
+        self.accounts.rollback();
+    }
+
+    /// Changes of token balances since the last
+    /// [`.checkpoint()`](Self::checkpoint).
+    /// Deltas exceeding `i128` are saturated.
+    pub fn balance_deltas(&self) -> BTreeMap<AccountId, TokenDeltas> {
+        self.accounts
+            .journal
+            .iter()
+            .flatten()
+            .filter_map(|(account_id, before)| {
+                let account = self.accounts.get(account_id)?;
+                let deltas: BTreeMap<_, _> = account
+                    .token_amounts
+                    .iter()
+                    .filter_map(|(token_id, &amount)| {
+                        let before = before
+                            .as_ref()
+                            .and_then(|before| before.token_amounts.get(token_id).copied())
+                            .unwrap_or_else(|| self.view.balance_of(account_id, token_id));
+                        let delta = if amount >= before {
+                            i128::try_from(amount - before).unwrap_or(i128::MAX)
+                        } else {
+                            i128::try_from(before - amount).map_or(i128::MIN, |d| -d)
+                        };
+                        (delta != 0).then(|| (token_id.clone(), delta))
+                    })
+                    .collect();
+                (!deltas.is_empty()).then(|| (account_id.clone(), TokenDeltas::new(deltas)))
+            })
+            .collect()
+    }
 }
 
 impl<W> StateView for CachedState<W>
@@ -50,7 +114,13 @@ where
 
     #[inline]
     fn fee(&self) -> Pips {
-        self.view.fee()
+        self.fee.unwrap_or_else(|| self.view.fee())
+    }
+
+    #[inline]
+    fn fee_for(&self, token_in: &TokenId, token_out: Option<&TokenId>) -> Pips {
+        self.fee
+            .unwrap_or_else(|| self.view.fee_for(token_in, token_out))
     }
 
     #[inline]
@@ -58,6 +128,26 @@ where
         self.view.fee_collector()
     }
 
+    #[inline]
+    fn fee_collector_shares(&self) -> Cow<'_, BTreeMap<AccountId, Pips>> {
+        self.view.fee_collector_shares()
+    }
+
+    #[inline]
+    fn referral_fee(&self) -> Pips {
+        self.view.referral_fee()
+    }
+
+    #[inline]
+    fn fee_mode(&self) -> FeeMode {
+        self.view.fee_mode()
+    }
+
+    #[inline]
+    fn fee_discount(&self, account_id: &AccountIdRef) -> Pips {
+        self.view.fee_discount(account_id)
+    }
+
     fn has_public_key(&self, account_id: &AccountIdRef, public_key: &PublicKey) -> bool {
         if let Some(account) = self.accounts.get(account_id) {
             if account.public_keys_added.contains(public_key) {
@@ -84,6 +174,89 @@ where
             )
     }
 
+    fn public_key_scope(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<PublicKeyScope> {
+        if let Some(scope) = self
+            .accounts
+            .get(account_id)
+            .and_then(|account| account.public_key_scopes.get(public_key))
+        {
+            return scope.clone();
+        }
+        self.view.public_key_scope(account_id, public_key)
+    }
+
+    fn public_key_added_at(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<Deadline> {
+        if self
+            .accounts
+            .get(account_id)
+            .is_some_and(|account| account.public_keys_added.contains(public_key))
+        {
+            return Some(Deadline::now());
+        }
+        self.view.public_key_added_at(account_id, public_key)
+    }
+
+    fn threshold(&self, account_id: &AccountIdRef) -> u16 {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.threshold)
+            .unwrap_or_else(|| self.view.threshold(account_id))
+    }
+
+    fn is_account_registered(&self, account_id: &AccountIdRef) -> bool {
+        !self
+            .accounts
+            .get(account_id)
+            .is_some_and(|account| account.closed)
+            && self.view.is_account_registered(account_id)
+    }
+
+    fn is_account_locked(&self, account_id: &AccountIdRef) -> bool {
+        self.accounts
+            .get(account_id)
+            .is_some_and(|account| account.locked)
+            || self.view.is_account_locked(account_id)
+    }
+
+    fn lockdown(&self, account_id: &AccountIdRef) -> Option<Lockdown> {
+        if let Some(account) = self.accounts.get(account_id) {
+            if account.lockdown.is_some() || account.closed {
+                return account.lockdown.clone();
+            }
+        }
+        self.view.lockdown(account_id)
+    }
+
+    fn guardians(&self, account_id: &AccountIdRef) -> Option<Guardians> {
+        if let Some(guardians) = self
+            .accounts
+            .get(account_id)
+            .and_then(|account| account.guardians.as_ref())
+        {
+            return guardians.clone();
+        }
+        self.view.guardians(account_id)
+    }
+
+    fn recovery(&self, account_id: &AccountIdRef) -> Option<Recovery> {
+        if let Some(recovery) = self
+            .accounts
+            .get(account_id)
+            .and_then(|account| account.recovery.as_ref())
+        {
+            return recovery.clone();
+        }
+        self.view.recovery(account_id)
+    }
+
     fn is_nonce_used(&self, account_id: &AccountIdRef, nonce: Nonce) -> bool {
         self.accounts
             .get(account_id)
@@ -97,6 +270,66 @@ where
             .and_then(|account| account.token_amounts.get(token_id).copied())
             .unwrap_or_else(|| self.view.balance_of(account_id, token_id))
     }
+
+    fn iter_token_ids(&self, account_id: &AccountIdRef) -> impl Iterator<Item = TokenId> + '_ {
+        let account = self.accounts.get(account_id);
+        self.view
+            .iter_token_ids(account_id)
+            .filter(move |token_id| account.is_none_or(|a| !a.token_amounts.contains_key(token_id)))
+            .chain(
+                account
+                    .map(|a| &a.token_amounts)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, amount)| **amount != 0)
+                    .map(|(token_id, _)| token_id.clone()),
+            )
+    }
+
+    fn limit_order(
+        &self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+    ) -> Option<LimitOrderState> {
+        if let Some(account) = self.accounts.get(maker_id) {
+            if let Some(order) = account.limit_orders.get(&order_hash) {
+                return order.clone();
+            }
+            if account.closed {
+                return None;
+            }
+        }
+        self.view.limit_order(maker_id, order_hash)
+    }
+
+    fn htlc(&self, sender_id: &AccountIdRef, hash_lock: &CryptoHash) -> Option<Htlc> {
+        if let Some(htlc) = self
+            .accounts
+            .get(sender_id)
+            .and_then(|account| account.htlcs.get(hash_lock))
+        {
+            return htlc.clone();
+        }
+        self.view.htlc(sender_id, hash_lock)
+    }
+
+    fn iter_htlc_hash_locks(
+        &self,
+        sender_id: &AccountIdRef,
+    ) -> impl Iterator<Item = CryptoHash> + '_ {
+        let account = self.accounts.get(sender_id);
+        self.view
+            .iter_htlc_hash_locks(sender_id)
+            .filter(move |hash_lock| account.is_none_or(|a| !a.htlcs.contains_key(hash_lock)))
+            .chain(
+                account
+                    .map(|a| &a.htlcs)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, htlc)| htlc.is_some())
+                    .map(|(hash_lock, _)| *hash_lock),
+            )
+    }
 }
 
 impl<W> State for CachedState<W>
@@ -118,6 +351,8 @@ where
     fn remove_public_key(&mut self, account_id: AccountId, public_key: PublicKey) -> bool {
         let had = self.has_public_key(&account_id, &public_key);
         let account = self.accounts.get_or_create(account_id.clone());
+        // re-added key should not inherit the scope
+        account.public_key_scopes.insert(public_key, None);
         if had {
             account.public_keys_removed.insert(public_key)
         } else {
@@ -125,6 +360,92 @@ where
         }
     }
 
+    #[inline]
+    fn set_public_key_scope(
+        &mut self,
+        account_id: AccountId,
+        public_key: PublicKey,
+        scope: PublicKeyScope,
+    ) {
+        self.accounts
+            .get_or_create(account_id)
+            .public_key_scopes
+            .insert(public_key, Some(scope));
+    }
+
+    #[inline]
+    fn set_threshold(&mut self, account_id: AccountId, threshold: u16) {
+        self.accounts.get_or_create(account_id).threshold = Some(threshold);
+    }
+
+    #[inline]
+    fn set_lockdown(&mut self, account_id: AccountId, lockdown: Lockdown) {
+        self.accounts.get_or_create(account_id).lockdown = Some(lockdown);
+    }
+
+    #[must_use]
+    fn lock_account(&mut self, account_id: AccountId) -> bool {
+        if self.is_account_locked(&account_id) {
+            return false;
+        }
+        self.accounts.get_or_create(account_id).locked = true;
+        true
+    }
+
+    fn set_guardians(&mut self, account_id: AccountId, guardians: Guardians) {
+        let account = self.accounts.get_or_create(account_id);
+        account.guardians = Some((!guardians.is_empty()).then_some(guardians));
+        account.recovery = Some(None);
+    }
+
+    fn approve_recovery(
+        &mut self,
+        account_id: AccountId,
+        guardian_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<()> {
+        if self.is_account_locked(&account_id) {
+            return Err(DefuseError::AccountLocked);
+        }
+        let guardians = self
+            .guardians(&account_id)
+            .ok_or(DefuseError::NotGuardian)?;
+        let mut recovery = self.recovery(&account_id).unwrap_or_default();
+        recovery.approve(&guardians, guardian_id, public_key)?;
+        self.accounts.get_or_create(account_id).recovery = Some(Some(recovery));
+        Ok(())
+    }
+
+    #[must_use]
+    fn cancel_recovery(&mut self, account_id: AccountId) -> bool {
+        let had = self.recovery(&account_id).is_some();
+        self.accounts.get_or_create(account_id).recovery = Some(None);
+        had
+    }
+
+    fn close_account(&mut self, account_id: AccountId, _receiver_id: AccountId) -> Result<()> {
+        if self.is_account_locked(&account_id) {
+            return Err(DefuseError::AccountLocked);
+        }
+        if self.iter_token_ids(&account_id).next().is_some()
+            || self.iter_htlc_hash_locks(&account_id).next().is_some()
+        {
+            return Err(DefuseError::AccountNotEmpty);
+        }
+        let public_keys: Vec<_> = self.iter_public_keys(&account_id).collect();
+        for public_key in public_keys {
+            let _ = self.remove_public_key(account_id.clone(), public_key);
+        }
+        let account = self.accounts.get_or_create(account_id);
+        account.threshold = Some(1);
+        account.guardians = Some(None);
+        account.recovery = Some(None);
+        account.lockdown = None;
+        account.limit_orders.clear();
+        account.closed = true;
+        Ok(())
+    }
+
     #[must_use]
     fn commit_nonce(&mut self, account_id: AccountId, nonce: Nonce) -> bool {
         if self.is_nonce_used(&account_id, nonce) {
@@ -133,6 +454,95 @@ where
         self.accounts.get_or_create(account_id).commit_nonce(nonce)
     }
 
+    #[must_use]
+    fn place_limit_order(
+        &mut self,
+        maker_id: AccountId,
+        order_hash: CryptoHash,
+        order: LimitOrderState,
+    ) -> bool {
+        if self.limit_order(&maker_id, order_hash).is_some() {
+            return false;
+        }
+        self.accounts
+            .get_or_create(maker_id)
+            .limit_orders
+            .insert(order_hash, Some(order));
+        true
+    }
+
+    fn fill_limit_order(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+        amount_in: u128,
+    ) -> Result<LimitOrderState> {
+        let mut state = self
+            .limit_order(maker_id, order_hash)
+            .ok_or(DefuseError::LimitOrderNotFound)?;
+        state
+            .fill(amount_in)
+            .ok_or(DefuseError::LimitOrderOverfilled)?;
+        self.accounts
+            .get_or_create(maker_id.to_owned())
+            .limit_orders
+            // keep removed orders as `None` to shadow the underlying view
+            .insert(order_hash, (!state.is_filled()).then(|| state.clone()));
+        Ok(state)
+    }
+
+    #[must_use]
+    fn cancel_limit_order(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash) -> bool {
+        if self.limit_order(maker_id, order_hash).is_none() {
+            return false;
+        }
+        self.accounts
+            .get_or_create(maker_id.to_owned())
+            .limit_orders
+            .insert(order_hash, None);
+        true
+    }
+
+    fn lock_htlc(&mut self, hash_lock: CryptoHash, htlc: Htlc) -> Result<()> {
+        if self.htlc(&htlc.sender_id, &hash_lock).is_some() {
+            return Err(DefuseError::HtlcExists);
+        }
+        self.internal_withdraw(&htlc.sender_id, htlc.tokens.clone())?;
+        self.accounts
+            .get_or_create(htlc.sender_id.clone())
+            .htlcs
+            .insert(hash_lock, Some(htlc));
+        Ok(())
+    }
+
+    fn release_htlc(
+        &mut self,
+        sender_id: &AccountIdRef,
+        hash_lock: &CryptoHash,
+        receiver_id: AccountId,
+    ) -> Result<Htlc> {
+        let htlc = self
+            .htlc(sender_id, hash_lock)
+            .ok_or(DefuseError::HtlcNotFound)?;
+        self.accounts
+            .get_or_create(sender_id.to_owned())
+            .htlcs
+            // keep released HTLCs as `None` to shadow the underlying view
+            .insert(*hash_lock, None);
+        self.internal_deposit(receiver_id, htlc.tokens.clone())?;
+        Ok(htlc)
+    }
+
+    #[inline]
+    fn record_fees_collected(
+        &mut self,
+        _fees_collected: &TokenAmounts,
+        _referral_fees: Option<(&AccountIdRef, &TokenAmounts)>,
+    ) -> Result<()> {
+        // fee statistics are not observable during execution
+        Ok(())
+    }
+
     fn internal_deposit(
         &mut self,
         owner_id: AccountId,
@@ -140,15 +550,12 @@ where
     ) -> Result<()> {
         let account = self.accounts.get_or_create(owner_id.clone());
         for (token_id, amount) in token_amounts {
-            if account.token_amounts.get(&token_id).is_none() {
-                account
-                    .token_amounts
-                    .deposit(token_id.clone(), self.view.balance_of(&owner_id, &token_id))
-                    .ok_or(DefuseError::BalanceOverflow)?;
-            }
-            account
+            let balance = account
                 .token_amounts
-                .deposit(token_id, amount)
+                .entry(token_id)
+                .or_insert_with_key(|token_id| self.view.balance_of(&owner_id, token_id));
+            *balance = balance
+                .checked_add(amount)
                 .ok_or(DefuseError::BalanceOverflow)?;
         }
         Ok(())
@@ -159,6 +566,9 @@ where
         owner_id: &AccountIdRef,
         token_amounts: impl IntoIterator<Item = (TokenId, u128)>,
     ) -> Result<()> {
+        if self.is_account_locked(owner_id) {
+            return Err(DefuseError::AccountLocked);
+        }
         let account = self
             .accounts
             .get_mut(owner_id)
@@ -168,55 +578,113 @@ where
                 return Err(DefuseError::InvalidIntent);
             }
 
-            if account.token_amounts.get(&token_id).is_none() {
-                account
-                    .token_amounts
-                    .deposit(token_id.clone(), self.view.balance_of(owner_id, &token_id))
-                    .ok_or(DefuseError::BalanceOverflow)?;
-            }
-            account
+            let balance = account
                 .token_amounts
-                .withdraw(token_id, amount)
+                .entry(token_id)
+                .or_insert_with_key(|token_id| self.view.balance_of(owner_id, token_id));
+            *balance = balance
+                .checked_sub(amount)
                 .ok_or(DefuseError::BalanceOverflow)?;
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct CachedAccounts(HashMap<AccountId, CachedAccount>);
+#[derive(Debug, Default)]
+pub struct CachedAccounts {
+    accounts: HashMap<AccountId, CachedAccount>,
+    /// Accounts modified since the last checkpoint as they were before,
+    /// `None` if they were not cached yet
+    journal: Option<HashMap<AccountId, Option<CachedAccount>>>,
+}
 
 impl CachedAccounts {
     #[must_use]
     #[inline]
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self::default()
     }
 
     #[inline]
     pub fn get(&self, account_id: &AccountIdRef) -> Option<&CachedAccount> {
-        self.0.get(account_id)
+        self.accounts.get(account_id)
     }
 
     #[inline]
     pub fn get_mut(&mut self, account_id: &AccountIdRef) -> Option<&mut CachedAccount> {
-        self.0.get_mut(account_id)
+        self.record(account_id);
+        self.accounts.get_mut(account_id)
     }
 
     #[inline]
     pub fn get_or_create(&mut self, account_id: AccountId) -> &mut CachedAccount {
-        self.0.entry(account_id).or_default()
+        self.record(&account_id);
+        self.accounts.entry(account_id).or_default()
+    }
+
+    #[inline]
+    pub fn checkpoint(&mut self) {
+        self.journal = Some(HashMap::new());
+    }
+
+    pub fn rollback(&mut self) {
+        for (account_id, account) in self.journal.take().into_iter().flatten() {
+            if let Some(account) = account {
+                self.accounts.insert(account_id, account);
+            } else {
+                self.accounts.remove(&account_id);
+            }
+        }
+    }
+
+    /// Saves the account as it was before the first modification since
+    /// the last checkpoint
+    fn record(&mut self, account_id: &AccountIdRef) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        if !journal.contains_key(account_id) {
+            journal.insert(
+                account_id.to_owned(),
+                self.accounts.get(account_id).cloned(),
+            );
+        }
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct CachedAccount {
+    /// Locked within this state, i.e. in addition to the underlying view
+    locked: bool,
+    /// Closed within this state, so that limit orders of the underlying
+    /// view are forgotten. Used nonces are kept to prevent replays.
+    closed: bool,
+
     nonces: Nonces<HashMap<U248, U256>>,
 
     public_keys_added: HashSet<PublicKey>,
     public_keys_removed: HashSet<PublicKey>,
+    /// `None` means that the scope was removed along with the key
+    public_key_scopes: HashMap<PublicKey, Option<PublicKeyScope>>,
+
+    threshold: Option<u16>,
+
+    lockdown: Option<Lockdown>,
+
+    /// `Some(None)` means that guardians were removed
+    guardians: Option<Option<Guardians>>,
+    /// `Some(None)` means that the recovery was cancelled
+    recovery: Option<Option<Recovery>>,
+
+    /// Balances of touched tokens. Zero balances are kept to shadow
+    /// the underlying view.
+    token_amounts: HashMap<TokenId, u128>,
+
+    /// `None` means that the order was removed
+    limit_orders: HashMap<CryptoHash, Option<LimitOrderState>>,
 
-    token_amounts: TokenAmounts<HashMap<TokenId, u128>>,
+    /// `None` means that the HTLC was released
+    htlcs: HashMap<CryptoHash, Option<Htlc>>,
 }
 
 impl CachedAccount {
@@ -2,30 +2,34 @@ use std::{
     borrow::Cow,
     cmp::Reverse,
     collections::{BTreeMap, HashMap},
-    iter,
+    iter, mem,
 };
 
 use defuse_crypto::PublicKey;
 use defuse_map_utils::cleanup::DefaultMap;
 use defuse_nep245::{MtEvent, MtTransferEvent};
-use near_sdk::{json_types::U128, near, AccountId, AccountIdRef};
+use near_sdk::{json_types::U128, near, AccountId, AccountIdRef, CryptoHash};
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::{
-    fees::Pips,
+    accounts::{Guardians, Lockdown, PublicKeyScope, Recovery},
+    fees::{FeeMode, Pips},
     intents::{
+        htlc::Htlc,
+        limit_order::LimitOrderState,
         token_diff::TokenDeltas,
-        tokens::{FtWithdraw, MtWithdraw, NativeWithdraw, NftWithdraw},
+        tokens::{Call, FtWithdraw, MtWithdraw, NativeWithdraw, NftWithdraw, StorageDeposit},
     },
     tokens::{TokenAmounts, TokenId},
-    DefuseError, Nonce, Result,
+    Deadline, DefuseError, Nonce, Result,
 };
 
-use super::{State, StateView};
+use super::{cached::CachedState, State, StateView};
 
 pub struct Deltas<S> {
     state: S,
     deltas: TransferMatcher,
+    guard: Option<ScopeGuard>,
 }
 
 impl<S> Deltas<S> {
@@ -34,15 +38,109 @@ impl<S> Deltas<S> {
         Self {
             state,
             deltas: TransferMatcher::new(),
+            guard: None,
         }
     }
 
+    /// Restrict balance changes of `owner_id` according to given scopes
+    /// until [`.unrestrict()`](Self::unrestrict) is called.
+    /// No-op if `scopes` is empty.
+    #[inline]
+    pub fn restrict(&mut self, owner_id: AccountId, scopes: Vec<PublicKeyScope>) {
+        self.guard = (!scopes.is_empty()).then(|| ScopeGuard::new(owner_id, scopes));
+    }
+
+    #[inline]
+    pub fn unrestrict(&mut self) {
+        self.guard = None;
+    }
+
+    /// Returns scopes restricting balance changes of `owner_id`, if any
+    #[inline]
+    pub fn scopes_of(&self, owner_id: &AccountIdRef) -> &[PublicKeyScope] {
+        self.guard
+            .as_ref()
+            .filter(|guard| guard.owner_id == owner_id)
+            .map_or(&[], |guard| guard.scopes.as_slice())
+    }
+
+    /// Restrict balance changes of `owner_id` according to given scopes
+    /// in addition to the current ones, if they are for the same account.
+    /// Returns current restriction to be put back with
+    /// [`.restore_restriction()`](Self::restore_restriction).
+    #[inline]
+    pub(crate) fn replace_restriction(
+        &mut self,
+        owner_id: AccountId,
+        mut scopes: Vec<PublicKeyScope>,
+    ) -> Option<ScopeGuard> {
+        scopes.extend_from_slice(self.scopes_of(&owner_id));
+        mem::replace(
+            &mut self.guard,
+            (!scopes.is_empty()).then(|| ScopeGuard::new(owner_id, scopes)),
+        )
+    }
+
+    #[inline]
+    pub(crate) fn restore_restriction(&mut self, guard: Option<ScopeGuard>) {
+        self.guard = guard;
+    }
+
+    #[inline]
+    fn check_deposit(&self, owner_id: &AccountIdRef, token_id: &TokenId) -> Result<()> {
+        self.guard
+            .as_ref()
+            .map_or(Ok(()), |guard| guard.check_deposit(owner_id, token_id))
+    }
+
+    #[inline]
+    fn check_withdraw(
+        &mut self,
+        owner_id: &AccountIdRef,
+        tokens: impl IntoIterator<Item = (TokenId, u128)>,
+    ) -> Result<()> {
+        let Some(guard) = self.guard.as_mut() else {
+            return Ok(());
+        };
+        tokens
+            .into_iter()
+            .try_for_each(|(token_id, amount)| guard.check_withdraw(owner_id, token_id, amount))
+    }
+
+    #[inline]
+    pub const fn as_inner(&self) -> &S {
+        &self.state
+    }
+
     #[inline]
     pub fn finalize(self) -> Result<Transfers, InvariantViolated> {
         self.deltas.finalize()
     }
 }
 
+impl<W> Deltas<CachedState<W>>
+where
+    W: StateView,
+{
+    /// Starts recording changes of both the state and deltas, so that
+    /// they can be discarded with [`.rollback()`](Self::rollback).
+    /// Only for use between payloads, when nothing is restricted.
+    #[inline]
+    pub fn checkpoint(&mut self) {
+        self.state.checkpoint();
+        self.deltas.checkpoint();
+    }
+
+    /// Discards changes made since the last
+    /// [`.checkpoint()`](Self::checkpoint)
+    #[inline]
+    pub fn rollback(&mut self) {
+        self.state.rollback();
+        self.deltas.rollback();
+        self.unrestrict();
+    }
+}
+
 impl<S> StateView for Deltas<S>
 where
     S: StateView,
@@ -62,11 +160,36 @@ where
         self.state.fee()
     }
 
+    #[inline]
+    fn fee_for(&self, token_in: &TokenId, token_out: Option<&TokenId>) -> Pips {
+        self.state.fee_for(token_in, token_out)
+    }
+
     #[inline]
     fn fee_collector(&self) -> Cow<'_, AccountIdRef> {
         self.state.fee_collector()
     }
 
+    #[inline]
+    fn fee_collector_shares(&self) -> Cow<'_, BTreeMap<AccountId, Pips>> {
+        self.state.fee_collector_shares()
+    }
+
+    #[inline]
+    fn referral_fee(&self) -> Pips {
+        self.state.referral_fee()
+    }
+
+    #[inline]
+    fn fee_mode(&self) -> FeeMode {
+        self.state.fee_mode()
+    }
+
+    #[inline]
+    fn fee_discount(&self, account_id: &AccountIdRef) -> Pips {
+        self.state.fee_discount(account_id)
+    }
+
     #[inline]
     fn has_public_key(&self, account_id: &AccountIdRef, public_key: &PublicKey) -> bool {
         self.state.has_public_key(account_id, public_key)
@@ -77,6 +200,54 @@ where
         self.state.iter_public_keys(account_id)
     }
 
+    #[inline]
+    fn public_key_scope(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<PublicKeyScope> {
+        self.state.public_key_scope(account_id, public_key)
+    }
+
+    #[inline]
+    fn public_key_added_at(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<Deadline> {
+        self.state.public_key_added_at(account_id, public_key)
+    }
+
+    #[inline]
+    fn threshold(&self, account_id: &AccountIdRef) -> u16 {
+        self.state.threshold(account_id)
+    }
+
+    #[inline]
+    fn is_account_registered(&self, account_id: &AccountIdRef) -> bool {
+        self.state.is_account_registered(account_id)
+    }
+
+    #[inline]
+    fn is_account_locked(&self, account_id: &AccountIdRef) -> bool {
+        self.state.is_account_locked(account_id)
+    }
+
+    #[inline]
+    fn lockdown(&self, account_id: &AccountIdRef) -> Option<Lockdown> {
+        self.state.lockdown(account_id)
+    }
+
+    #[inline]
+    fn guardians(&self, account_id: &AccountIdRef) -> Option<Guardians> {
+        self.state.guardians(account_id)
+    }
+
+    #[inline]
+    fn recovery(&self, account_id: &AccountIdRef) -> Option<Recovery> {
+        self.state.recovery(account_id)
+    }
+
     #[inline]
     fn is_nonce_used(&self, account_id: &AccountIdRef, nonce: Nonce) -> bool {
         self.state.is_nonce_used(account_id, nonce)
@@ -86,6 +257,33 @@ where
     fn balance_of(&self, account_id: &AccountIdRef, token_id: &TokenId) -> u128 {
         self.state.balance_of(account_id, token_id)
     }
+
+    #[inline]
+    fn iter_token_ids(&self, account_id: &AccountIdRef) -> impl Iterator<Item = TokenId> + '_ {
+        self.state.iter_token_ids(account_id)
+    }
+
+    #[inline]
+    fn limit_order(
+        &self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+    ) -> Option<LimitOrderState> {
+        self.state.limit_order(maker_id, order_hash)
+    }
+
+    #[inline]
+    fn htlc(&self, sender_id: &AccountIdRef, hash_lock: &CryptoHash) -> Option<Htlc> {
+        self.state.htlc(sender_id, hash_lock)
+    }
+
+    #[inline]
+    fn iter_htlc_hash_locks(
+        &self,
+        sender_id: &AccountIdRef,
+    ) -> impl Iterator<Item = CryptoHash> + '_ {
+        self.state.iter_htlc_hash_locks(sender_id)
+    }
 }
 
 impl<S> State for Deltas<S>
@@ -104,18 +302,156 @@ where
         self.state.remove_public_key(account_id, public_key)
     }
 
+    #[inline]
+    fn set_public_key_scope(
+        &mut self,
+        account_id: AccountId,
+        public_key: PublicKey,
+        scope: PublicKeyScope,
+    ) {
+        self.state
+            .set_public_key_scope(account_id, public_key, scope);
+    }
+
+    #[inline]
+    fn set_threshold(&mut self, account_id: AccountId, threshold: u16) {
+        self.state.set_threshold(account_id, threshold);
+    }
+
+    #[inline]
+    fn set_lockdown(&mut self, account_id: AccountId, lockdown: Lockdown) {
+        self.state.set_lockdown(account_id, lockdown);
+    }
+
+    #[must_use]
+    #[inline]
+    fn lock_account(&mut self, account_id: AccountId) -> bool {
+        self.state.lock_account(account_id)
+    }
+
+    #[inline]
+    fn set_guardians(&mut self, account_id: AccountId, guardians: Guardians) {
+        self.state.set_guardians(account_id, guardians);
+    }
+
+    #[inline]
+    fn approve_recovery(
+        &mut self,
+        account_id: AccountId,
+        guardian_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<()> {
+        self.state
+            .approve_recovery(account_id, guardian_id, public_key)
+    }
+
+    #[must_use]
+    #[inline]
+    fn cancel_recovery(&mut self, account_id: AccountId) -> bool {
+        self.state.cancel_recovery(account_id)
+    }
+
+    #[inline]
+    fn close_account(&mut self, account_id: AccountId, receiver_id: AccountId) -> Result<()> {
+        self.state.close_account(account_id, receiver_id)
+    }
+
     #[must_use]
     #[inline]
     fn commit_nonce(&mut self, account_id: AccountId, nonce: Nonce) -> bool {
         self.state.commit_nonce(account_id, nonce)
     }
 
+    #[must_use]
+    #[inline]
+    fn place_limit_order(
+        &mut self,
+        maker_id: AccountId,
+        order_hash: CryptoHash,
+        order: LimitOrderState,
+    ) -> bool {
+        self.state.place_limit_order(maker_id, order_hash, order)
+    }
+
+    #[inline]
+    fn fill_limit_order(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+        amount_in: u128,
+    ) -> Result<LimitOrderState> {
+        self.state.fill_limit_order(maker_id, order_hash, amount_in)
+    }
+
+    #[must_use]
+    #[inline]
+    fn cancel_limit_order(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash) -> bool {
+        self.state.cancel_limit_order(maker_id, order_hash)
+    }
+
+    fn lock_htlc(&mut self, hash_lock: CryptoHash, htlc: Htlc) -> Result<()> {
+        let sender_id = htlc.sender_id.clone();
+        let tokens = htlc.tokens.clone();
+        self.check_withdraw(&sender_id, tokens.clone())?;
+        self.state.lock_htlc(hash_lock, htlc)?;
+
+        // escrow is represented by the verifying contract in transfers
+        let escrow_id = self.verifying_contract().into_owned();
+        for (token_id, amount) in tokens {
+            if !(self
+                .deltas
+                .withdraw(sender_id.clone(), token_id.clone(), amount)
+                && self.deltas.deposit(escrow_id.clone(), token_id, amount))
+            {
+                return Err(DefuseError::BalanceOverflow);
+            }
+        }
+        Ok(())
+    }
+
+    fn release_htlc(
+        &mut self,
+        sender_id: &AccountIdRef,
+        hash_lock: &CryptoHash,
+        receiver_id: AccountId,
+    ) -> Result<Htlc> {
+        let htlc = self
+            .state
+            .release_htlc(sender_id, hash_lock, receiver_id.clone())?;
+
+        let escrow_id = self.verifying_contract().into_owned();
+        for (token_id, amount) in htlc.tokens.iter() {
+            self.check_deposit(&receiver_id, token_id)?;
+            if !(self
+                .deltas
+                .withdraw(escrow_id.clone(), token_id.clone(), *amount)
+                && self
+                    .deltas
+                    .deposit(receiver_id.clone(), token_id.clone(), *amount))
+            {
+                return Err(DefuseError::BalanceOverflow);
+            }
+        }
+        Ok(htlc)
+    }
+
+    #[inline]
+    fn record_fees_collected(
+        &mut self,
+        fees_collected: &TokenAmounts,
+        referral_fees: Option<(&AccountIdRef, &TokenAmounts)>,
+    ) -> Result<()> {
+        self.state
+            .record_fees_collected(fees_collected, referral_fees)
+    }
+
     fn internal_deposit(
         &mut self,
         owner_id: AccountId,
         tokens: impl IntoIterator<Item = (TokenId, u128)>,
     ) -> Result<()> {
         for (token_id, amount) in tokens {
+            self.check_deposit(&owner_id, &token_id)?;
             self.state
                 .internal_deposit(owner_id.clone(), [(token_id.clone(), amount)])?;
             if !self.deltas.deposit(owner_id.clone(), token_id, amount) {
@@ -131,6 +467,7 @@ where
         tokens: impl IntoIterator<Item = (TokenId, u128)>,
     ) -> Result<()> {
         for (token_id, amount) in tokens {
+            self.check_withdraw(owner_id, [(token_id.clone(), amount)])?;
             self.state
                 .internal_withdraw(owner_id, [(token_id.clone(), amount)])?;
             if !self.deltas.withdraw(owner_id.to_owned(), token_id, amount) {
@@ -142,49 +479,207 @@ where
 
     #[inline]
     fn ft_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: FtWithdraw) -> Result<()> {
+        let wnear = TokenId::Nep141(self.wnear_id().into_owned());
+        self.check_withdraw(
+            owner_id,
+            iter::once((TokenId::Nep141(withdraw.token.clone()), withdraw.amount.0)).chain(
+                withdraw
+                    .storage_deposit
+                    .map(|amount| (wnear, amount.as_yoctonear())),
+            ),
+        )?;
         self.state.ft_withdraw(owner_id, withdraw)
     }
 
     #[inline]
     fn nft_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: NftWithdraw) -> Result<()> {
+        let wnear = TokenId::Nep141(self.wnear_id().into_owned());
+        self.check_withdraw(
+            owner_id,
+            iter::once((
+                TokenId::Nep171(withdraw.token.clone(), withdraw.token_id.clone()),
+                1,
+            ))
+            .chain(
+                withdraw
+                    .storage_deposit
+                    .map(|amount| (wnear, amount.as_yoctonear())),
+            ),
+        )?;
         self.state.nft_withdraw(owner_id, withdraw)
     }
 
     #[inline]
     fn mt_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: MtWithdraw) -> Result<()> {
+        let wnear = TokenId::Nep141(self.wnear_id().into_owned());
+        self.check_withdraw(
+            owner_id,
+            withdraw
+                .token_ids
+                .iter()
+                .map(|token_id| TokenId::Nep245(withdraw.token.clone(), token_id.clone()))
+                .zip(withdraw.amounts.iter().map(|a| a.0))
+                .chain(
+                    withdraw
+                        .storage_deposit
+                        .map(|amount| (wnear, amount.as_yoctonear())),
+                ),
+        )?;
         self.state.mt_withdraw(owner_id, withdraw)
     }
 
     #[inline]
     fn native_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: NativeWithdraw) -> Result<()> {
+        let wnear = TokenId::Nep141(self.wnear_id().into_owned());
+        self.check_withdraw(owner_id, [(wnear, withdraw.amount.as_yoctonear())])?;
         self.state.native_withdraw(owner_id, withdraw)
     }
+
+    #[inline]
+    fn storage_deposit(&mut self, owner_id: &AccountIdRef, deposit: StorageDeposit) -> Result<()> {
+        let wnear = TokenId::Nep141(self.wnear_id().into_owned());
+        self.check_withdraw(owner_id, [(wnear, deposit.amount.as_yoctonear())])?;
+        self.state.storage_deposit(owner_id, deposit)
+    }
+
+    #[inline]
+    fn call(&mut self, owner_id: &AccountIdRef, call: Call) -> Result<()> {
+        self.check_withdraw(
+            owner_id,
+            call.tokens
+                .iter()
+                .map(|(token_id, amount)| (token_id.clone(), *amount)),
+        )?;
+        self.state.call(owner_id, call)
+    }
+}
+
+/// Restricts balance changes of a single account according to scopes
+/// of public keys the payload was signed with
+#[derive(Debug)]
+pub(crate) struct ScopeGuard {
+    owner_id: AccountId,
+    scopes: Vec<PublicKeyScope>,
+    spent: TokenAmounts<HashMap<TokenId, u128>>,
+}
+
+impl ScopeGuard {
+    #[inline]
+    fn new(owner_id: AccountId, scopes: Vec<PublicKeyScope>) -> Self {
+        Self {
+            owner_id,
+            scopes,
+            spent: TokenAmounts::default(),
+        }
+    }
+
+    fn check_deposit(&self, owner_id: &AccountIdRef, token_id: &TokenId) -> Result<()> {
+        if owner_id != self.owner_id {
+            return Ok(());
+        }
+        if !self
+            .scopes
+            .iter()
+            .all(|scope| scope.is_token_allowed(token_id))
+        {
+            return Err(DefuseError::PublicKeyScopeViolated);
+        }
+        Ok(())
+    }
+
+    fn check_withdraw(
+        &mut self,
+        owner_id: &AccountIdRef,
+        token_id: TokenId,
+        amount: u128,
+    ) -> Result<()> {
+        if owner_id != self.owner_id {
+            return Ok(());
+        }
+        let spent = self
+            .spent
+            .deposit(token_id.clone(), amount)
+            .ok_or(DefuseError::BalanceOverflow)?;
+        if !self
+            .scopes
+            .iter()
+            .all(|scope| scope.is_amount_allowed(&token_id, spent))
+        {
+            return Err(DefuseError::PublicKeyScopeViolated);
+        }
+        Ok(())
+    }
 }
 
 /// Accumulates internal deposits and withdrawals on different tokens
 /// to match transfers using `.finalize()`
 #[derive(Debug, Default)]
-pub struct TransferMatcher(HashMap<TokenId, TokenTransferMatcher>);
+pub struct TransferMatcher {
+    tokens: HashMap<TokenId, TokenTransferMatcher>,
+    /// Deposited and withdrawn amounts modified since the last
+    /// checkpoint as they were before
+    journal: Option<HashMap<(TokenId, AccountId), [u128; 2]>>,
+}
 
 impl TransferMatcher {
     #[inline]
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self::default()
     }
 
     #[inline]
     pub fn deposit(&mut self, owner_id: AccountId, token_id: TokenId, amount: u128) -> bool {
-        self.0.entry_or_default(token_id).deposit(owner_id, amount)
+        self.record(&owner_id, &token_id);
+        self.tokens
+            .entry_or_default(token_id)
+            .deposit(owner_id, amount)
     }
 
     #[inline]
     pub fn withdraw(&mut self, owner_id: AccountId, token_id: TokenId, amount: u128) -> bool {
-        self.0.entry_or_default(token_id).withdraw(owner_id, amount)
+        self.record(&owner_id, &token_id);
+        self.tokens
+            .entry_or_default(token_id)
+            .withdraw(owner_id, amount)
     }
 
     #[inline]
     pub fn add_delta(&mut self, owner_id: AccountId, token_id: TokenId, delta: i128) -> bool {
-        self.0.entry_or_default(token_id).add_delta(owner_id, delta)
+        self.record(&owner_id, &token_id);
+        self.tokens
+            .entry_or_default(token_id)
+            .add_delta(owner_id, delta)
+    }
+
+    /// Starts recording changes, so that they can be discarded with
+    /// [`.rollback()`](Self::rollback). Changes made before are kept.
+    #[inline]
+    pub fn checkpoint(&mut self) {
+        self.journal = Some(HashMap::new());
+    }
+
+    /// Discards changes made since the last
+    /// [`.checkpoint()`](Self::checkpoint)
+    pub fn rollback(&mut self) {
+        for ((token_id, owner_id), amounts) in self.journal.take().into_iter().flatten() {
+            self.tokens
+                .entry_or_default(token_id)
+                .restore(owner_id, amounts);
+        }
+    }
+
+    fn record(&mut self, owner_id: &AccountId, token_id: &TokenId) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        journal
+            .entry((token_id.clone(), owner_id.clone()))
+            .or_insert_with(|| {
+                self.tokens
+                    .get(token_id)
+                    .map(|matcher| matcher.amounts_of(owner_id))
+                    .unwrap_or_default()
+            });
     }
 
     // Finalizes all transfers, or returns unmatched deltas.
@@ -192,7 +687,7 @@ impl TransferMatcher {
     pub fn finalize(self) -> Result<Transfers, InvariantViolated> {
         let mut transfers = Transfers::default();
         let mut deltas = TokenDeltas::default();
-        for (token_id, transfer_matcher) in self.0 {
+        for (token_id, transfer_matcher) in self.tokens {
             if let Err(unmatched) = transfer_matcher.finalize_into(&token_id, &mut transfers) {
                 if unmatched == 0 || deltas.add_delta(token_id, unmatched).is_none() {
                     return Err(InvariantViolated::Overflow);
@@ -238,6 +733,26 @@ impl TokenTransferMatcher {
         }
     }
 
+    /// Deposited and withdrawn amounts of `owner_id`
+    #[inline]
+    fn amounts_of(&self, owner_id: &AccountId) -> [u128; 2] {
+        [&self.deposits, &self.withdrawals].map(|amounts| amounts.balance_of(owner_id))
+    }
+
+    /// Sets amounts previously returned by [`.amounts_of()`](Self::amounts_of)
+    fn restore(&mut self, owner_id: AccountId, [deposited, withdrawn]: [u128; 2]) {
+        for (amounts, amount) in [
+            (&mut self.deposits, deposited),
+            (&mut self.withdrawals, withdrawn),
+        ] {
+            let current = amounts.balance_of(&owner_id);
+            amounts
+                .withdraw(owner_id.clone(), current)
+                .and_then(|_| amounts.deposit(owner_id.clone(), amount))
+                .unwrap_or_else(|| unreachable!());
+        }
+    }
+
     fn sub_add(
         sub: &mut AccountAmounts,
         add: &mut AccountAmounts,
@@ -421,6 +936,8 @@ impl InvariantViolated {
 #[cfg(test)]
 #[allow(clippy::many_single_char_names)]
 mod tests {
+    use crate::intents::IntentKind;
+
     use super::*;
 
     #[test]
@@ -515,4 +1032,56 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_rollback() {
+        let [a, b, c]: [AccountId; 3] =
+            ["a", "b", "c"].map(|s| format!("{s}.near").parse().unwrap());
+        let [ft1, ft2] =
+            ["ft1", "ft2"].map(|a| TokenId::Nep141(format!("{a}.near").parse().unwrap()));
+
+        let mut expected = TransferMatcher::default();
+        assert!(expected.add_delta(a.clone(), ft1.clone(), -5));
+        assert!(expected.add_delta(b.clone(), ft1.clone(), 5));
+
+        let mut deltas = TransferMatcher::default();
+        assert!(deltas.add_delta(a.clone(), ft1.clone(), -5));
+        deltas.checkpoint();
+        assert!(deltas.add_delta(a.clone(), ft1.clone(), 7));
+        assert!(deltas.add_delta(c, ft1.clone(), -2));
+        assert!(deltas.add_delta(b.clone(), ft2, 1));
+        deltas.rollback();
+        assert!(deltas.add_delta(b, ft1, 5));
+
+        assert_eq!(deltas.finalize().unwrap(), expected.finalize().unwrap());
+    }
+
+    #[test]
+    fn test_scope_guard() {
+        let [a, b]: [AccountId; 2] = ["a", "b"].map(|s| format!("{s}.near").parse().unwrap());
+        let [ft1, ft2, ft3] =
+            ["ft1", "ft2", "ft3"].map(|a| TokenId::Nep141(format!("{a}.near").parse().unwrap()));
+
+        let mut guard = ScopeGuard::new(
+            a.clone(),
+            vec![PublicKeyScope {
+                intents: [IntentKind::TokenDiff].into(),
+                tokens: Some([ft1.clone(), ft2.clone()].into()),
+                max_amounts: TokenAmounts::new([(ft1.clone(), 10)].into()),
+                expires_at: Deadline::MAX,
+            }],
+        );
+
+        guard.check_deposit(&a, &ft2).unwrap();
+        assert!(guard.check_deposit(&a, &ft3).is_err());
+        // other accounts are not restricted
+        guard.check_deposit(&b, &ft3).unwrap();
+        guard.check_withdraw(&b, ft1.clone(), 100).unwrap();
+
+        guard.check_withdraw(&a, ft1.clone(), 6).unwrap();
+        guard.check_withdraw(&a, ft1.clone(), 4).unwrap();
+        assert!(guard.check_withdraw(&a, ft1.clone(), 1).is_err());
+        guard.check_withdraw(&a, ft2.clone(), 1000).unwrap();
+        assert!(guard.check_withdraw(&a, ft3, 1).is_err());
+    }
 }
@@ -1,18 +1,23 @@
 pub mod cached;
 pub mod deltas;
 
-use std::{borrow::Cow, iter};
+use std::{borrow::Cow, collections::BTreeMap, iter};
 
 use cached::CachedState;
 use defuse_crypto::PublicKey;
 use impl_tools::autoimpl;
-use near_sdk::{AccountId, AccountIdRef};
+use near_sdk::{AccountId, AccountIdRef, CryptoHash};
 
 use crate::{
-    fees::Pips,
-    intents::tokens::{FtWithdraw, MtWithdraw, NativeWithdraw, NftWithdraw},
-    tokens::TokenId,
-    DefuseError, Nonce, Result,
+    accounts::{Guardians, Lockdown, PublicKeyScope, Recovery},
+    fees::{FeeMode, Pips},
+    intents::{
+        htlc::Htlc,
+        limit_order::LimitOrderState,
+        tokens::{Call, FtWithdraw, MtWithdraw, NativeWithdraw, NftWithdraw, StorageDeposit},
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline, DefuseError, Nonce, Result,
 };
 
 #[autoimpl(for<T: trait + ?Sized> &T, &mut T, Box<T>)]
@@ -21,17 +26,77 @@ pub trait StateView {
     fn wnear_id(&self) -> Cow<'_, AccountIdRef>;
 
     fn fee(&self) -> Pips;
+    /// Effective fee taken on `token_in` when it's swapped for `token_out`
+    fn fee_for(&self, token_in: &TokenId, token_out: Option<&TokenId>) -> Pips;
     fn fee_collector(&self) -> Cow<'_, AccountIdRef>;
+    /// Additional fee collectors with their shares of collected fees
+    fn fee_collector_shares(&self) -> Cow<'_, BTreeMap<AccountId, Pips>>;
+    /// Share of collected fees to be paid to referral
+    fn referral_fee(&self) -> Pips;
+    /// Side of `TokenDiff` intents to charge fees on
+    fn fee_mode(&self) -> FeeMode;
+    /// Share of protocol fee waived for given account
+    fn fee_discount(&self, account_id: &AccountIdRef) -> Pips;
 
     #[must_use]
     fn has_public_key(&self, account_id: &AccountIdRef, public_key: &PublicKey) -> bool;
     fn iter_public_keys(&self, account_id: &AccountIdRef) -> impl Iterator<Item = PublicKey> + '_;
+    /// Returns `None` if the key has full access to the account
+    fn public_key_scope(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<PublicKeyScope>;
+    /// Returns when the public key was added to the account, `None` for
+    /// keys added before it was tracked or implicit keys never removed
+    fn public_key_added_at(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<Deadline>;
+
+    /// Minimum number of distinct public keys required to sign
+    /// intents on behalf of the account
+    #[must_use]
+    fn threshold(&self, account_id: &AccountIdRef) -> u16;
+
+    /// Whether the account can receive deposits
+    #[must_use]
+    fn is_account_registered(&self, account_id: &AccountIdRef) -> bool;
+
+    /// Locked accounts can't sign intents or withdraw tokens,
+    /// but can still receive deposits
+    #[must_use]
+    fn is_account_locked(&self, account_id: &AccountIdRef) -> bool;
+
+    /// Returns the latest lockdown of the account, which might
+    /// have already expired
+    fn lockdown(&self, account_id: &AccountIdRef) -> Option<Lockdown>;
+
+    fn guardians(&self, account_id: &AccountIdRef) -> Option<Guardians>;
+    /// Returns pending recovery of the account, if any
+    fn recovery(&self, account_id: &AccountIdRef) -> Option<Recovery>;
 
     #[must_use]
     fn is_nonce_used(&self, account_id: &AccountIdRef, nonce: Nonce) -> bool;
 
     #[must_use]
     fn balance_of(&self, account_id: &AccountIdRef, token_id: &TokenId) -> u128;
+    /// Tokens the account has non-zero balances of
+    fn iter_token_ids(&self, account_id: &AccountIdRef) -> impl Iterator<Item = TokenId> + '_;
+
+    fn limit_order(
+        &self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+    ) -> Option<LimitOrderState>;
+
+    fn htlc(&self, sender_id: &AccountIdRef, hash_lock: &CryptoHash) -> Option<Htlc>;
+    /// Hash locks of pending HTLCs sent by the account
+    fn iter_htlc_hash_locks(
+        &self,
+        sender_id: &AccountIdRef,
+    ) -> impl Iterator<Item = CryptoHash> + '_;
 
     #[inline]
     fn cached(self) -> CachedState<Self>
@@ -48,10 +113,80 @@ pub trait State: StateView {
     fn add_public_key(&mut self, account_id: AccountId, public_key: PublicKey) -> bool;
     #[must_use]
     fn remove_public_key(&mut self, account_id: AccountId, public_key: PublicKey) -> bool;
+    fn set_public_key_scope(
+        &mut self,
+        account_id: AccountId,
+        public_key: PublicKey,
+        scope: PublicKeyScope,
+    );
+
+    fn set_threshold(&mut self, account_id: AccountId, threshold: u16);
+
+    fn set_lockdown(&mut self, account_id: AccountId, lockdown: Lockdown);
+
+    /// Returns `false` if the account was already locked
+    #[must_use]
+    fn lock_account(&mut self, account_id: AccountId) -> bool;
+
+    /// Empty `guardians` removes them. Cancels pending recovery, if any.
+    fn set_guardians(&mut self, account_id: AccountId, guardians: Guardians);
+    fn approve_recovery(
+        &mut self,
+        account_id: AccountId,
+        guardian_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<()>;
+    /// Returns `false` if there was no pending recovery
+    #[must_use]
+    fn cancel_recovery(&mut self, account_id: AccountId) -> bool;
+
+    /// Removes the account along with its nonces, limit orders and
+    /// approvals. All its token balances must be zero and no HTLCs
+    /// can be pending. Reclaimable storage deposit is refunded to
+    /// `receiver_id`.
+    fn close_account(&mut self, account_id: AccountId, receiver_id: AccountId) -> Result<()>;
 
     #[must_use]
     fn commit_nonce(&mut self, account_id: AccountId, nonce: Nonce) -> bool;
 
+    /// Returns `false` if the order with given hash already exists
+    #[must_use]
+    fn place_limit_order(
+        &mut self,
+        maker_id: AccountId,
+        order_hash: CryptoHash,
+        order: LimitOrderState,
+    ) -> bool;
+    /// Fills given `amount_in` of the order and removes it if it
+    /// gets fully filled. Returns state of the order after the fill.
+    fn fill_limit_order(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+        amount_in: u128,
+    ) -> Result<LimitOrderState>;
+    #[must_use]
+    fn cancel_limit_order(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash) -> bool;
+
+    /// Withdraws `htlc.tokens` from its sender into the escrow.
+    /// Fails if the sender already has HTLC with given hash lock.
+    fn lock_htlc(&mut self, hash_lock: CryptoHash, htlc: Htlc) -> Result<()>;
+    /// Removes HTLC and deposits its tokens to `receiver_id`
+    fn release_htlc(
+        &mut self,
+        sender_id: &AccountIdRef,
+        hash_lock: &CryptoHash,
+        receiver_id: AccountId,
+    ) -> Result<Htlc>;
+
+    /// Accumulate cumulative statistics of fees collected on `TokenDiff`
+    /// intents, including `referral_fees` paid to `referral`
+    fn record_fees_collected(
+        &mut self,
+        fees_collected: &TokenAmounts,
+        referral_fees: Option<(&AccountIdRef, &TokenAmounts)>,
+    ) -> Result<()>;
+
     fn internal_deposit(
         &mut self,
         owner_id: AccountId,
@@ -137,4 +272,18 @@ pub trait State: StateView {
             )],
         )
     }
+
+    fn storage_deposit(&mut self, owner_id: &AccountIdRef, deposit: StorageDeposit) -> Result<()> {
+        self.internal_withdraw(
+            owner_id,
+            [(
+                TokenId::Nep141(self.wnear_id().into_owned()),
+                deposit.amount.as_yoctonear(),
+            )],
+        )
+    }
+
+    fn call(&mut self, owner_id: &AccountIdRef, call: Call) -> Result<()> {
+        self.internal_withdraw(owner_id, call.tokens)
+    }
 }
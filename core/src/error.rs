@@ -10,18 +10,63 @@ pub enum DefuseError {
     #[error("account not found")]
     AccountNotFound,
 
+    #[error("account is locked")]
+    AccountLocked,
+
+    #[error("account is not registered, see `storage_deposit()`")]
+    AccountNotRegistered,
+
+    #[error("account has non-zero token balances or pending HTLCs")]
+    AccountNotEmpty,
+
+    #[error("amount exceeds approved one")]
+    ApprovalExceeded,
+
+    #[error("approval not found")]
+    ApprovalNotFound,
+
     #[error("insufficient balance or overflow")]
     BalanceOverflow,
 
+    #[error("condition is not met")]
+    ConditionNotMet,
+
     #[error("deadline has expired")]
     DeadlineExpired,
 
+    #[error("fee mode has changed since the intent was signed")]
+    FeeModeChanged,
+
+    #[error("HTLC with given hash lock already exists")]
+    HtlcExists,
+
+    #[error("HTLC not found")]
+    HtlcNotFound,
+
+    #[error("HTLC has not expired yet")]
+    HtlcNotExpired,
+
+    #[error("not enough gas left to execute the call and resolve it")]
+    InsufficientGas,
+
+    #[error("not enough signatures to reach the threshold")]
+    InsufficientSignatures,
+
+    #[error("insufficient storage deposit")]
+    InsufficientStorageDeposit,
+
+    #[error("guardians must not include the account and threshold must not exceed their number")]
+    InvalidGuardians,
+
     #[error("invalid intent")]
     InvalidIntent,
 
     #[error("invalid signature")]
     InvalidSignature,
 
+    #[error("threshold must be positive and not exceed the number of public keys")]
+    InvalidThreshold,
+
     #[error(
         "invariant violated: {}",
         serde_json::to_string(.0).unwrap_or_else(|_| unreachable!())
@@ -31,18 +76,51 @@ pub enum DefuseError {
     #[error("JSON: {0}")]
     JSON(#[from] serde_json::Error),
 
+    #[error("limit order already exists")]
+    LimitOrderExists,
+
+    #[error("limit order not found")]
+    LimitOrderNotFound,
+
+    #[error("limit order overfilled")]
+    LimitOrderOverfilled,
+
+    #[error("all signatures of a multisig payload must use the same standard")]
+    MixedPayloadStandards,
+
     #[error("nonce was already used")]
     NonceUsed,
 
+    #[error("not a guardian of the account")]
+    NotGuardian,
+
     #[error("public key already exists")]
     PublicKeyExists,
 
     #[error("public key doesn't exist")]
     PublicKeyNotExist,
 
+    #[error("public key is disabled during account lockdown")]
+    PublicKeyLockedDown,
+
+    #[error("not allowed by public key scope")]
+    PublicKeyScopeViolated,
+
+    #[error("public key was added too recently")]
+    PublicKeyTooRecent,
+
     #[error("token_id: {0}")]
     ParseTokenId(#[from] ParseTokenIdError),
 
+    #[error("recovery not found")]
+    RecoveryNotFound,
+
+    #[error("recovery is not scheduled or its delay hasn't passed yet")]
+    RecoveryNotReady,
+
+    #[error("recovery is already scheduled")]
+    RecoveryScheduled,
+
     #[error("wrong verifying_contract")]
     WrongVerifyingContract,
 }
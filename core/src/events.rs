@@ -4,9 +4,21 @@ use derive_more::derive::From;
 use near_sdk::{near, serde::Deserialize};
 
 use crate::{
-    accounts::{AccountEvent, PublicKeyEvent},
-    fees::{FeeChangedEvent, FeeCollectorChangedEvent},
-    intents::{token_diff::TokenDiffEvent, tokens::Transfer, IntentEvent},
+    accounts::{
+        AccountEvent, Guardians, Lockdown, PublicKeyEvent, RecoveryApprovedEvent,
+        ScheduledRecovery, ThresholdChangedEvent,
+    },
+    fees::{
+        FeeChangedEvent, FeeCollectorChangedEvent, FeeDiscountChangedEvent, FeeModeChangedEvent,
+        PairFeeChangedEvent, TokenFeeChangedEvent,
+    },
+    intents::{
+        htlc::{HtlcClaimedEvent, HtlcLock, HtlcRefund},
+        limit_order::LimitOrder,
+        token_diff::{ReferralNotRegisteredEvent, TokenDiffEvent},
+        tokens::{BatchTransfer, Transfer},
+        IntentEvent,
+    },
 };
 
 #[must_use = "make sure to `.emit()` this event"]
@@ -19,17 +31,71 @@ pub enum DefuseEvent<'a> {
     #[event_version("0.2.1")]
     #[from(skip)]
     PublicKeyRemoved(AccountEvent<'a, PublicKeyEvent<'a>>),
+    #[event_version("0.2.1")]
+    ThresholdChanged(AccountEvent<'a, ThresholdChangedEvent>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    AccountLocked(AccountEvent<'a, ()>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    AccountUnlocked(AccountEvent<'a, ()>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    AccountLockedDown(AccountEvent<'a, Lockdown>),
+    #[event_version("0.2.1")]
+    GuardiansChanged(AccountEvent<'a, Guardians>),
+    #[event_version("0.2.1")]
+    RecoveryApproved(AccountEvent<'a, RecoveryApprovedEvent<'a>>),
+    #[event_version("0.2.1")]
+    RecoveryScheduled(AccountEvent<'a, ScheduledRecovery>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    RecoveryCancelled(AccountEvent<'a, ()>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    AccountRecovered(AccountEvent<'a, PublicKeyEvent<'a>>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    AccountClosed(AccountEvent<'a, ()>),
 
     #[event_version("0.2.1")]
     FeeChanged(FeeChangedEvent),
     #[event_version("0.2.1")]
+    #[from(skip)]
+    ReferralFeeChanged(FeeChangedEvent),
+    #[event_version("0.2.1")]
+    TokenFeeChanged(TokenFeeChangedEvent),
+    #[event_version("0.2.1")]
+    PairFeeChanged(PairFeeChangedEvent),
+    #[event_version("0.2.1")]
+    FeeDiscountChanged(AccountEvent<'a, FeeDiscountChangedEvent>),
+    #[event_version("0.2.1")]
+    FeeModeChanged(FeeModeChangedEvent),
+    #[event_version("0.2.1")]
     FeeCollectorChanged(FeeCollectorChangedEvent<'a>),
 
     #[event_version("0.2.1")]
     Transfer(Cow<'a, [IntentEvent<AccountEvent<'a, Cow<'a, Transfer>>>]>),
+    #[event_version("0.2.1")]
+    BatchTransfer(Cow<'a, [IntentEvent<AccountEvent<'a, Cow<'a, BatchTransfer>>>]>),
 
     #[event_version("0.2.1")]
     TokenDiff(Cow<'a, [IntentEvent<AccountEvent<'a, TokenDiffEvent<'a>>>]>),
+    #[event_version("0.2.1")]
+    ReferralNotRegistered(Cow<'a, [IntentEvent<AccountEvent<'a, ReferralNotRegisteredEvent>>]>),
+
+    #[event_version("0.2.1")]
+    LimitOrderPlaced(Cow<'a, [IntentEvent<AccountEvent<'a, Cow<'a, LimitOrder>>>]>),
+    #[event_version("0.2.1")]
+    #[from(skip)]
+    LimitOrderCancelled(Cow<'a, [IntentEvent<AccountEvent<'a, ()>>]>),
+
+    #[event_version("0.2.1")]
+    HtlcLocked(Cow<'a, [IntentEvent<AccountEvent<'a, Cow<'a, HtlcLock>>>]>),
+    #[event_version("0.2.1")]
+    HtlcClaimed(Cow<'a, [IntentEvent<AccountEvent<'a, HtlcClaimedEvent>>]>),
+    #[event_version("0.2.1")]
+    HtlcRefunded(Cow<'a, [IntentEvent<AccountEvent<'a, Cow<'a, HtlcRefund>>>]>),
 
     #[event_version("0.2.1")]
     IntentsExecuted(Cow<'a, [IntentEvent<AccountEvent<'a, ()>>]>),
@@ -1,20 +1,257 @@
 use core::{
+    cmp::Ordering,
     fmt::{self, Display},
     ops::{Add, Div, Mul, Not, Sub},
 };
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 
+use defuse_map_utils::Map;
 use defuse_num_utils::{CheckedAdd, CheckedMulDiv, CheckedSub};
+use impl_tools::autoimpl;
 use near_sdk::{near, AccountId, AccountIdRef};
+use serde_with::serde_as;
 use thiserror::Error as ThisError;
 
+use crate::tokens::{TokenAmounts, TokenId};
+
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
 #[near(serializers = [borsh, json])]
 #[derive(Debug, Clone)]
 pub struct FeesConfig {
     pub fee: Pips,
     pub fee_collector: AccountId,
+
+    /// Additional fee collectors with their shares of collected fees.
+    /// The rest, including rounding remainders, goes to `fee_collector`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fee_collector_shares: BTreeMap<AccountId, Pips>,
+
+    /// Share of collected fees to be paid to `referral`
+    /// specified in `TokenDiff` intents
+    #[serde(default)]
+    pub referral_fee: Pips,
+
+    /// Side of `TokenDiff` intents to charge fees on
+    #[serde(default)]
+    pub fee_mode: FeeMode,
+}
+
+impl FeesConfig {
+    /// Returns [`TokenFees`] with given `overrides` of [`Self::fee`]
+    #[inline]
+    pub const fn with_overrides<'a, T, P>(
+        &'a self,
+        overrides: &'a FeeOverrides<T, P>,
+    ) -> impl TokenFees + 'a
+    where
+        T: Map<K = TokenId, V = Pips>,
+        P: Map<K = TokenPair, V = Pips>,
+    {
+        WithOverrides {
+            config: self,
+            overrides,
+        }
+    }
+
+    /// Returns whether `fee_collector_shares` are non-zero, do not
+    /// include `fee_collector` itself and sum up to at most [`Pips::MAX`]
+    #[must_use]
+    pub fn are_fee_collector_shares_valid(&self) -> bool {
+        !self.fee_collector_shares.contains_key(&self.fee_collector)
+            && self
+                .fee_collector_shares
+                .values()
+                .try_fold(Pips::ZERO, |total, share| {
+                    if share.is_zero() {
+                        return None;
+                    }
+                    total.checked_add(*share)
+                })
+                .is_some()
+    }
+}
+
+/// Per-token and per-pair overrides of [`FeesConfig::fee`]
+#[near(serializers = [borsh])]
+#[derive(Debug, Clone, Default)]
+pub struct FeeOverrides<T = BTreeMap<TokenId, Pips>, P = BTreeMap<TokenPair, Pips>> {
+    /// Per-token fee overrides, take precedence over default fee
+    pub token_fees: T,
+
+    /// Per-pair fee overrides, take precedence over `token_fees`.
+    /// Applied only to `TokenDiff` intents swapping exactly one token
+    /// for another.
+    pub pair_fees: P,
+}
+
+impl<T, P> FeeOverrides<T, P>
+where
+    T: Map<K = TokenId, V = Pips>,
+    P: Map<K = TokenPair, V = Pips>,
+{
+    /// Returns fee override for `token_in` when it's swapped
+    /// for `token_out`, if any
+    #[must_use]
+    pub fn fee_for(&self, token_in: &TokenId, token_out: Option<&TokenId>) -> Option<Pips> {
+        token_out
+            .and_then(|token_out| TokenPair::new(token_in.clone(), token_out.clone()))
+            .and_then(|pair| self.pair_fees.get(&pair))
+            .or_else(|| self.token_fees.get(token_in))
+            .copied()
+    }
+}
+
+struct WithOverrides<'a, T, P> {
+    config: &'a FeesConfig,
+    overrides: &'a FeeOverrides<T, P>,
+}
+
+impl<T, P> TokenFees for WithOverrides<'_, T, P>
+where
+    T: Map<K = TokenId, V = Pips>,
+    P: Map<K = TokenPair, V = Pips>,
+{
+    #[inline]
+    fn token_fee(&self, token_id: &TokenId, counterpart: Option<&TokenId>) -> Pips {
+        self.overrides
+            .fee_for(token_id, counterpart)
+            .unwrap_or(self.config.fee)
+    }
+
+    #[inline]
+    fn fee_mode(&self) -> FeeMode {
+        self.config.fee_mode
+    }
+}
+
+/// Splits `fees` among collectors according to their `shares`.
+/// Amounts are rounded down, so the remainder returned along
+/// with the splits should go to the primary fee collector.
+pub fn split_fees(
+    shares: impl IntoIterator<Item = (AccountId, Pips)>,
+    fees: TokenAmounts,
+) -> Option<(Vec<(AccountId, TokenAmounts)>, TokenAmounts)> {
+    let mut remainder = fees.clone();
+    let mut splits = Vec::new();
+    for (collector, share) in shares {
+        let amounts = TokenAmounts::default().with_deposit_many(
+            fees.iter()
+                .map(|(token_id, amount)| (token_id.clone(), share.fee(*amount))),
+        )?;
+        // skip collectors with nothing to receive
+        if amounts.is_empty() {
+            continue;
+        }
+        remainder = remainder.with_withdraw_many(amounts.clone())?;
+        splits.push((collector, amounts));
+    }
+    Some((splits, remainder))
+}
+
+/// Side of `TokenDiff` intents to charge fees on
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeeMode {
+    /// Fees are charged on top of negative deltas (i.e. `token_in`)
+    #[default]
+    TokenIn,
+    /// Fees are deducted from positive deltas (i.e. `token_out`),
+    /// so that signer receives net-of-fee amount
+    TokenOut,
 }
 
+impl FeeMode {
+    /// Returns whether fee should be charged on given delta
+    #[must_use]
+    #[inline]
+    pub const fn is_charged_on(self, delta: i128) -> bool {
+        match self {
+            Self::TokenIn => delta < 0,
+            Self::TokenOut => delta > 0,
+        }
+    }
+}
+
+/// Fees to be taken on `token_in`, or `token_out`
+/// depending on [`fee_mode`](TokenFees::fee_mode)
+#[autoimpl(for<T: trait + ?Sized> &T, Box<T>)]
+pub trait TokenFees {
+    /// Fee taken on `token_id` when it's swapped for `counterpart`,
+    /// which is known only for `TokenDiff` swapping exactly one token
+    /// for another
+    fn token_fee(&self, token_id: &TokenId, counterpart: Option<&TokenId>) -> Pips;
+
+    #[inline]
+    fn fee_mode(&self) -> FeeMode {
+        FeeMode::TokenIn
+    }
+}
+
+/// The same fee for all tokens
+impl TokenFees for Pips {
+    #[inline]
+    fn token_fee(&self, _token_id: &TokenId, _counterpart: Option<&TokenId>) -> Pips {
+        *self
+    }
+}
+
+/// The same fee for all tokens, see [`FeesConfig::with_overrides`]
+impl TokenFees for FeesConfig {
+    #[inline]
+    fn token_fee(&self, _token_id: &TokenId, _counterpart: Option<&TokenId>) -> Pips {
+        self.fee
+    }
+
+    #[inline]
+    fn fee_mode(&self) -> FeeMode {
+        self.fee_mode
+    }
+}
+
+/// Unordered pair of distinct tokens
+#[near(serializers = [borsh, json])]
+#[serde(try_from = "(TokenId, TokenId)")]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenPair(TokenId, TokenId);
+
+impl TokenPair {
+    /// Returns `None` if both tokens are the same
+    #[must_use]
+    pub fn new(a: TokenId, b: TokenId) -> Option<Self> {
+        match a.cmp(&b) {
+            Ordering::Less => Some(Self(a, b)),
+            Ordering::Greater => Some(Self(b, a)),
+            Ordering::Equal => None,
+        }
+    }
+
+    #[inline]
+    pub const fn tokens(&self) -> (&TokenId, &TokenId) {
+        (&self.0, &self.1)
+    }
+}
+
+impl TryFrom<(TokenId, TokenId)> for TokenPair {
+    type Error = SameTokenPair;
+
+    #[inline]
+    fn try_from((a, b): (TokenId, TokenId)) -> Result<Self, Self::Error> {
+        Self::new(a, b).ok_or(SameTokenPair)
+    }
+}
+
+#[derive(Debug, ThisError)]
+#[error("pair of the same token")]
+pub struct SameTokenPair;
+
 /// 1 pip == 1/100th of bip == 0.0001%
 #[near(serializers = [borsh, json])]
 #[serde(try_from = "u32")]
@@ -93,6 +330,15 @@ impl Pips {
         Self(Self::MAX.as_pips() - self.as_pips())
     }
 
+    /// Returns fee reduced by given `discount` share of it,
+    /// i.e. [`Pips::MAX`] discount results in zero fee
+    #[must_use]
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn discounted(self, discount: Pips) -> Self {
+        Self(discount.invert().fee_ceil(self.as_pips().into()) as u32)
+    }
+
     #[inline]
     pub fn fee(self, amount: u128) -> u128 {
         amount
@@ -204,4 +450,169 @@ pub struct FeeChangedEvent {
 pub struct FeeCollectorChangedEvent<'a> {
     pub old_fee_collector: Cow<'a, AccountIdRef>,
     pub new_fee_collector: Cow<'a, AccountIdRef>,
+
+    /// Set only when shares of additional fee collectors were changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_shares: Option<BTreeMap<AccountId, Pips>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_shares: Option<BTreeMap<AccountId, Pips>>,
+}
+
+#[must_use = "make sure to `.emit()` this event"]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct FeeDiscountChangedEvent {
+    pub old_discount: Option<Pips>,
+    pub new_discount: Option<Pips>,
+}
+
+#[must_use = "make sure to `.emit()` this event"]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct TokenFeeChangedEvent {
+    pub token_id: TokenId,
+    pub old_fee: Option<Pips>,
+    pub new_fee: Option<Pips>,
+}
+
+#[must_use = "make sure to `.emit()` this event"]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct PairFeeChangedEvent {
+    pub pair: TokenPair,
+    pub old_fee: Option<Pips>,
+    pub new_fee: Option<Pips>,
+}
+
+#[must_use = "make sure to `.emit()` this event"]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct FeeModeChangedEvent {
+    pub old_mode: FeeMode,
+    pub new_mode: FeeMode,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_for() {
+        let [t1, t2, t3] = ["ft1", "ft2", "ft3"].map(|t| TokenId::Nep141(t.parse().unwrap()));
+
+        let config = FeesConfig {
+            fee: Pips::ONE_PERCENT,
+            fee_collector: "fees.near".parse().unwrap(),
+            fee_collector_shares: Default::default(),
+            referral_fee: Pips::ZERO,
+            fee_mode: FeeMode::TokenIn,
+        };
+        let overrides = FeeOverrides {
+            token_fees: [(t1.clone(), Pips::ONE_BIP * 10)].into(),
+            pair_fees: [(
+                TokenPair::new(t2.clone(), t1.clone()).unwrap(),
+                Pips::ONE_BIP,
+            )]
+            .into(),
+        };
+        let fees = config.with_overrides(&overrides);
+
+        assert_eq!(fees.token_fee(&t3, None), Pips::ONE_PERCENT);
+        assert_eq!(fees.token_fee(&t1, None), Pips::ONE_BIP * 10);
+        assert_eq!(fees.token_fee(&t1, Some(&t3)), Pips::ONE_BIP * 10);
+        // pair is unordered
+        assert_eq!(fees.token_fee(&t1, Some(&t2)), Pips::ONE_BIP);
+        assert_eq!(fees.token_fee(&t2, Some(&t1)), Pips::ONE_BIP);
+    }
+
+    #[test]
+    fn discounted() {
+        assert_eq!(Pips::ONE_PERCENT.discounted(Pips::ZERO), Pips::ONE_PERCENT);
+        assert_eq!(
+            Pips::ONE_PERCENT.discounted(Pips::from_percent(50).unwrap()),
+            Pips::ONE_BIP * 50
+        );
+        assert_eq!(Pips::ONE_PERCENT.discounted(Pips::MAX), Pips::ZERO);
+        // rounded up
+        assert_eq!(
+            Pips::ONE_PIP.discounted(Pips::from_percent(50).unwrap()),
+            Pips::ONE_PIP
+        );
+    }
+
+    #[test]
+    fn split_fees() {
+        let [t1, t2] = ["ft1", "ft2"].map(|t| TokenId::Nep141(t.parse().unwrap()));
+        let [a, b]: [AccountId; 2] = ["a.near", "b.near"].map(|a| a.parse().unwrap());
+
+        let (splits, remainder) = super::split_fees(
+            [
+                (a.clone(), Pips::ONE_PERCENT * 50),
+                (b.clone(), Pips::ONE_PERCENT * 25),
+            ],
+            TokenAmounts::default()
+                .with_deposit_many([(t1.clone(), 101), (t2.clone(), 1)])
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            splits,
+            [
+                (
+                    a,
+                    TokenAmounts::default()
+                        .with_deposit(t1.clone(), 50)
+                        .unwrap()
+                ),
+                (
+                    b,
+                    TokenAmounts::default()
+                        .with_deposit(t1.clone(), 25)
+                        .unwrap()
+                ),
+            ]
+        );
+        // rounding remainders go to primary fee collector
+        assert_eq!(
+            remainder,
+            TokenAmounts::default()
+                .with_deposit_many([(t1, 26), (t2, 1)])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn fee_collector_shares_valid() {
+        let fees = |shares: &[(&str, Pips)]| FeesConfig {
+            fee: Pips::ONE_PERCENT,
+            fee_collector: "fees.near".parse().unwrap(),
+            fee_collector_shares: shares
+                .iter()
+                .map(|(a, s)| (a.parse().unwrap(), *s))
+                .collect(),
+            referral_fee: Pips::ZERO,
+            fee_mode: FeeMode::TokenIn,
+        };
+
+        assert!(fees(&[]).are_fee_collector_shares_valid());
+        assert!(fees(&[
+            ("a.near", Pips::ONE_PERCENT * 60),
+            ("b.near", Pips::ONE_PERCENT * 40)
+        ])
+        .are_fee_collector_shares_valid());
+        assert!(!fees(&[
+            ("a.near", Pips::ONE_PERCENT * 60),
+            ("b.near", Pips::ONE_PERCENT * 50)
+        ])
+        .are_fee_collector_shares_valid());
+        assert!(!fees(&[("a.near", Pips::ZERO)]).are_fee_collector_shares_valid());
+        assert!(!fees(&[("fees.near", Pips::ONE_PERCENT)]).are_fee_collector_shares_valid());
+    }
+
+    #[test]
+    fn same_token_pair() {
+        let t = TokenId::Nep141("ft.near".parse().unwrap());
+        assert!(TokenPair::new(t.clone(), t).is_none());
+    }
 }
@@ -1,11 +1,14 @@
+use std::collections::BTreeSet;
+
 use defuse_crypto::PublicKey;
 use defuse_serde_utils::base64::Base64;
-use near_sdk::{near, AccountIdRef, CryptoHash};
+use near_sdk::{near, AccountId, AccountIdRef, CryptoHash};
 use serde_with::serde_as;
 
 use crate::{
-    engine::{Engine, Inspector, State},
-    DefuseError, Nonce, Result,
+    accounts::{Guardians, Lockdown, PublicKeyScope},
+    engine::{Engine, Inspector, State, StateView},
+    Deadline, DefuseError, Nonce, Result,
 };
 
 use super::ExecutableIntent;
@@ -15,6 +18,11 @@ use super::ExecutableIntent;
 #[derive(Debug, Clone)]
 pub struct AddPublicKey {
     pub public_key: PublicKey,
+
+    /// Restrict what can be signed with this key.
+    /// If not set, the key has full access to the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<PublicKeyScope>,
 }
 
 impl ExecutableIntent for AddPublicKey {
@@ -35,6 +43,11 @@ impl ExecutableIntent for AddPublicKey {
         {
             return Err(DefuseError::PublicKeyExists);
         }
+        if let Some(scope) = self.scope {
+            engine
+                .state
+                .set_public_key_scope(signer_id.to_owned(), self.public_key, scope);
+        }
         Ok(())
     }
 }
@@ -64,10 +77,291 @@ impl ExecutableIntent for RemovePublicKey {
         {
             return Err(DefuseError::PublicKeyNotExist);
         }
+        // make sure multisig account doesn't lock itself out
+        let threshold = engine.state.threshold(signer_id);
+        if threshold > 1 && !is_threshold_reachable(&engine.state, signer_id, threshold) {
+            return Err(DefuseError::InvalidThreshold);
+        }
+        Ok(())
+    }
+}
+
+/// Set minimum number of distinct public keys required to sign intents
+/// on behalf of the signer account. It can't be zero and can't exceed
+/// the number of public keys registered for the account.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct SetThreshold {
+    pub threshold: u16,
+}
+
+impl ExecutableIntent for SetThreshold {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if !is_threshold_reachable(&engine.state, signer_id, self.threshold) {
+            return Err(DefuseError::InvalidThreshold);
+        }
+        engine
+            .state
+            .set_threshold(signer_id.to_owned(), self.threshold);
+        Ok(())
+    }
+}
+
+/// Immediately disable all public keys of the signer account except
+/// `recovery_keys` until `expires_at`. While the lockdown is active,
+/// intents can only be signed by `recovery_keys`, and the threshold
+/// of the account still applies. Signing a new lockdown replaces the
+/// current one, but it can only shorten the active lockdown, so that
+/// a leaked key can't extend it. Limit orders placed with other keys
+/// can't be filled while the lockdown is active.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct LockAccount {
+    /// Must be full-access public keys of the signer account, added
+    /// at least [`Lockdown::MIN_RECOVERY_KEY_AGE`] ago. There should be
+    /// enough of them to reach the threshold of the account.
+    pub recovery_keys: BTreeSet<PublicKey>,
+
+    /// Can't exceed [`Lockdown::MAX_DURATION`] from now
+    pub expires_at: Deadline,
+}
+
+impl ExecutableIntent for LockAccount {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        for recovery_key in &self.recovery_keys {
+            if !engine.state.has_public_key(signer_id, recovery_key) {
+                return Err(DefuseError::PublicKeyNotExist);
+            }
+            // scoped keys can't be used to recover the account
+            if engine
+                .state
+                .public_key_scope(signer_id, recovery_key)
+                .is_some()
+            {
+                return Err(DefuseError::PublicKeyScopeViolated);
+            }
+            // keys without recorded time were added long ago
+            if let Some(added_at) = engine.state.public_key_added_at(signer_id, recovery_key) {
+                if !(added_at + Lockdown::MIN_RECOVERY_KEY_AGE).has_expired() {
+                    return Err(DefuseError::PublicKeyTooRecent);
+                }
+            }
+        }
+        // make sure recovery keys don't lock out multisig account
+        if self.recovery_keys.len() < usize::from(engine.state.threshold(signer_id)) {
+            return Err(DefuseError::InvalidThreshold);
+        }
+        if self.expires_at.has_expired()
+            || self.expires_at > Deadline::timeout(Lockdown::MAX_DURATION)
+        {
+            return Err(DefuseError::InvalidIntent);
+        }
+        // active lockdown can only be shortened
+        if engine
+            .state
+            .lockdown(signer_id)
+            .filter(Lockdown::is_active)
+            .is_some_and(|lockdown| self.expires_at > lockdown.expires_at)
+        {
+            return Err(DefuseError::InvalidIntent);
+        }
+        engine.state.set_lockdown(
+            signer_id.to_owned(),
+            Lockdown {
+                recovery_keys: self.recovery_keys,
+                expires_at: self.expires_at,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Freeze the signer account, e.g. if its keys were compromised: it
+/// won't be able to sign any further intents, transfer or withdraw
+/// tokens, but can still receive deposits. Only accounts with
+/// `UnrestrictedAccountLocker` role can unfreeze it.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct FreezeAccount {}
+
+impl ExecutableIntent for FreezeAccount {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if !engine.state.lock_account(signer_id.to_owned()) {
+            return Err(DefuseError::AccountLocked);
+        }
+        Ok(())
+    }
+}
+
+/// Set guardians of the signer account, who can jointly recover access
+/// to it by replacing all its public keys. Empty set of guardians removes
+/// them. Any pending recovery is cancelled.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct SetGuardians {
+    #[serde(flatten)]
+    pub guardians: Guardians,
+}
+
+impl ExecutableIntent for SetGuardians {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if !self.guardians.is_valid(signer_id) {
+            return Err(DefuseError::InvalidGuardians);
+        }
+        engine
+            .state
+            .set_guardians(signer_id.to_owned(), self.guardians);
         Ok(())
     }
 }
 
+/// Approve recovery of `account_id` with `public_key` on behalf of the
+/// signer, who must be one of its guardians. Once enough guardians approve
+/// the same public key, the recovery gets scheduled and can be finalized
+/// after the delay, unless the owner cancels it.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct ApproveRecovery {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+}
+
+impl ExecutableIntent for ApproveRecovery {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        engine
+            .state
+            .approve_recovery(self.account_id, signer_id.to_owned(), self.public_key)
+    }
+}
+
+/// Cancel pending recovery of the signer account
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct CancelRecovery {}
+
+impl ExecutableIntent for CancelRecovery {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if !engine.state.cancel_recovery(signer_id.to_owned()) {
+            return Err(DefuseError::RecoveryNotFound);
+        }
+        Ok(())
+    }
+}
+
+/// Close the signer account: remove all its public keys, settings,
+/// limit orders and approvals and refund storage deposit.
+/// All token balances of the account must be zero and no HTLCs can
+/// be pending.
+///
+/// NOTE: used nonces are kept, so that intents signed before closing
+/// can't be replayed on the re-created account. Storage occupied by
+/// them is not refunded.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct CloseAccount {
+    /// Receiver of refunded storage deposit, defaults to the signer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receiver_id: Option<AccountId>,
+}
+
+impl ExecutableIntent for CloseAccount {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        engine.state.close_account(
+            signer_id.to_owned(),
+            self.receiver_id.unwrap_or_else(|| signer_id.to_owned()),
+        )
+    }
+}
+
+#[inline]
+fn is_threshold_reachable<S>(state: &S, account_id: &AccountIdRef, threshold: u16) -> bool
+where
+    S: StateView + ?Sized,
+{
+    // only recovery keys can sign during lockdown
+    let lockdown = state.lockdown(account_id).filter(Lockdown::is_active);
+    threshold > 0
+        && state
+            .iter_public_keys(account_id)
+            .filter(|public_key| {
+                lockdown
+                    .as_ref()
+                    .is_none_or(|lockdown| lockdown.is_key_allowed(public_key))
+            })
+            .count()
+            >= usize::from(threshold)
+}
+
 /// Invalidate given nonces TODO: error?
 #[cfg_attr(
     all(feature = "abi", not(target_arch = "wasm32")),
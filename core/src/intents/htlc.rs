@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use defuse_serde_utils::{base58::Base58, base64::Base64};
+use near_sdk::{env, near, AccountId, AccountIdRef, CryptoHash};
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::{
+    engine::{Engine, Inspector, State, StateView},
+    tokens::TokenAmounts,
+    Deadline, DefuseError, Result,
+};
+
+use super::ExecutableIntent;
+
+/// Hash-time-locked escrow: tokens are withdrawn from the sender and
+/// held in the escrow itself until either `receiver_id` claims them by
+/// revealing preimage of the hash lock via [`HtlcClaim`] before the
+/// deadline, or the sender refunds them via [`HtlcRefund`] after the
+/// deadline. Escrows are identified by their sender and hash lock.
+///
+/// NOTE: transfer events represent the escrow as the verifying contract.
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Htlc {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    pub tokens: TokenAmounts,
+
+    pub deadline: Deadline,
+}
+
+/// Lock tokens of the signer in [`Htlc`] escrow. The signer can't have
+/// two escrows with the same hash lock at the same time.
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct HtlcLock {
+    /// SHA-256 hash of the secret preimage
+    #[serde_as(as = "Base58")]
+    pub hash_lock: CryptoHash,
+
+    pub receiver_id: AccountId,
+
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    pub tokens: TokenAmounts,
+
+    /// Tokens can be claimed only before this deadline
+    /// and refunded only after it
+    pub deadline: Deadline,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl ExecutableIntent for HtlcLock {
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if self.tokens.is_empty() || signer_id == self.receiver_id {
+            return Err(DefuseError::InvalidIntent);
+        }
+        if self.deadline.has_expired() {
+            return Err(DefuseError::DeadlineExpired);
+        }
+
+        engine
+            .inspector
+            .on_htlc_locked(signer_id, &self, intent_hash);
+
+        engine.state.lock_htlc(
+            self.hash_lock,
+            Htlc {
+                sender_id: signer_id.to_owned(),
+                receiver_id: self.receiver_id,
+                tokens: self.tokens,
+                deadline: self.deadline,
+            },
+        )
+    }
+}
+
+/// Claim tokens locked in [`Htlc`] by revealing the preimage.
+/// Can only be executed by its receiver before the deadline.
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct HtlcClaim {
+    pub sender_id: AccountId,
+
+    #[serde_as(as = "Base64")]
+    pub preimage: Vec<u8>,
+}
+
+impl ExecutableIntent for HtlcClaim {
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        let hash_lock = env::sha256_array(&self.preimage);
+        let htlc = engine
+            .state
+            .htlc(&self.sender_id, &hash_lock)
+            .filter(|htlc| signer_id == htlc.receiver_id)
+            .ok_or(DefuseError::HtlcNotFound)?;
+        if htlc.deadline.has_expired() {
+            return Err(DefuseError::DeadlineExpired);
+        }
+
+        engine.inspector.on_htlc_claimed(
+            signer_id,
+            &self.sender_id,
+            hash_lock,
+            &self.preimage,
+            intent_hash,
+        );
+
+        engine
+            .state
+            .release_htlc(&self.sender_id, &hash_lock, htlc.receiver_id)
+            .map(|_htlc| ())
+    }
+}
+
+/// Refund tokens locked in [`Htlc`] back to its sender.
+/// Can only be executed by the sender after the deadline.
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct HtlcRefund {
+    #[serde_as(as = "Base58")]
+    pub hash_lock: CryptoHash,
+}
+
+impl ExecutableIntent for HtlcRefund {
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        let htlc = engine
+            .state
+            .htlc(signer_id, &self.hash_lock)
+            .ok_or(DefuseError::HtlcNotFound)?;
+        if !htlc.deadline.has_expired() {
+            return Err(DefuseError::HtlcNotExpired);
+        }
+
+        engine
+            .inspector
+            .on_htlc_refunded(signer_id, &self, intent_hash);
+
+        engine
+            .state
+            .release_htlc(signer_id, &self.hash_lock, htlc.sender_id)
+            .map(|_htlc| ())
+    }
+}
+
+#[must_use = "make sure to `.emit()` this event"]
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct HtlcClaimedEvent {
+    pub sender_id: AccountId,
+
+    #[serde_as(as = "Base58")]
+    pub hash_lock: CryptoHash,
+
+    /// Revealed preimage, so that counterparty can claim
+    /// corresponding HTLC on the other chain
+    #[serde_as(as = "Base64")]
+    pub preimage: Vec<u8>,
+}
@@ -0,0 +1,354 @@
+use std::collections::BTreeSet;
+
+use defuse_crypto::PublicKey;
+use defuse_num_utils::CheckedMulDiv;
+use defuse_serde_utils::base58::Base58;
+use near_sdk::{json_types::U128, near, AccountId, AccountIdRef, CryptoHash};
+use serde_with::serde_as;
+
+use crate::{
+    accounts::{Lockdown, PublicKeyScope},
+    engine::{Engine, Inspector, State},
+    fees::FeeMode,
+    tokens::TokenId,
+    Deadline, DefuseError, Result,
+};
+
+use super::{
+    token_diff::{TokenDeltas, TokenDiff},
+    ExecutableIntent,
+};
+
+/// Place a limit order, which can be partially filled by solvers
+/// via [`FillLimitOrder`] across several `execute_intents` calls.
+/// The order is identified by the hash of the signed intent it was
+/// placed with, so there can be only one limit order per signed intent.
+/// Expired orders are pruned a few at a time whenever the maker places
+/// or cancels another one.
+///
+/// NOTE: no funds are locked when placing the order: each fill is
+/// executed as a [`TokenDiff`] on behalf of the maker, so fees are
+/// charged the same way. If the order was signed with restricted
+/// public keys, their scopes are stored along with the order and
+/// checked on every fill. The order can't be filled once any of the
+/// public keys it was signed with is removed from the maker account
+/// or disallowed by its lockdown, see
+/// [`LockAccount`](super::account::LockAccount).
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitOrder {
+    pub token_in: TokenId,
+    /// Maximum amount of `token_in` the maker is willing to give
+    pub amount_in: U128,
+
+    pub token_out: TokenId,
+    /// Amount of `token_out` the maker wants to receive for the whole
+    /// `amount_in`, i.e. the price is `amount_out / amount_in`
+    pub amount_out: U128,
+
+    /// The order can't be filled after this deadline
+    pub deadline: Deadline,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+
+    /// Side to charge fees on when the order is filled,
+    /// see [`TokenDiff::fee_mode`]
+    #[serde(default)]
+    pub fee_mode: FeeMode,
+}
+
+impl LimitOrder {
+    /// Returns amount of `token_out` the maker should receive for given
+    /// `amount_in`. Rounded up, so the maker never gets worse price.
+    #[inline]
+    pub fn amount_out_for(&self, amount_in: u128) -> Option<u128> {
+        amount_in.checked_mul_div_ceil(self.amount_out.0, self.amount_in.0)
+    }
+}
+
+impl ExecutableIntent for LimitOrder {
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if self.token_in == self.token_out || self.amount_in.0 == 0 || self.amount_out.0 == 0 {
+            return Err(DefuseError::InvalidIntent);
+        }
+        if self.deadline.has_expired() {
+            return Err(DefuseError::DeadlineExpired);
+        }
+
+        let scopes = engine.state.scopes_of(signer_id).to_vec();
+        if !scopes.iter().all(|scope| {
+            scope.is_amount_allowed(&self.token_in, self.amount_in.0)
+                && scope.is_token_allowed(&self.token_out)
+        }) {
+            return Err(DefuseError::PublicKeyScopeViolated);
+        }
+
+        if !engine.state.place_limit_order(
+            signer_id.to_owned(),
+            intent_hash,
+            LimitOrderState::new(self.clone(), scopes, engine.signer_public_keys.clone()),
+        ) {
+            return Err(DefuseError::LimitOrderExists);
+        }
+        engine
+            .inspector
+            .on_limit_order_placed(signer_id, &self, intent_hash);
+        Ok(())
+    }
+}
+
+/// Current state of the placed [`LimitOrder`]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitOrderState {
+    #[serde(flatten)]
+    pub order: LimitOrder,
+
+    /// Amount of `token_in` that was already filled
+    pub filled: U128,
+
+    /// Scopes of public keys the order was signed with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<PublicKeyScope>,
+
+    /// Public keys the order was signed with
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub public_keys: BTreeSet<PublicKey>,
+}
+
+impl LimitOrderState {
+    #[inline]
+    pub const fn new(
+        order: LimitOrder,
+        scopes: Vec<PublicKeyScope>,
+        public_keys: BTreeSet<PublicKey>,
+    ) -> Self {
+        Self {
+            order,
+            filled: U128(0),
+            scopes,
+            public_keys,
+        }
+    }
+
+    /// Returns amount of `token_in` that is left to be filled
+    #[inline]
+    pub const fn remaining(&self) -> u128 {
+        self.order.amount_in.0.saturating_sub(self.filled.0)
+    }
+
+    #[inline]
+    pub const fn is_filled(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Fills given `amount_in` of the order. Returns `None` if
+    /// it exceeds remaining amount.
+    #[must_use]
+    #[inline]
+    pub fn fill(&mut self, amount_in: u128) -> Option<()> {
+        if amount_in > self.remaining() {
+            return None;
+        }
+        self.filled.0 = self.filled.0.checked_add(amount_in)?;
+        Some(())
+    }
+}
+
+/// Fill given `amount_in` of [`LimitOrder`] placed by `maker_id`.
+/// Maker's balances are changed as by executing [`TokenDiff`] restricted
+/// by scopes the order was placed with, so the signer is expected to
+/// provide the counterpart in the same batch.
+/// The order is removed as soon as it gets fully filled.
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct FillLimitOrder {
+    pub maker_id: AccountId,
+    #[serde_as(as = "Base58")]
+    pub order_hash: CryptoHash,
+    pub amount_in: U128,
+}
+
+impl ExecutableIntent for FillLimitOrder {
+    fn execute_intent<S, I>(
+        self,
+        _signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if self.amount_in.0 == 0 {
+            return Err(DefuseError::InvalidIntent);
+        }
+
+        let LimitOrderState {
+            order,
+            scopes,
+            public_keys,
+            ..
+        } = engine
+            .state
+            .fill_limit_order(&self.maker_id, self.order_hash, self.amount_in.0)?;
+        if order.deadline.has_expired() {
+            return Err(DefuseError::DeadlineExpired);
+        }
+
+        // keys the order was signed with must still be allowed to sign
+        // on behalf of the maker, so that orders placed with a leaked
+        // key can't be filled after it was removed or locked down
+        if !public_keys
+            .iter()
+            .all(|public_key| engine.state.has_public_key(&self.maker_id, public_key))
+        {
+            return Err(DefuseError::PublicKeyNotExist);
+        }
+        if let Some(lockdown) = engine
+            .state
+            .lockdown(&self.maker_id)
+            .filter(Lockdown::is_active)
+        {
+            engine.inspector.on_deadline(lockdown.expires_at);
+            if !public_keys
+                .iter()
+                .all(|public_key| lockdown.is_key_allowed(public_key))
+            {
+                return Err(DefuseError::PublicKeyLockedDown);
+            }
+        }
+        for scope in &scopes {
+            engine.inspector.on_deadline(scope.expires_at);
+            if scope.expires_at.has_expired() {
+                return Err(DefuseError::DeadlineExpired);
+            }
+        }
+
+        let amount_in = i128::try_from(self.amount_in.0)
+            .ok()
+            .and_then(i128::checked_neg)
+            .ok_or(DefuseError::BalanceOverflow)?;
+        let amount_out = order
+            .amount_out_for(self.amount_in.0)
+            .and_then(|amount| i128::try_from(amount).ok())
+            .ok_or(DefuseError::BalanceOverflow)?;
+
+        let diff = TokenDiff {
+            diff: TokenDeltas::default()
+                .with_add_deltas([(order.token_in, amount_in), (order.token_out, amount_out)])
+                .ok_or(DefuseError::BalanceOverflow)?,
+            memo: order.memo,
+            referral: None,
+            fee_mode: order.fee_mode,
+        };
+
+        let restriction = engine
+            .state
+            .replace_restriction(self.maker_id.clone(), scopes);
+        let result = diff.execute_intent(&self.maker_id, engine, self.order_hash);
+        engine.state.restore_restriction(restriction);
+        result
+    }
+}
+
+/// Cancel [`LimitOrder`] placed by the signer
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct CancelLimitOrder {
+    #[serde_as(as = "Base58")]
+    pub order_hash: CryptoHash,
+}
+
+impl ExecutableIntent for CancelLimitOrder {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if !engine.state.cancel_limit_order(signer_id, self.order_hash) {
+            return Err(DefuseError::LimitOrderNotFound);
+        }
+        engine
+            .inspector
+            .on_limit_order_cancelled(signer_id, self.order_hash);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(amount_in: u128, amount_out: u128) -> LimitOrder {
+        LimitOrder {
+            token_in: TokenId::Nep141("ft1.near".parse().unwrap()),
+            amount_in: U128(amount_in),
+            token_out: TokenId::Nep141("ft2.near".parse().unwrap()),
+            amount_out: U128(amount_out),
+            deadline: Deadline::MAX,
+            memo: None,
+            fee_mode: FeeMode::TokenIn,
+        }
+    }
+
+    #[test]
+    fn amount_out_rounds_up() {
+        let order = order(3, 10);
+        assert_eq!(order.amount_out_for(3), Some(10));
+        assert_eq!(order.amount_out_for(1), Some(4));
+        assert_eq!(order.amount_out_for(2), Some(7));
+        assert_eq!(order.amount_out_for(0), Some(0));
+    }
+
+    #[test]
+    fn partial_fills() {
+        let mut state = LimitOrderState::new(order(100, 200), Vec::new(), BTreeSet::new());
+        assert_eq!(state.remaining(), 100);
+
+        state.fill(30).unwrap();
+        state.fill(50).unwrap();
+        assert_eq!(state.filled, U128(80));
+        assert_eq!(state.remaining(), 20);
+        assert!(!state.is_filled());
+
+        assert!(state.fill(21).is_none());
+        assert_eq!(state.filled, U128(80));
+
+        state.fill(20).unwrap();
+        assert!(state.is_filled());
+        assert!(state.fill(1).is_none());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod account;
+pub mod htlc;
+pub mod limit_order;
+pub mod require;
 pub mod token_diff;
 pub mod tokens;
 
@@ -14,9 +17,15 @@ use crate::{
 };
 
 use self::{
-    account::{AddPublicKey, InvalidateNonces, RemovePublicKey},
+    account::{
+        AddPublicKey, ApproveRecovery, CancelRecovery, CloseAccount, FreezeAccount,
+        InvalidateNonces, LockAccount, RemovePublicKey, SetGuardians, SetThreshold,
+    },
+    htlc::{HtlcClaim, HtlcLock, HtlcRefund},
+    limit_order::{CancelLimitOrder, FillLimitOrder, LimitOrder},
+    require::Require,
     token_diff::TokenDiff,
-    tokens::{FtWithdraw, MtWithdraw, NftWithdraw, Transfer},
+    tokens::{BatchTransfer, Call, FtWithdraw, MtWithdraw, NftWithdraw, StorageDeposit, Transfer},
 };
 
 #[near(serializers = [borsh, json])]
@@ -35,16 +44,113 @@ pub struct DefuseIntents {
 pub enum Intent {
     AddPublicKey(AddPublicKey),
     RemovePublicKey(RemovePublicKey),
+    SetThreshold(SetThreshold),
     InvalidateNonces(InvalidateNonces),
+    LockAccount(LockAccount),
+    FreezeAccount(FreezeAccount),
+    SetGuardians(SetGuardians),
+    ApproveRecovery(ApproveRecovery),
+    CancelRecovery(CancelRecovery),
+    CloseAccount(CloseAccount),
 
     Transfer(Transfer),
+    BatchTransfer(BatchTransfer),
 
     FtWithdraw(FtWithdraw),
     NftWithdraw(NftWithdraw),
     MtWithdraw(MtWithdraw),
     NativeWithdraw(NativeWithdraw),
 
+    StorageDeposit(StorageDeposit),
+
+    Call(Call),
+
     TokenDiff(TokenDiff),
+
+    LimitOrder(LimitOrder),
+    FillLimitOrder(FillLimitOrder),
+    CancelLimitOrder(CancelLimitOrder),
+
+    HtlcLock(HtlcLock),
+    HtlcClaim(HtlcClaim),
+    HtlcRefund(HtlcRefund),
+
+    Require(Require),
+}
+
+/// Kind of [`Intent`], serialized the same way as its `intent` tag
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IntentKind {
+    AddPublicKey,
+    RemovePublicKey,
+    SetThreshold,
+    InvalidateNonces,
+    LockAccount,
+    FreezeAccount,
+    SetGuardians,
+    ApproveRecovery,
+    CancelRecovery,
+    CloseAccount,
+
+    Transfer,
+    BatchTransfer,
+
+    FtWithdraw,
+    NftWithdraw,
+    MtWithdraw,
+    NativeWithdraw,
+
+    StorageDeposit,
+
+    Call,
+
+    TokenDiff,
+
+    LimitOrder,
+    FillLimitOrder,
+    CancelLimitOrder,
+
+    HtlcLock,
+    HtlcClaim,
+    HtlcRefund,
+
+    Require,
+}
+
+impl Intent {
+    #[inline]
+    pub const fn kind(&self) -> IntentKind {
+        match self {
+            Self::AddPublicKey(_) => IntentKind::AddPublicKey,
+            Self::RemovePublicKey(_) => IntentKind::RemovePublicKey,
+            Self::SetThreshold(_) => IntentKind::SetThreshold,
+            Self::InvalidateNonces(_) => IntentKind::InvalidateNonces,
+            Self::LockAccount(_) => IntentKind::LockAccount,
+            Self::FreezeAccount(_) => IntentKind::FreezeAccount,
+            Self::SetGuardians(_) => IntentKind::SetGuardians,
+            Self::ApproveRecovery(_) => IntentKind::ApproveRecovery,
+            Self::CancelRecovery(_) => IntentKind::CancelRecovery,
+            Self::CloseAccount(_) => IntentKind::CloseAccount,
+            Self::Transfer(_) => IntentKind::Transfer,
+            Self::BatchTransfer(_) => IntentKind::BatchTransfer,
+            Self::FtWithdraw(_) => IntentKind::FtWithdraw,
+            Self::NftWithdraw(_) => IntentKind::NftWithdraw,
+            Self::MtWithdraw(_) => IntentKind::MtWithdraw,
+            Self::NativeWithdraw(_) => IntentKind::NativeWithdraw,
+            Self::StorageDeposit(_) => IntentKind::StorageDeposit,
+            Self::Call(_) => IntentKind::Call,
+            Self::TokenDiff(_) => IntentKind::TokenDiff,
+            Self::LimitOrder(_) => IntentKind::LimitOrder,
+            Self::FillLimitOrder(_) => IntentKind::FillLimitOrder,
+            Self::CancelLimitOrder(_) => IntentKind::CancelLimitOrder,
+            Self::HtlcLock(_) => IntentKind::HtlcLock,
+            Self::HtlcClaim(_) => IntentKind::HtlcClaim,
+            Self::HtlcRefund(_) => IntentKind::HtlcRefund,
+            Self::Require(_) => IntentKind::Require,
+        }
+    }
 }
 
 pub struct MetaIntent {
@@ -95,13 +201,30 @@ impl ExecutableIntent for Intent {
         match self {
             Self::AddPublicKey(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::RemovePublicKey(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::SetThreshold(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::InvalidateNonces(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::LockAccount(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::FreezeAccount(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::SetGuardians(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::ApproveRecovery(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::CancelRecovery(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::CloseAccount(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::Transfer(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::BatchTransfer(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::FtWithdraw(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::NftWithdraw(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::MtWithdraw(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::NativeWithdraw(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::StorageDeposit(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::Call(intent) => intent.execute_intent(signer_id, engine, intent_hash),
             Self::TokenDiff(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::LimitOrder(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::FillLimitOrder(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::CancelLimitOrder(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::HtlcLock(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::HtlcClaim(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::HtlcRefund(intent) => intent.execute_intent(signer_id, engine, intent_hash),
+            Self::Require(intent) => intent.execute_intent(signer_id, engine, intent_hash),
         }
     }
 }
@@ -0,0 +1,115 @@
+use defuse_crypto::PublicKey;
+use defuse_serde_utils::base64::Base64;
+use near_sdk::{json_types::U128, near, AccountId, AccountIdRef, CryptoHash};
+use serde_with::serde_as;
+
+use crate::{
+    engine::{Engine, Inspector, State, StateView},
+    tokens::TokenId,
+    DefuseError, Nonce, Result,
+};
+
+use super::ExecutableIntent;
+
+/// Assert given conditions against the state at the moment of execution.
+/// The whole batch fails if any of them doesn't hold, so it can be used
+/// to guard subsequent intents, e.g. "only swap if I still hold at
+/// least N of token A".
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct Require {
+    pub conditions: Vec<Condition>,
+}
+
+impl ExecutableIntent for Require {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        _signer_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        _intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if !self
+            .conditions
+            .iter()
+            .all(|condition| condition.check(&engine.state))
+        {
+            return Err(DefuseError::ConditionNotMet);
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[serde(tag = "condition", rename_all = "snake_case")]
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// Balance of `token_id` owned by `account_id` is at least `amount`
+    MinBalance {
+        account_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+    },
+    /// Balance of `token_id` owned by `account_id` is at most `amount`
+    MaxBalance {
+        account_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+    },
+    /// Whether given `nonce` was already used by `account_id`
+    NonceUsed {
+        account_id: AccountId,
+        #[serde_as(as = "Base64")]
+        nonce: Nonce,
+        used: bool,
+    },
+    /// Whether `public_key` is registered for `account_id`
+    HasPublicKey {
+        account_id: AccountId,
+        public_key: PublicKey,
+        has: bool,
+    },
+}
+
+impl Condition {
+    #[must_use]
+    pub fn check<S>(&self, state: &S) -> bool
+    where
+        S: StateView + ?Sized,
+    {
+        match self {
+            Self::MinBalance {
+                account_id,
+                token_id,
+                amount,
+            } => state.balance_of(account_id, token_id) >= amount.0,
+            Self::MaxBalance {
+                account_id,
+                token_id,
+                amount,
+            } => state.balance_of(account_id, token_id) <= amount.0,
+            Self::NonceUsed {
+                account_id,
+                nonce,
+                used,
+            } => state.is_nonce_used(account_id, *nonce) == *used,
+            Self::HasPublicKey {
+                account_id,
+                public_key,
+                has,
+            } => state.has_public_key(account_id, public_key) == *has,
+        }
+    }
+}
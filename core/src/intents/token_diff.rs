@@ -7,7 +7,7 @@ use serde_with::{serde_as, DisplayFromStr};
 
 use crate::{
     engine::{Engine, Inspector, State, StateView},
-    fees::Pips,
+    fees::{split_fees, FeeMode, Pips, TokenFees},
     tokens::{TokenAmounts, TokenId},
     DefuseError, Result,
 };
@@ -35,8 +35,16 @@ pub struct TokenDiff {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub memo: Option<String>,
 
+    /// Account to receive a share of the protocol fee. If it's not
+    /// registered, the share is paid to fee collectors instead.
+    /// Can't be the signer itself.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub referral: Option<AccountId>,
+
+    /// Side to charge fees on. Must match the current fee mode of the
+    /// contract, so that it can't be changed after the intent was signed.
+    #[serde(default)]
+    pub fee_mode: FeeMode,
 }
 
 impl ExecutableIntent for TokenDiff {
@@ -50,44 +58,130 @@ impl ExecutableIntent for TokenDiff {
         S: State,
         I: Inspector,
     {
-        if self.diff.is_empty() {
+        if self.diff.is_empty()
+            || self
+                .referral
+                .as_ref()
+                .is_some_and(|referral| referral == signer_id)
+        {
             return Err(DefuseError::InvalidIntent);
         }
+        if self.fee_mode != engine.state.fee_mode() {
+            return Err(DefuseError::FeeModeChanged);
+        }
+
+        // referral share goes to fee collectors if the referral is not
+        // registered, so that it can't fail the whole batch
+        let mut referral = self.referral.clone();
+        if let Some(unregistered) =
+            referral.take_if(|referral| !engine.state.is_account_registered(referral))
+        {
+            engine
+                .inspector
+                .on_referral_not_registered(signer_id, &unregistered, intent_hash);
+        }
 
-        let protocol_fee = engine.state.fee();
+        let fee_mode = self.fee_mode;
+        let fee_discount = engine.state.fee_discount(signer_id);
+        let referral_fee = if referral.is_some() {
+            engine.state.referral_fee()
+        } else {
+            Pips::ZERO
+        };
         let mut fees_collected: TokenAmounts = TokenAmounts::default();
+        let mut referral_fees: TokenAmounts = TokenAmounts::default();
 
         for (token_id, delta) in self.diff.clone() {
             if delta == 0 {
                 return Err(DefuseError::InvalidIntent);
             }
 
-            // add delta to signer's account
-            engine
-                .state
-                .internal_add_deltas(signer_id, [(token_id.clone(), delta)])?;
-
-            // take fees only from negative deltas (i.e. token_in)
-            if delta < 0 {
+            // take fees only from one side: either negative deltas
+            // (i.e. token_in) or positive ones (i.e. token_out)
+            let fee = if fee_mode.is_charged_on(delta) {
                 let amount = delta.unsigned_abs();
-                let fee = Self::token_fee(&token_id, amount, protocol_fee).fee_ceil(amount);
+                // pair fee overrides apply only when swapping one token
+                // for another
+                let counterpart = self.counterpart(&token_id);
+                Self::token_fee(
+                    &token_id,
+                    amount,
+                    engine
+                        .state
+                        .fee_for(&token_id, counterpart)
+                        .discounted(fee_discount),
+                )
+                .fee_ceil(amount)
+            } else {
+                0
+            };
+
+            // add delta to signer's account, fees on token_out
+            // are deducted from it
+            let delta = if delta > 0 {
+                delta
+                    .checked_sub_unsigned(fee)
+                    .ok_or(DefuseError::BalanceOverflow)?
+            } else {
+                delta
+            };
+            if delta != 0 {
+                engine
+                    .state
+                    .internal_add_deltas(signer_id, [(token_id.clone(), delta)])?;
+            }
 
+            if fee > 0 {
                 // collect fee
                 fees_collected
-                    .deposit(token_id, fee)
+                    .deposit(token_id.clone(), fee)
+                    .ok_or(DefuseError::BalanceOverflow)?;
+                // referral share is a part of collected fee
+                referral_fees
+                    .deposit(token_id, referral_fee.fee(fee))
                     .ok_or(DefuseError::BalanceOverflow)?;
             }
         }
 
-        engine
-            .inspector
-            .on_token_diff(signer_id, &self, &fees_collected, intent_hash);
+        engine.state.record_fees_collected(
+            &fees_collected,
+            referral
+                .as_deref()
+                .map(|referral| (referral, &referral_fees)),
+        )?;
+
+        engine.inspector.on_token_diff(
+            signer_id,
+            &self,
+            &fees_collected,
+            &referral_fees,
+            intent_hash,
+        );
 
-        // deposit fees to collector
+        // deposit referral share of fees to referral
+        if let Some(referral) = referral.filter(|_| !referral_fees.is_empty()) {
+            fees_collected = fees_collected
+                .with_withdraw_many(referral_fees.clone())
+                .ok_or(DefuseError::BalanceOverflow)?;
+            engine.state.internal_deposit(referral, referral_fees)?;
+        }
+
+        // split the rest of fees among collectors
         if !fees_collected.is_empty() {
-            engine
-                .state
-                .internal_deposit(engine.state.fee_collector().into_owned(), fees_collected)?;
+            let (splits, remainder) = split_fees(
+                engine.state.fee_collector_shares().into_owned(),
+                fees_collected,
+            )
+            .ok_or(DefuseError::BalanceOverflow)?;
+            for (collector, amounts) in splits {
+                engine.state.internal_deposit(collector, amounts)?;
+            }
+            // primary fee collector receives the remainder
+            if !remainder.is_empty() {
+                engine
+                    .state
+                    .internal_deposit(engine.state.fee_collector().into_owned(), remainder)?;
+            }
         }
 
         Ok(())
@@ -111,92 +205,216 @@ pub struct TokenDiffEvent<'a> {
     #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
     #[serde(skip_serializing_if = "TokenAmounts::is_empty")]
     pub fees_collected: TokenAmounts,
+
+    /// Part of `fees_collected` paid to `referral`,
+    /// the rest goes to fee collector
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    #[serde(default, skip_serializing_if = "TokenAmounts::is_empty")]
+    pub referral_fees: TokenAmounts,
+}
+
+/// Referral share of fees of [`TokenDiff`] was paid to fee collectors,
+/// since the referral is not registered
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct ReferralNotRegisteredEvent {
+    pub referral: AccountId,
 }
 
 impl TokenDiff {
+    /// Returns `(token_in, token_out)` if this diff swaps exactly
+    /// one token for another
+    #[inline]
+    pub fn as_pair(&self) -> Option<(&TokenId, &TokenId)> {
+        Self::pair_of(&self.diff)
+    }
+
+    /// Returns the other token of the pair if this diff swaps
+    /// `token_id` for exactly one another token, or vice versa
+    #[inline]
+    pub fn counterpart(&self, token_id: &TokenId) -> Option<&TokenId> {
+        Self::counterpart_in(self.as_pair(), token_id)
+    }
+
+    fn pair_of(deltas: &TokenDeltas) -> Option<(&TokenId, &TokenId)> {
+        let mut deltas = deltas.iter();
+        let ((t1, d1), (t2, d2)) = (deltas.next()?, deltas.next()?);
+        if deltas.next().is_some() {
+            return None;
+        }
+        match (d1.is_negative(), d2.is_negative()) {
+            (true, false) => Some((t1, t2)),
+            (false, true) => Some((t2, t1)),
+            _ => None,
+        }
+    }
+
+    fn counterpart_in<'a>(
+        pair: Option<(&'a TokenId, &'a TokenId)>,
+        token_id: &TokenId,
+    ) -> Option<&'a TokenId> {
+        pair.map(|(token_in, token_out)| {
+            if token_in == token_id {
+                token_out
+            } else {
+                token_in
+            }
+        })
+    }
+
     /// Returns [`TokenDiff`] closure to successfully execute `self`
-    /// assuming given `fee`
+    /// assuming given `fees`
     #[inline]
-    pub fn closure(self, fee: Pips) -> Option<TokenDeltas> {
-        Self::closure_deltas(self.diff.into_inner(), fee)
+    pub fn closure(self, fees: impl TokenFees) -> Option<TokenDeltas> {
+        Self::closure_many([self], fees)
     }
 
     /// Returns [`TokenDiff`] closure to successfully execute given set
-    /// of distinct [`TokenDiff`] assuming given `fee`
-    #[inline]
-    pub fn closure_many(diffs: impl IntoIterator<Item = Self>, fee: Pips) -> Option<TokenDeltas> {
-        Self::closure_deltas(diffs.into_iter().flat_map(|d| d.diff.into_inner()), fee)
+    /// of distinct [`TokenDiff`] assuming given `fees`, including
+    /// pair overrides of both the diffs and the closure itself
+    pub fn closure_many(
+        diffs: impl IntoIterator<Item = Self>,
+        fees: impl TokenFees,
+    ) -> Option<TokenDeltas> {
+        // collect total supply deltas
+        let supply_deltas =
+            diffs
+                .into_iter()
+                .try_fold(TokenDeltas::default(), |deltas, diff| {
+                    diff.iter().try_fold(deltas, |deltas, (token_id, delta)| {
+                        let supply_delta = Self::supply_delta(
+                            token_id,
+                            *delta,
+                            fees.token_fee(token_id, diff.counterpart(token_id)),
+                            fees.fee_mode(),
+                        )?;
+                        deltas.with_add_delta(token_id.clone(), supply_delta)
+                    })
+                })?;
+        Self::closure_supply_deltas(supply_deltas, fees)
     }
 
     /// Returns closure for deltas that should be given in a single
     /// [`TokenDiff`] to successfully execute given set of distinct `deltas`
-    /// assuming given `fee`
-    #[inline]
+    /// assuming given `fees`. Since `deltas` can't be matched with their
+    /// [`TokenDiff`], pair overrides are applied only to the closure,
+    /// see [`TokenDiff::closure_many`].
     pub fn closure_deltas(
         deltas: impl IntoIterator<Item = (TokenId, i128)>,
-        fee: Pips,
+        fees: impl TokenFees,
     ) -> Option<TokenDeltas> {
-        deltas
-            .into_iter()
-            // collect total supply deltas
-            .try_fold(TokenDeltas::default(), |deltas, (token_id, delta)| {
-                let supply_delta = Self::supply_delta(&token_id, delta, fee)?;
-                deltas.with_add_delta(token_id, supply_delta)
-            })?
-            .into_inner()
-            .into_iter()
-            // calculate closures from total supply deltas
+        // collect total supply deltas
+        let supply_deltas =
+            deltas
+                .into_iter()
+                .try_fold(TokenDeltas::default(), |deltas, (token_id, delta)| {
+                    let supply_delta = Self::supply_delta(
+                        &token_id,
+                        delta,
+                        fees.token_fee(&token_id, None),
+                        fees.fee_mode(),
+                    )?;
+                    deltas.with_add_delta(token_id, supply_delta)
+                })?;
+        Self::closure_supply_deltas(supply_deltas, fees)
+    }
+
+    /// Calculates closures from total supply deltas
+    fn closure_supply_deltas(
+        supply_deltas: TokenDeltas,
+        fees: impl TokenFees,
+    ) -> Option<TokenDeltas> {
+        // closure swaps the same tokens in the opposite direction
+        let pair = Self::pair_of(&supply_deltas);
+        supply_deltas
+            .iter()
             .try_fold(TokenDeltas::default(), |deltas, (token_id, delta)| {
-                let closure = Self::closure_supply_delta(&token_id, delta, fee)?;
-                deltas.with_add_delta(token_id, closure)
+                let closure = Self::closure_supply_delta(
+                    token_id,
+                    *delta,
+                    fees.token_fee(token_id, Self::counterpart_in(pair, token_id)),
+                    fees.fee_mode(),
+                )?;
+                deltas.with_add_delta(token_id.clone(), closure)
             })
     }
 
     /// Returns closure for delta that should be given in a single
     /// [`TokenDiff`] to successfully execute [`TokenDiff`] with given
-    /// `delta` on the same token assuming given `fee`.
+    /// `delta` on the same token assuming given `fee` and `mode`.
     #[inline]
-    pub fn closure_delta(token_id: &TokenId, delta: i128, fee: Pips) -> Option<i128> {
-        Self::closure_supply_delta(token_id, Self::supply_delta(token_id, delta, fee)?, fee)
+    pub fn closure_delta(
+        token_id: &TokenId,
+        delta: i128,
+        fee: Pips,
+        mode: FeeMode,
+    ) -> Option<i128> {
+        Self::closure_supply_delta(
+            token_id,
+            Self::supply_delta(token_id, delta, fee, mode)?,
+            fee,
+            mode,
+        )
     }
 
     /// Returns total supply delta from token delta
     #[inline]
-    fn supply_delta(token_id: &TokenId, delta: i128, fee: Pips) -> Option<i128> {
-        if delta < 0 {
-            // fee is taken only on negative deltas (i.e. token_in)
-            delta.checked_mul_div_ceil(
+    fn supply_delta(token_id: &TokenId, delta: i128, fee: Pips, mode: FeeMode) -> Option<i128> {
+        match mode {
+            // fee is taken on top of negative deltas (i.e. token_in)
+            FeeMode::TokenIn if delta < 0 => delta.checked_mul_div_ceil(
                 Self::token_fee(token_id, delta.unsigned_abs(), fee)
                     .invert()
                     .as_pips()
                     .into(),
                 Pips::MAX.as_pips().into(),
-            )
-        } else {
-            // token_out
-            Some(delta)
+            ),
+            // fee is deducted from positive deltas (i.e. token_out)
+            // and deposited to fee collector, so the supply is unchanged
+            FeeMode::TokenIn | FeeMode::TokenOut => Some(delta),
         }
     }
 
     /// Returns closure for total supply delta that should be given in
     /// a single [`TokenDiff`] to successfully execute [`TokenDiff`] with
-    /// given `delta` on the same token assuming given `fee`.
+    /// given `delta` on the same token assuming given `fee` and `mode`.
     #[inline]
-    pub fn closure_supply_delta(token_id: &TokenId, delta: i128, fee: Pips) -> Option<i128> {
+    pub fn closure_supply_delta(
+        token_id: &TokenId,
+        delta: i128,
+        fee: Pips,
+        mode: FeeMode,
+    ) -> Option<i128> {
         let closure = delta.checked_neg()?;
-        if closure < 0 {
-            // fee is taken only on negative deltas (i.e. token_in)
-            closure.checked_mul_div_euclid(
+        match mode {
+            // fee is taken on top of negative deltas (i.e. token_in)
+            FeeMode::TokenIn if closure < 0 => closure.checked_mul_div_euclid(
                 Pips::MAX.as_pips().into(),
                 Self::token_fee(token_id, delta.unsigned_abs(), fee)
                     .invert()
                     .as_pips()
                     .into(),
-            )
-        } else {
-            // token_out
-            Some(closure)
+            ),
+            // fee is deducted from positive deltas (i.e. token_out)
+            FeeMode::TokenIn | FeeMode::TokenOut => Some(closure),
+        }
+    }
+
+    /// Returns amount to be received by signer for positive `delta`
+    /// on `token_out` when fees are charged in [`FeeMode::TokenOut`]
+    #[inline]
+    pub fn net_amount_out(token_id: &TokenId, delta: u128, fee: Pips) -> u128 {
+        delta - Self::token_fee(token_id, delta, fee).fee_ceil(delta)
+    }
+
+    /// Returns minimum positive delta on `token_out` to be signed in
+    /// [`FeeMode::TokenOut`] for signer to receive at least `net_amount`,
+    /// i.e. the inverse of [`TokenDiff::net_amount_out`]
+    pub fn gross_amount_out(token_id: &TokenId, net_amount: u128, fee: Pips) -> Option<u128> {
+        if Self::token_fee(token_id, net_amount, fee).fee_ceil(net_amount) == 0 {
+            return Some(net_amount);
         }
+        net_amount.checked_mul_div_ceil(Pips::MAX.as_pips().into(), fee.invert().as_pips().into())
     }
 
     #[inline]
@@ -216,6 +434,8 @@ mod tests {
     use itertools::Itertools;
     use rstest::rstest;
 
+    use crate::fees::{FeeOverrides, FeesConfig, TokenPair};
+
     use super::*;
 
     #[rstest]
@@ -239,15 +459,16 @@ mod tests {
             Pips::ONE_PERCENT * 50,
         )]
         fee: Pips,
+        #[values(FeeMode::TokenIn, FeeMode::TokenOut)] mode: FeeMode,
     ) {
         let (token_id, delta) = token_delta;
-        let closure = TokenDiff::closure_delta(&token_id, delta, fee).unwrap();
+        let closure = TokenDiff::closure_delta(&token_id, delta, fee, mode).unwrap();
 
         assert_eq!(
-            TokenDiff::supply_delta(&token_id, delta, fee).unwrap()
-                + TokenDiff::supply_delta(&token_id, closure, fee).unwrap(),
+            TokenDiff::supply_delta(&token_id, delta, fee, mode).unwrap()
+                + TokenDiff::supply_delta(&token_id, closure, fee, mode).unwrap(),
             0,
-            "invariant violated for {token_id}: delta: {delta}, closure: {closure}, fee: {fee}",
+            "invariant violated for {token_id}: delta: {delta}, closure: {closure}, fee: {fee}, mode: {mode:?}",
         );
     }
 
@@ -294,9 +515,18 @@ mod tests {
                 .unwrap(),
                 TokenDeltas::default()
                     .with_add_deltas([
-                        (t1.clone(), TokenDiff::closure_delta(&t1, d1, fee).unwrap()),
-                        (t2.clone(), TokenDiff::closure_delta(&t2, d2, fee).unwrap()),
-                        (t3.clone(), TokenDiff::closure_delta(&t3, d3, fee).unwrap()),
+                        (
+                            t1.clone(),
+                            TokenDiff::closure_delta(&t1, d1, fee, FeeMode::TokenIn).unwrap()
+                        ),
+                        (
+                            t2.clone(),
+                            TokenDiff::closure_delta(&t2, d2, fee, FeeMode::TokenIn).unwrap()
+                        ),
+                        (
+                            t3.clone(),
+                            TokenDiff::closure_delta(&t3, d3, fee, FeeMode::TokenIn).unwrap()
+                        ),
                     ])
                     .unwrap(),
                 "d1: {d1}, d2: {d2}, d3: {d3}"
@@ -304,6 +534,140 @@ mod tests {
         }
     }
 
+    #[test]
+    fn as_pair() {
+        let [t1, t2, t3] = ["ft1", "ft2", "ft3"].map(|t| TokenId::Nep141(t.parse().unwrap()));
+        let diff = |deltas: &[(&TokenId, i128)]| TokenDiff {
+            diff: TokenDeltas::default()
+                .with_add_deltas(deltas.iter().map(|(t, d)| ((*t).clone(), *d)))
+                .unwrap(),
+            ..Default::default()
+        };
+
+        assert_eq!(diff(&[(&t1, 100), (&t2, -200)]).as_pair(), Some((&t2, &t1)));
+        assert_eq!(diff(&[(&t1, -100)]).as_pair(), None);
+        assert_eq!(diff(&[(&t1, -100), (&t2, -200)]).as_pair(), None);
+        assert_eq!(diff(&[(&t1, -100), (&t2, 200), (&t3, 300)]).as_pair(), None);
+    }
+
+    #[rstest]
+    #[test]
+    fn closure_deltas_token_fees(#[values(FeeMode::TokenIn, FeeMode::TokenOut)] fee_mode: FeeMode) {
+        let [t1, t2] = ["ft1", "ft2"].map(|t| TokenId::Nep141(t.parse().unwrap()));
+        let config = FeesConfig {
+            fee: Pips::ONE_PERCENT,
+            fee_collector: "fees.near".parse().unwrap(),
+            fee_collector_shares: Default::default(),
+            referral_fee: Pips::ZERO,
+            fee_mode,
+        };
+        let overrides = FeeOverrides {
+            token_fees: [(t1.clone(), Pips::ONE_BIP)].into(),
+            pair_fees: Default::default(),
+        };
+
+        assert_eq!(
+            TokenDiff::closure_deltas(
+                [(t1.clone(), -10_000), (t2.clone(), -10_000)],
+                config.with_overrides(&overrides)
+            )
+            .unwrap(),
+            TokenDeltas::default()
+                .with_add_deltas([
+                    (
+                        t1.clone(),
+                        TokenDiff::closure_delta(&t1, -10_000, Pips::ONE_BIP, fee_mode).unwrap()
+                    ),
+                    (
+                        t2.clone(),
+                        TokenDiff::closure_delta(&t2, -10_000, Pips::ONE_PERCENT, fee_mode)
+                            .unwrap()
+                    ),
+                ])
+                .unwrap(),
+        );
+    }
+
+    #[rstest]
+    #[test]
+    fn closure_pair_fees(#[values(FeeMode::TokenIn, FeeMode::TokenOut)] fee_mode: FeeMode) {
+        let [t1, t2] = ["ft1", "ft2"].map(|t| TokenId::Nep141(t.parse().unwrap()));
+        let config = FeesConfig {
+            fee: Pips::ONE_PERCENT,
+            fee_collector: "fees.near".parse().unwrap(),
+            fee_collector_shares: Default::default(),
+            referral_fee: Pips::ZERO,
+            fee_mode,
+        };
+        let overrides = FeeOverrides {
+            token_fees: Default::default(),
+            pair_fees: [(
+                TokenPair::new(t1.clone(), t2.clone()).unwrap(),
+                Pips::ONE_BIP,
+            )]
+            .into(),
+        };
+
+        // both the diff and its closure swap the same pair
+        assert_eq!(
+            TokenDiff {
+                diff: TokenDeltas::default()
+                    .with_add_deltas([(t1.clone(), -10_000), (t2.clone(), 10_000)])
+                    .unwrap(),
+                memo: None,
+                referral: None,
+                fee_mode,
+            }
+            .closure(config.with_overrides(&overrides))
+            .unwrap(),
+            TokenDeltas::default()
+                .with_add_deltas([
+                    (
+                        t1.clone(),
+                        TokenDiff::closure_delta(&t1, -10_000, Pips::ONE_BIP, fee_mode).unwrap()
+                    ),
+                    (
+                        t2.clone(),
+                        TokenDiff::closure_delta(&t2, 10_000, Pips::ONE_BIP, fee_mode).unwrap()
+                    ),
+                ])
+                .unwrap(),
+        );
+    }
+
+    #[rstest]
+    #[test]
+    fn gross_amount_out(
+        #[values(
+            TokenId::Nep141("ft.near".parse().unwrap()),
+            TokenId::Nep171("nft.near".parse().unwrap(), "1".to_string()),
+            TokenId::Nep245("mt.near".parse().unwrap(), "ft1".to_string()),
+        )]
+        token_id: TokenId,
+        #[values(
+            Pips::ZERO,
+            Pips::ONE_PIP,
+            Pips::ONE_BIP,
+            Pips::ONE_PERCENT,
+            Pips::ONE_PERCENT * 50,
+        )]
+        fee: Pips,
+    ) {
+        for net_amount in [0, 1, 2, 3, 99, 100, 101, 10_000, 1_000_000_007] {
+            let gross = TokenDiff::gross_amount_out(&token_id, net_amount, fee).unwrap();
+            assert!(
+                TokenDiff::net_amount_out(&token_id, gross, fee) >= net_amount,
+                "not enough for {token_id}: net: {net_amount}, gross: {gross}, fee: {fee}",
+            );
+            if gross > 0 {
+                assert!(
+                    TokenDiff::net_amount_out(&token_id, gross - 1, fee) < net_amount,
+                    "not minimal for {token_id}: net: {net_amount}, gross: {gross}, fee: {fee}",
+                );
+            }
+        }
+    }
+
     #[rstest]
     #[test]
     fn arbitrage_means_somebody_looses(#[values(Pips::ZERO, Pips::ONE_BIP)] fee: Pips) {
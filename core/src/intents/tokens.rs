@@ -1,12 +1,12 @@
-use std::collections::BTreeMap;
+use std::{borrow::Cow, collections::BTreeMap};
 
 use near_contract_standards::non_fungible_token;
-use near_sdk::{json_types::U128, near, AccountId, AccountIdRef, CryptoHash, NearToken};
+use near_sdk::{json_types::U128, near, AccountId, AccountIdRef, CryptoHash, Gas, NearToken};
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::{
     engine::{Engine, Inspector, State},
-    tokens::TokenAmounts,
+    tokens::{TokenAmounts, TokenId},
     DefuseError, Result,
 };
 
@@ -57,6 +57,48 @@ impl ExecutableIntent for Transfer {
     }
 }
 
+/// Execute multiple [`Transfer`]s from the signer in given order
+/// under a single signature and nonce
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct BatchTransfer {
+    pub transfers: Vec<Transfer>,
+}
+
+impl ExecutableIntent for BatchTransfer {
+    fn execute_intent<S, I>(
+        self,
+        sender_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if self.transfers.is_empty()
+            || self
+                .transfers
+                .iter()
+                .any(|transfer| sender_id == transfer.receiver_id || transfer.tokens.is_empty())
+        {
+            return Err(DefuseError::InvalidIntent);
+        }
+        engine
+            .inspector
+            .on_batch_transfer(sender_id, &self, intent_hash);
+        for transfer in self.transfers {
+            engine
+                .state
+                .internal_withdraw(sender_id, transfer.tokens.clone())?;
+            engine
+                .state
+                .internal_deposit(transfer.receiver_id, transfer.tokens)?;
+        }
+        Ok(())
+    }
+}
+
 #[near(serializers = [borsh, json])]
 #[derive(Debug, Clone)]
 pub struct FtWithdraw {
@@ -85,12 +127,15 @@ impl ExecutableIntent for FtWithdraw {
         self,
         owner_id: &AccountIdRef,
         engine: &mut Engine<S, I>,
-        _intent_hash: CryptoHash,
+        intent_hash: CryptoHash,
     ) -> Result<()>
     where
         S: State,
         I: Inspector,
     {
+        engine
+            .inspector
+            .on_withdraw(owner_id, &Withdrawal::Ft(Cow::Borrowed(&self)), intent_hash);
         engine.state.ft_withdraw(owner_id, self)
     }
 }
@@ -123,12 +168,17 @@ impl ExecutableIntent for NftWithdraw {
         self,
         owner_id: &AccountIdRef,
         engine: &mut Engine<S, I>,
-        _intent_hash: CryptoHash,
+        intent_hash: CryptoHash,
     ) -> Result<()>
     where
         S: State,
         I: Inspector,
     {
+        engine.inspector.on_withdraw(
+            owner_id,
+            &Withdrawal::Nft(Cow::Borrowed(&self)),
+            intent_hash,
+        );
         engine.state.nft_withdraw(owner_id, self)
     }
 }
@@ -161,12 +211,15 @@ impl ExecutableIntent for MtWithdraw {
         self,
         owner_id: &AccountIdRef,
         engine: &mut Engine<S, I>,
-        _intent_hash: CryptoHash,
+        intent_hash: CryptoHash,
     ) -> Result<()>
     where
         S: State,
         I: Inspector,
     {
+        engine
+            .inspector
+            .on_withdraw(owner_id, &Withdrawal::Mt(Cow::Borrowed(&self)), intent_hash);
         engine.state.mt_withdraw(owner_id, self)
     }
 }
@@ -188,12 +241,150 @@ impl ExecutableIntent for NativeWithdraw {
         self,
         owner_id: &AccountIdRef,
         engine: &mut Engine<S, I>,
-        _intent_hash: CryptoHash,
+        intent_hash: CryptoHash,
     ) -> Result<()>
     where
         S: State,
         I: Inspector,
     {
+        engine.inspector.on_withdraw(
+            owner_id,
+            &Withdrawal::Native(Cow::Borrowed(&self)),
+            intent_hash,
+        );
         engine.state.native_withdraw(owner_id, self)
     }
 }
+
+/// Prepay storage for `account_id` (see NEP-145), registering it if
+/// needed. The amount will be subtracted from user's NEP-141 `wNEAR`
+/// balance.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct StorageDeposit {
+    pub account_id: AccountId,
+    pub amount: NearToken,
+}
+
+impl ExecutableIntent for StorageDeposit {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        owner_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if self.amount.is_zero() {
+            return Err(DefuseError::InvalidIntent);
+        }
+        engine.inspector.on_withdraw(
+            owner_id,
+            &Withdrawal::StorageDeposit(Cow::Borrowed(&self)),
+            intent_hash,
+        );
+        engine.state.storage_deposit(owner_id, self)
+    }
+}
+
+/// Withdraw given tokens and deliver them to `receiver_id` contract
+/// with `msg` in a single intent: NEP-141 tokens are sent via separate
+/// `ft_transfer_call()` each, while NEP-245 tokens are batched into one
+/// `mt_batch_transfer_call()` per token contract. Any amounts not used
+/// by `receiver_id` are refunded back to the signer.
+/// NOTE: NEP-171 tokens are not supported
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub receiver_id: AccountId,
+
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    pub tokens: TokenAmounts,
+
+    /// Message to pass to `ft_on_transfer()` / `mt_on_transfer()`
+    /// of `receiver_id`
+    pub msg: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+
+    /// Gas to attach to each `*_transfer_call()`, so that `receiver_id`
+    /// has enough of it to compose further calls (e.g. swap on a DEX and
+    /// deposit the result back). Defaults to the gas used by
+    /// [`FtWithdraw`] and [`MtWithdraw`]. Can't exceed [`Call::MAX_GAS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas: Option<Gas>,
+}
+
+impl Call {
+    /// Upper bound for [`Call::gas`], so that a single call can't
+    /// starve the rest of the intents and refund callbacks
+    pub const MAX_GAS: Gas = Gas::from_tgas(200);
+}
+
+impl ExecutableIntent for Call {
+    #[inline]
+    fn execute_intent<S, I>(
+        self,
+        owner_id: &AccountIdRef,
+        engine: &mut Engine<S, I>,
+        intent_hash: CryptoHash,
+    ) -> Result<()>
+    where
+        S: State,
+        I: Inspector,
+    {
+        if self.tokens.is_empty()
+            || self
+                .tokens
+                .iter()
+                .any(|(token_id, _)| matches!(token_id, TokenId::Nep171(..)))
+            || self.gas.is_some_and(|gas| gas > Self::MAX_GAS)
+        {
+            return Err(DefuseError::InvalidIntent);
+        }
+        engine.inspector.on_withdraw(
+            owner_id,
+            &Withdrawal::Call(Cow::Borrowed(&self)),
+            intent_hash,
+        );
+        engine.state.call(owner_id, self)
+    }
+}
+
+/// Tokens leaving the contract as a result of executing an intent
+#[near(serializers = [json])]
+#[serde(tag = "withdrawal", rename_all = "snake_case")]
+#[derive(Debug, Clone)]
+pub enum Withdrawal<'a> {
+    Ft(Cow<'a, FtWithdraw>),
+    Nft(Cow<'a, NftWithdraw>),
+    Mt(Cow<'a, MtWithdraw>),
+    Native(Cow<'a, NativeWithdraw>),
+    StorageDeposit(Cow<'a, StorageDeposit>),
+    Call(Cow<'a, Call>),
+}
+
+impl Withdrawal<'_> {
+    pub fn into_owned(self) -> Withdrawal<'static> {
+        match self {
+            Self::Ft(w) => Withdrawal::Ft(Cow::Owned(w.into_owned())),
+            Self::Nft(w) => Withdrawal::Nft(Cow::Owned(w.into_owned())),
+            Self::Mt(w) => Withdrawal::Mt(Cow::Owned(w.into_owned())),
+            Self::Native(w) => Withdrawal::Native(Cow::Owned(w.into_owned())),
+            Self::StorageDeposit(w) => Withdrawal::StorageDeposit(Cow::Owned(w.into_owned())),
+            Self::Call(w) => Withdrawal::Call(Cow::Owned(w.into_owned())),
+        }
+    }
+}
@@ -18,6 +18,11 @@ where
         Self(BitMap256::new(bitmap))
     }
 
+    #[inline]
+    pub fn as_inner_mut(&mut self) -> &mut T {
+        self.0.as_inner_mut()
+    }
+
     #[inline]
     pub fn is_used(&self, n: Nonce) -> bool {
         self.0.get_bit(n)
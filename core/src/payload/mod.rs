@@ -1,5 +1,6 @@
 pub mod erc191;
 pub mod multi;
+pub mod multisig;
 pub mod nep413;
 pub mod raw;
 pub mod webauthn;
@@ -10,10 +11,10 @@ use defuse_serde_utils::base64::Base64;
 use impl_tools::autoimpl;
 use near_sdk::{near, AccountId};
 use serde_with::serde_as;
+use thiserror::Error as ThisError;
 
 use crate::{Deadline, Nonce};
 
-// TODO: add version
 #[cfg_attr(
     all(feature = "abi", not(target_arch = "wasm32")),
     serde_as(schemars = true)
@@ -27,6 +28,11 @@ use crate::{Deadline, Nonce};
 #[autoimpl(DerefMut using self.message)]
 #[derive(Debug, Clone)]
 pub struct DefusePayload<T> {
+    /// Messages signed without explicit version are treated
+    /// as [`PayloadVersion::V1`]
+    #[serde(default)]
+    pub version: PayloadVersion,
+
     pub signer_id: AccountId,
     pub verifying_contract: AccountId,
     pub deadline: Deadline,
@@ -41,6 +47,55 @@ pub struct DefusePayload<T> {
     pub message: T,
 }
 
+/// Version of [`DefusePayload`] format and semantics of intents in it.
+/// Unsupported versions are rejected on deserialization, so that
+/// previously signed messages can never be reinterpreted under
+/// different semantics.
+#[near(serializers = [borsh, json])]
+#[serde(try_from = "u32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PayloadVersion(u32);
+
+impl PayloadVersion {
+    pub const V1: Self = Self(1);
+
+    pub const LATEST: Self = Self::V1;
+
+    /// All versions supported by this implementation
+    pub const SUPPORTED: &'static [Self] = &[Self::V1];
+
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for PayloadVersion {
+    #[inline]
+    fn default() -> Self {
+        Self::V1
+    }
+}
+
+impl TryFrom<u32> for PayloadVersion {
+    type Error = UnsupportedPayloadVersion;
+
+    #[inline]
+    fn try_from(version: u32) -> Result<Self, Self::Error> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|v| v.0 == version)
+            .ok_or(UnsupportedPayloadVersion(version))
+    }
+}
+
+#[derive(Debug, ThisError)]
+#[error("unsupported payload version: {0}")]
+pub struct UnsupportedPayloadVersion(pub u32);
+
+/// Implementations MUST deserialize [`DefusePayload::version`], so that
+/// unsupported versions get rejected before any intent is executed
 pub trait ExtractDefusePayload<T> {
     type Error;
 
@@ -66,3 +121,47 @@ mod examples {
         base64::engine::general_purpose::STANDARD.encode(Nonce::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json::{self, json};
+
+    use crate::intents::DefuseIntents;
+
+    use super::*;
+
+    fn payload(version: Option<u32>) -> serde_json::Value {
+        let mut payload = json!({
+            "signer_id": "user.near",
+            "verifying_contract": "intents.near",
+            "deadline": "2100-01-01T00:00:00Z",
+            "nonce": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+        });
+        if let Some(version) = version {
+            payload["version"] = version.into();
+        }
+        payload
+    }
+
+    #[test]
+    fn version_defaults_to_v1() {
+        let p: DefusePayload<DefuseIntents> = serde_json::from_value(payload(None)).unwrap();
+        assert_eq!(p.version, PayloadVersion::V1);
+    }
+
+    #[test]
+    fn supported_version() {
+        let p: DefusePayload<DefuseIntents> = serde_json::from_value(payload(Some(1))).unwrap();
+        assert_eq!(p.version, PayloadVersion::V1);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        for version in [0, 2, u32::MAX] {
+            assert!(
+                serde_json::from_value::<DefusePayload<DefuseIntents>>(payload(Some(version)))
+                    .is_err()
+            );
+        }
+    }
+}
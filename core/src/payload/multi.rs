@@ -1,8 +1,8 @@
 use defuse_crypto::{Payload, PublicKey, SignedPayload};
-use defuse_erc191::SignedErc191Payload;
-use defuse_nep413::SignedNep413Payload;
+use defuse_erc191::{Erc191Payload, SignedErc191Payload};
+use defuse_nep413::{Nep413Payload, SignedNep413Payload};
 use derive_more::derive::From;
-use near_sdk::{near, serde::de::DeserializeOwned, serde_json, CryptoHash};
+use near_sdk::{env, near, serde::de::DeserializeOwned, serde_json, CryptoHash};
 
 use super::{
     raw::SignedRawEd25519Payload, webauthn::SignedWebAuthnPayload, DefusePayload,
@@ -63,6 +63,79 @@ where
     }
 }
 
+/// [`MultiPayload`] without signature, i.e. exactly what is going to be
+/// signed under corresponding standard. Its hash equals to the hash of
+/// the signed one.
+#[near(serializers = [json])]
+#[serde(tag = "standard", rename_all = "snake_case")]
+#[derive(Debug, Clone)]
+pub enum UnsignedMultiPayload {
+    Nep413 {
+        payload: Nep413Payload,
+    },
+    Erc191 {
+        payload: Erc191Payload,
+    },
+    RawEd25519 {
+        payload: String,
+    },
+    #[serde(rename = "webauthn")]
+    WebAuthn {
+        payload: String,
+    },
+}
+
+impl From<MultiPayload> for UnsignedMultiPayload {
+    #[inline]
+    fn from(signed: MultiPayload) -> Self {
+        match signed {
+            MultiPayload::Nep413(signed) => Self::Nep413 {
+                payload: signed.payload,
+            },
+            MultiPayload::Erc191(signed) => Self::Erc191 {
+                payload: signed.payload,
+            },
+            MultiPayload::RawEd25519(signed) => Self::RawEd25519 {
+                payload: signed.payload,
+            },
+            MultiPayload::WebAuthn(signed) => Self::WebAuthn {
+                payload: signed.payload,
+            },
+        }
+    }
+}
+
+impl Payload for UnsignedMultiPayload {
+    #[inline]
+    fn hash(&self) -> CryptoHash {
+        match self {
+            Self::Nep413 { payload } => payload.hash(),
+            Self::Erc191 { payload } => payload.hash(),
+            Self::RawEd25519 { payload } | Self::WebAuthn { payload } => {
+                env::sha256_array(payload.as_bytes())
+            }
+        }
+    }
+}
+
+impl<T> ExtractDefusePayload<T> for UnsignedMultiPayload
+where
+    T: DeserializeOwned,
+{
+    type Error = serde_json::Error;
+
+    #[inline]
+    fn extract_defuse_payload(self) -> Result<DefusePayload<T>, Self::Error> {
+        match self {
+            Self::Nep413 { payload } => payload.extract_defuse_payload(),
+            Self::Erc191 { payload } => serde_json::from_str(&payload.0),
+            Self::RawEd25519 { payload } | Self::WebAuthn { payload } => {
+                serde_json::from_str(&payload)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use near_sdk::bs58;
@@ -76,6 +149,9 @@ mod tests {
             bs58::encode(p.hash()).into_string(),
             "8LKE47o44ybZQR9ozLyDnvMDTh4Ao5ipy2mJWsYByG5Q"
         );
+
+        let unsigned = UnsignedMultiPayload::from(p.clone());
+        assert_eq!(unsigned.hash(), p.hash());
         assert_eq!(
             p.verify().unwrap(),
             "ed25519:8rVvtHWFr8hasdQGGD5WiQBTyr4iH2ruEPPVfj491RPN"
@@ -0,0 +1,125 @@
+use core::mem;
+use std::collections::BTreeSet;
+
+use defuse_crypto::{Payload, PublicKey, SignedPayload};
+use derive_more::derive::From;
+use near_sdk::{
+    near,
+    serde::de::{DeserializeOwned, Error as _},
+    serde_json, CryptoHash,
+};
+
+use super::{multi::MultiPayload, DefusePayload, ExtractDefusePayload};
+
+/// Payload signed by one or more public keys of the same account,
+/// i.e. for accounts with threshold greater than one.
+/// All signatures MUST be made over the same payload under the same
+/// standard: different standards (e.g. NEP-413 and ERC-191) sign
+/// different messages, so their hashes never match. Hence, all keys
+/// used in a single multisig payload must support the same standard.
+#[near(serializers = [borsh, json])]
+#[serde(untagged)]
+#[derive(Debug, Clone, From)]
+pub enum MultiSigPayload {
+    Single(MultiPayload),
+    Multi(Vec<MultiPayload>),
+}
+
+impl MultiSigPayload {
+    /// Returns whether all payloads are signed under the same standard
+    #[must_use]
+    pub fn is_single_standard(&self) -> bool {
+        match self {
+            Self::Single(_) => true,
+            Self::Multi(payloads) => payloads
+                .windows(2)
+                .all(|pair| mem::discriminant(&pair[0]) == mem::discriminant(&pair[1])),
+        }
+    }
+
+    /// Verifies all signatures and returns hash of the signed payload
+    /// along with distinct public keys it was signed with.
+    /// Returns `None` if any of signatures is invalid or if they were
+    /// made over different payloads.
+    pub fn verify(&self) -> Option<(CryptoHash, BTreeSet<PublicKey>)> {
+        match self {
+            Self::Single(payload) => Some((payload.hash(), [payload.verify()?].into())),
+            Self::Multi(payloads) => {
+                let hash = payloads.first()?.hash();
+                payloads
+                    .iter()
+                    .map(|payload| {
+                        if payload.hash() != hash {
+                            return None;
+                        }
+                        payload.verify()
+                    })
+                    .collect::<Option<_>>()
+                    .map(|public_keys| (hash, public_keys))
+            }
+        }
+    }
+}
+
+impl<T> ExtractDefusePayload<T> for MultiSigPayload
+where
+    T: DeserializeOwned,
+{
+    type Error = serde_json::Error;
+
+    #[inline]
+    fn extract_defuse_payload(self) -> Result<DefusePayload<T>, Self::Error> {
+        match self {
+            Self::Single(payload) => payload.extract_defuse_payload(),
+            Self::Multi(payloads) => payloads
+                .into_iter()
+                .next()
+                .ok_or_else(|| serde_json::Error::custom("no payloads"))?
+                .extract_defuse_payload(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &str = r#"{"standard":"raw_ed25519","payload":"{\"signer_id\":\"74affa71ab030d400fdfa1bed033dfa6fd3ae34f92d17c046ebe368e80d53751\",\"verifying_contract\":\"intents.near\",\"deadline\":{\"timestamp\":1732035219},\"nonce\":\"XVoKfmScb3G+XqH9ke/fSlJ/3xO59sNhCxhpG821BH8=\",\"intents\":[{\"intent\":\"token_diff\",\"diff\":{\"nep141:base-0x833589fcd6edb6e08f4c7c32d4f71b54bda02913.omft.near\":\"-1000\",\"nep141:eth-0xdac17f958d2ee523a2206206994597c13d831ec7.omft.near\":\"998\"}}]}","public_key":"ed25519:8rVvtHWFr8hasdQGGD5WiQBTyr4iH2ruEPPVfj491RPN","signature":"ed25519:3vtbNQJHZfuV1s5DykzyjkbNLc583hnkrhTz57eDhd966iqzkor6Twgr4Loh2C195SCSEsiGfrd6KcxpjNq9ZbVj"}"#;
+
+    #[test]
+    fn single_is_backwards_compatible() {
+        let single: MultiPayload = serde_json::from_str(PAYLOAD).unwrap();
+        let p: MultiSigPayload = serde_json::from_str(PAYLOAD).unwrap();
+        assert!(matches!(p, MultiSigPayload::Single(_)));
+        assert_eq!(
+            p.verify().unwrap(),
+            (single.hash(), [single.verify().unwrap()].into())
+        );
+    }
+
+    #[test]
+    fn distinct_public_keys() {
+        let p: MultiSigPayload = serde_json::from_str(&format!("[{PAYLOAD},{PAYLOAD}]")).unwrap();
+        assert!(matches!(p, MultiSigPayload::Multi(_)));
+        let (_hash, public_keys) = p.verify().unwrap();
+        assert_eq!(public_keys.len(), 1);
+    }
+
+    #[test]
+    fn mixed_standards() {
+        let webauthn = r#"{"standard":"webauthn","payload":"{\"signer_id\":\"19a8cd22b37802c3cbc0031f55c70f3858ac48dbfb7697c435da637fea0e0e47\",\"verifying_contract\":\"intents.near\",\"deadline\":{\"timestamp\":1732035219},\"nonce\":\"XVoKfmScb3G+XqH9ke/fSlJ/3xO59sNhCxhpG821BH8=\",\"intents\":[{\"intent\":\"token_diff\",\"diff\":{\"nep141:base-0x833589fcd6edb6e08f4c7c32d4f71b54bda02913.omft.near\":\"-1000\",\"nep141:eth-0xdac17f958d2ee523a2206206994597c13d831ec7.omft.near\":\"998\"}}]}","public_key":"ed25519:2jAUugnvWPvMaftKj5TDkyfsfxBwYjkMSf5MRtqDUMHY","signature":"ed25519:2yBp5oExa9BBZQf8habpjLUaSiprvT7srHrK38Bxt9zL1yrkQSeeXMLmkihKCd9frmTdk24YctUdzNN5nGqHWHgb","client_data_json":"{\"type\":\"webauthn.get\",\"challenge\":\"PfRFOFrLxCfyomuDryxhv6v2OzJIWqyMXaMikUYHSmY\",\"origin\":\"http://localhost:3000\"}","authenticator_data":"SZYN5YgOjGh0NBcPZHZgW4_krrmihjLHmVzzuoMdl2MFZ50DuA"}"#;
+
+        let same: MultiSigPayload =
+            serde_json::from_str(&format!("[{PAYLOAD},{PAYLOAD}]")).unwrap();
+        assert!(same.is_single_standard());
+
+        let mixed: MultiSigPayload =
+            serde_json::from_str(&format!("[{PAYLOAD},{webauthn}]")).unwrap();
+        assert!(!mixed.is_single_standard());
+    }
+
+    #[test]
+    fn empty() {
+        assert!(MultiSigPayload::Multi(Vec::new()).verify().is_none());
+    }
+}
@@ -8,13 +8,18 @@ use near_sdk::{
 
 use crate::Deadline;
 
-use super::{DefusePayload, ExtractDefusePayload};
+use super::{DefusePayload, ExtractDefusePayload, PayloadVersion};
 
 #[near(serializers = [json])]
 #[autoimpl(Deref using self.message)]
 #[autoimpl(DerefMut using self.message)]
 #[derive(Debug, Clone)]
 pub struct Nep413DefuseMessage<T> {
+    /// Messages signed without explicit version are treated
+    /// as [`PayloadVersion::V1`]
+    #[serde(default)]
+    pub version: PayloadVersion,
+
     pub signer_id: AccountId,
 
     pub deadline: Deadline,
@@ -31,12 +36,14 @@ where
 
     fn extract_defuse_payload(self) -> Result<DefusePayload<T>, Self::Error> {
         let Nep413DefuseMessage {
+            version,
             signer_id,
             deadline,
             message,
         } = serde_json::from_str(&self.message)?;
 
         Ok(DefusePayload {
+            version,
             signer_id,
             verifying_contract: self.recipient.parse().map_err(|_| {
                 de::Error::invalid_value(de::Unexpected::Str(&self.recipient), &"AccountId")
@@ -126,6 +126,11 @@ impl<T> TokenAmounts<T> {
     pub fn into_inner(self) -> T {
         self.0
     }
+
+    #[inline]
+    pub fn as_inner_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
 }
 
 impl<T> TokenAmounts<T>
@@ -1,7 +1,12 @@
 use std::collections::HashSet;
 
-use defuse_core::{crypto::PublicKey, Nonce};
+use defuse_core::{
+    accounts::{Guardians, Lockdown, PublicKeyScope, Recovery},
+    crypto::PublicKey,
+    Nonce,
+};
 use defuse_serde_utils::base64::AsBase64;
+use near_plugins::AccessControllable;
 use near_sdk::{ext_contract, AccountId};
 
 #[ext_contract(ext_public_key_manager)]
@@ -12,6 +17,14 @@ pub trait AccountManager {
     /// Returns set of public keys registered for given account
     fn public_keys_of(&self, account_id: &AccountId) -> HashSet<PublicKey>;
 
+    /// Returns scope of given public key, or `None` if it has full access
+    /// to the account or doesn't exist
+    fn public_key_scope_of(
+        &self,
+        account_id: &AccountId,
+        public_key: &PublicKey,
+    ) -> Option<PublicKeyScope>;
+
     /// Registers or re-activates `public_key` under the caller account_id.
     ///
     /// NOTE: MUST attach 1 yⓃ for security purposes.
@@ -23,6 +36,44 @@ pub trait AccountManager {
     /// NOTE: MUST attach 1 yⓃ for security purposes.
     fn remove_public_key(&mut self, public_key: &PublicKey);
 
+    /// Returns minimum number of distinct public keys required to sign
+    /// intents on behalf of given account
+    fn threshold_of(&self, account_id: &AccountId) -> u16;
+
+    /// Sets minimum number of distinct public keys required to sign
+    /// intents on behalf of the caller account_id.
+    /// It can't be zero and can't exceed the number of registered public keys.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn set_threshold(&mut self, threshold: u16);
+
+    /// Returns active lockdown of given account, if any.
+    /// See `lock_account` intent.
+    fn lockdown_of(&self, account_id: &AccountId) -> Option<Lockdown>;
+
+    /// Returns guardians of given account, if any
+    fn guardians_of(&self, account_id: &AccountId) -> Option<Guardians>;
+
+    /// Sets guardians of the caller account_id, who can jointly recover
+    /// access to it via `approve_recovery` intents. Empty set of guardians
+    /// removes them. Cancels pending recovery, if any.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn set_guardians(&mut self, guardians: Guardians);
+
+    /// Returns pending recovery of given account, if any
+    fn recovery_of(&self, account_id: &AccountId) -> Option<Recovery>;
+
+    /// Cancels pending recovery of the caller account_id.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn cancel_recovery(&mut self);
+
+    /// Replaces all public keys of given account with the one approved
+    /// by its guardians, once the recovery delay has passed.
+    /// Can be called by anyone.
+    fn finalize_recovery(&mut self, account_id: AccountId) -> PublicKey;
+
     /// Returns whether given nonce was already used by the account
     /// NOTE: nonces are non-sequential and follow
     /// [permit2 nonce schema](https://docs.uniswap.org/contracts/permit2/reference/signature-transfer#nonce-schema).
@@ -31,3 +82,22 @@ pub trait AccountManager {
     /// NOTE: MUST attach 1 yⓃ for security purposes.
     fn invalidate_nonces(&mut self, nonces: Vec<AsBase64<Nonce>>);
 }
+
+#[ext_contract(ext_force_account_locker)]
+pub trait AccountForceLocker: AccessControllable {
+    /// Returns whether given account is locked. Locked accounts can't
+    /// sign intents, transfer or withdraw tokens, but can still receive
+    /// deposits. Account owners can lock their own accounts with
+    /// `freeze_account` intent.
+    fn is_account_locked(&self, account_id: &AccountId) -> bool;
+
+    /// Locks given account. Returns `false` if it was already locked.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn force_lock_account(&mut self, account_id: AccountId) -> bool;
+
+    /// Unlocks given account. Returns `false` if it wasn't locked.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn force_unlock_account(&mut self, account_id: &AccountId) -> bool;
+}
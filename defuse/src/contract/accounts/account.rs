@@ -1,19 +1,27 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 
 use defuse_bitmap::{U248, U256};
 use defuse_core::{
-    accounts::{AccountEvent, PublicKeyEvent},
+    accounts::{
+        AccountEvent, Guardians, Lockdown, PublicKeyEvent, PublicKeyScope, Recovery,
+        RecoveryApprovedEvent, ThresholdChangedEvent,
+    },
     crypto::PublicKey,
     events::DefuseEvent,
-    Nonces,
+    intents::{htlc::Htlc, limit_order::LimitOrderState},
+    tokens::TokenId,
+    Deadline, DefuseError, Nonces, Result,
 };
-use defuse_near_utils::NestPrefix;
+use defuse_near_utils::{Lock, NestPrefix};
+use defuse_nep245::approval::Approval;
 use impl_tools::autoimpl;
 use near_sdk::{
-    borsh::BorshSerialize,
+    borsh::{self, BorshSerialize},
+    env,
+    json_types::U128,
     near,
-    store::{IterableSet, LookupMap},
-    AccountIdRef, BorshStorageKey, IntoStorageKey,
+    store::{IterableMap, IterableSet, LookupMap},
+    AccountId, AccountIdRef, BorshStorageKey, CryptoHash, IntoStorageKey, NearToken,
 };
 
 use super::AccountState;
@@ -27,13 +35,54 @@ pub struct Account {
 
     implicit_public_key_removed: bool,
     public_keys: IterableSet<PublicKey>,
+    public_key_scopes: LookupMap<PublicKey, PublicKeyScope>,
+    /// Keys added before it was tracked are not listed here
+    public_keys_added_at: LookupMap<PublicKey, Deadline>,
+    threshold: u16,
+    lockdown: Option<Lockdown>,
+    guardians: Option<Guardians>,
+    recovery: Option<Recovery>,
 
     pub state: AccountState,
 
+    limit_orders: IterableMap<CryptoHash, LimitOrderState>,
+    /// Position in [`Self::limit_orders`] to continue pruning from
+    limit_orders_prune_cursor: u32,
+
+    /// Hash-time-locked escrows sent by the account by their hash locks
+    htlcs: IterableMap<CryptoHash, Htlc>,
+
+    /// NEP-245 approvals of other accounts to transfer tokens
+    approvals: IterableMap<TokenId, BTreeMap<AccountId, Approval>>,
+    next_approval_id: u64,
+
+    /// Prepaid storage deposit
+    storage_deposit: NearToken,
+    /// Storage is paid by the contract, e.g. for fee collectors
+    storage_sponsored: bool,
+    /// Bytes paid by the contract on top of storage deposit, i.e. used
+    /// by accounts registered before storage deposits were required
+    sponsored_storage_usage: u64,
+    /// Bytes occupied by nested collections under [`Self::prefix`]
+    nested_storage_usage: u64,
+
     prefix: Vec<u8>,
 }
 
 impl Account {
+    /// Upper bound for key and value overhead of an entry in
+    /// [`Accounts`](super::Accounts), including the account id
+    /// stored in both of them
+    pub const ENTRY_STORAGE_OVERHEAD: u64 = 2 * (40 + 64) + 16;
+
+    /// Minimum storage usage of a newly registered account, enough to
+    /// cover the account itself along with a few token balances
+    pub const MIN_STORAGE_USAGE: u64 = 2048;
+
+    /// Maximum number of limit orders checked for expiration at once,
+    /// so that gas doesn't grow with the number of orders
+    pub const LIMIT_ORDERS_PRUNE_BATCH: u32 = 10;
+
     #[inline]
     pub fn new<S>(prefix: S, me: &AccountIdRef) -> Self
     where
@@ -47,7 +96,26 @@ impl Account {
             )),
             implicit_public_key_removed: !me.get_account_type().is_implicit(),
             public_keys: IterableSet::new(prefix.as_slice().nest(AccountPrefix::PublicKeys)),
+            public_key_scopes: LookupMap::new(
+                prefix.as_slice().nest(AccountPrefix::PublicKeyScopes),
+            ),
+            public_keys_added_at: LookupMap::new(
+                prefix.as_slice().nest(AccountPrefix::PublicKeysAddedAt),
+            ),
+            threshold: 1,
+            lockdown: None,
+            guardians: None,
+            recovery: None,
             state: AccountState::new(prefix.as_slice().nest(AccountPrefix::State)),
+            limit_orders: IterableMap::new(prefix.as_slice().nest(AccountPrefix::LimitOrders)),
+            limit_orders_prune_cursor: 0,
+            htlcs: IterableMap::new(prefix.as_slice().nest(AccountPrefix::Htlcs)),
+            approvals: IterableMap::new(prefix.as_slice().nest(AccountPrefix::Approvals)),
+            next_approval_id: 0,
+            storage_deposit: NearToken::from_yoctonear(0),
+            storage_sponsored: false,
+            sponsored_storage_usage: 0,
+            nested_storage_usage: 0,
             prefix,
         }
     }
@@ -71,13 +139,18 @@ impl Account {
 
     #[inline]
     fn maybe_add_public_key(&mut self, me: &AccountIdRef, public_key: PublicKey) -> bool {
-        if me == public_key.to_implicit_account_id() {
+        let added = if me == public_key.to_implicit_account_id() {
             let was_removed = self.implicit_public_key_removed;
             self.implicit_public_key_removed = false;
             was_removed
         } else {
             self.public_keys.insert(public_key)
+        };
+        if added {
+            self.public_keys_added_at
+                .insert(public_key, Deadline::now());
         }
+        added
     }
 
     #[inline]
@@ -99,6 +172,8 @@ impl Account {
 
     #[inline]
     fn maybe_remove_public_key(&mut self, me: &AccountIdRef, public_key: &PublicKey) -> bool {
+        self.public_key_scopes.remove(public_key);
+        self.public_keys_added_at.remove(public_key);
         if me == public_key.to_implicit_account_id() {
             let was_removed = self.implicit_public_key_removed;
             self.implicit_public_key_removed = true;
@@ -123,6 +198,176 @@ impl Account {
         )
     }
 
+    #[inline]
+    pub fn public_key_scope(&self, public_key: &PublicKey) -> Option<&PublicKeyScope> {
+        self.public_key_scopes.get(public_key)
+    }
+
+    #[inline]
+    pub fn public_key_added_at(&self, public_key: &PublicKey) -> Option<Deadline> {
+        self.public_keys_added_at.get(public_key).copied()
+    }
+
+    #[inline]
+    pub fn set_public_key_scope(&mut self, public_key: PublicKey, scope: PublicKeyScope) {
+        self.public_key_scopes.insert(public_key, scope);
+    }
+
+    #[inline]
+    pub const fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    #[inline]
+    pub fn set_threshold(&mut self, me: &AccountIdRef, threshold: u16) {
+        self.threshold = threshold;
+
+        DefuseEvent::ThresholdChanged(AccountEvent::new(
+            Cow::Borrowed(me),
+            ThresholdChangedEvent { threshold },
+        ))
+        .emit();
+    }
+
+    #[inline]
+    pub const fn lockdown(&self) -> Option<&Lockdown> {
+        self.lockdown.as_ref()
+    }
+
+    #[inline]
+    pub fn set_lockdown(&mut self, me: &AccountIdRef, lockdown: Lockdown) {
+        DefuseEvent::AccountLockedDown(AccountEvent::new(Cow::Borrowed(me), lockdown.clone()))
+            .emit();
+
+        self.lockdown = Some(lockdown);
+    }
+
+    #[inline]
+    pub const fn guardians(&self) -> Option<&Guardians> {
+        self.guardians.as_ref()
+    }
+
+    /// Empty `guardians` removes them. Cancels pending recovery, if any.
+    #[inline]
+    pub fn set_guardians(&mut self, me: &AccountIdRef, guardians: Guardians) {
+        self.cancel_recovery(me);
+
+        DefuseEvent::GuardiansChanged(AccountEvent::new(Cow::Borrowed(me), guardians.clone()))
+            .emit();
+
+        self.guardians = (!guardians.is_empty()).then_some(guardians);
+    }
+
+    #[inline]
+    pub const fn recovery(&self) -> Option<&Recovery> {
+        self.recovery.as_ref()
+    }
+
+    pub fn approve_recovery(
+        &mut self,
+        me: &AccountIdRef,
+        guardian_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<()> {
+        let guardians = self.guardians.as_ref().ok_or(DefuseError::NotGuardian)?;
+        let recovery = self.recovery.get_or_insert_default();
+        let just_scheduled = recovery.approve(guardians, guardian_id.clone(), public_key)?;
+
+        DefuseEvent::RecoveryApproved(AccountEvent::new(
+            Cow::Borrowed(me),
+            RecoveryApprovedEvent {
+                guardian_id: Cow::Owned(guardian_id),
+                public_key: Cow::Borrowed(&public_key),
+            },
+        ))
+        .emit();
+
+        if let Some(scheduled) = recovery.scheduled.as_ref().filter(|_| just_scheduled) {
+            DefuseEvent::RecoveryScheduled(AccountEvent::new(Cow::Borrowed(me), scheduled.clone()))
+                .emit();
+        }
+
+        Ok(())
+    }
+
+    /// Returns `false` if there was no pending recovery
+    #[inline]
+    pub fn cancel_recovery(&mut self, me: &AccountIdRef) -> bool {
+        if self.recovery.take().is_none() {
+            return false;
+        }
+
+        DefuseEvent::RecoveryCancelled(AccountEvent::new(Cow::Borrowed(me), ())).emit();
+
+        true
+    }
+
+    /// Replaces all public keys of the account with the one approved by
+    /// guardians, once the recovery delay has passed. Scopes are cleared,
+    /// so the new key has full access, threshold is reset to a single
+    /// signature and lockdown is lifted.
+    pub fn finalize_recovery(&mut self, me: &AccountIdRef) -> Result<PublicKey> {
+        let public_key = self
+            .recovery
+            .as_ref()
+            .and_then(Recovery::ready)
+            .ok_or(DefuseError::RecoveryNotReady)?;
+        self.recovery = None;
+
+        let public_keys: Vec<_> = self.iter_public_keys(me).collect();
+        for pk in public_keys {
+            self.remove_public_key(me, &pk);
+        }
+        self.add_public_key(me, public_key);
+        // scopes of removed keys were removed along with them,
+        // make sure the new key doesn't inherit a stale one
+        self.public_key_scopes.remove(&public_key);
+        if self.threshold != 1 {
+            self.set_threshold(me, 1);
+        }
+        self.lockdown = None;
+
+        DefuseEvent::AccountRecovered(AccountEvent::new(
+            Cow::Borrowed(me),
+            PublicKeyEvent {
+                public_key: Cow::Borrowed(&public_key),
+            },
+        ))
+        .emit();
+
+        Ok(public_key)
+    }
+
+    /// Removes public keys of the account along with their scopes,
+    /// limit orders and approvals. All token balances must be zero and
+    /// no HTLCs can be pending. Used nonces are kept in storage, so that
+    /// intents signed before closing can't be replayed if the account
+    /// gets re-created. Returns storage deposit not covering them.
+    pub fn close(&mut self, me: &AccountIdRef) -> Result<NearToken> {
+        if !self.state.token_balances.is_empty() || !self.htlcs.is_empty() {
+            return Err(DefuseError::AccountNotEmpty);
+        }
+
+        let public_keys: Vec<_> = self.iter_public_keys(me).collect();
+        for public_key in public_keys {
+            self.remove_public_key(me, &public_key);
+        }
+        self.limit_orders.clear();
+        self.limit_orders_prune_cursor = 0;
+        self.approvals.clear();
+        self.flush_storage();
+
+        DefuseEvent::AccountClosed(AccountEvent::new(Cow::Borrowed(me), ())).emit();
+
+        Ok(self.storage_deposit.saturating_sub(
+            env::storage_byte_cost().saturating_mul(
+                self.nested_storage_usage
+                    .saturating_sub(self.sponsored_storage_usage)
+                    .into(),
+            ),
+        ))
+    }
+
     #[inline]
     pub fn is_nonce_used(&self, nonce: U256) -> bool {
         self.nonces.is_used(nonce)
@@ -132,6 +377,340 @@ impl Account {
     pub fn commit_nonce(&mut self, n: U256) -> bool {
         self.nonces.commit(n)
     }
+
+    #[inline]
+    pub fn limit_order(&self, order_hash: &CryptoHash) -> Option<&LimitOrderState> {
+        self.limit_orders.get(order_hash)
+    }
+
+    /// Returns `false` if the order was already placed. Expired orders
+    /// are pruned beforehand.
+    #[inline]
+    pub fn place_limit_order(&mut self, order_hash: CryptoHash, order: LimitOrderState) -> bool {
+        self.prune_limit_orders();
+        if self.limit_orders.contains_key(&order_hash) {
+            return false;
+        }
+        self.limit_orders.insert(order_hash, order);
+        true
+    }
+
+    pub fn fill_limit_order(
+        &mut self,
+        order_hash: CryptoHash,
+        amount_in: u128,
+    ) -> Result<LimitOrderState> {
+        let state = self
+            .limit_orders
+            .get_mut(&order_hash)
+            .ok_or(DefuseError::LimitOrderNotFound)?;
+        state
+            .fill(amount_in)
+            .ok_or(DefuseError::LimitOrderOverfilled)?;
+        let state = state.clone();
+        if state.is_filled() {
+            self.limit_orders.remove(&order_hash);
+        }
+        Ok(state)
+    }
+
+    #[inline]
+    pub fn cancel_limit_order(&mut self, order_hash: &CryptoHash) -> bool {
+        let cancelled = self.limit_orders.remove(order_hash).is_some();
+        self.prune_limit_orders();
+        cancelled
+    }
+
+    /// Removes expired limit orders among next
+    /// [`Self::LIMIT_ORDERS_PRUNE_BATCH`] ones, wrapping around at the
+    /// end. Fully filled orders are removed right away by
+    /// [`Self::fill_limit_order`].
+    pub fn prune_limit_orders(&mut self) {
+        let mut start = self.limit_orders_prune_cursor;
+        if start >= self.limit_orders.len() {
+            start = 0;
+        }
+        let expired: Vec<_> = self
+            .limit_orders
+            .iter()
+            .skip(start as usize)
+            .take(Self::LIMIT_ORDERS_PRUNE_BATCH as usize)
+            .filter(|(_, state)| state.order.deadline.has_expired())
+            .map(|(order_hash, _)| *order_hash)
+            .collect();
+        // removed entries are replaced with the last ones, which
+        // should be checked as well
+        self.limit_orders_prune_cursor =
+            start + Self::LIMIT_ORDERS_PRUNE_BATCH - u32::try_from(expired.len()).unwrap_or(0);
+        for order_hash in expired {
+            self.limit_orders.remove(&order_hash);
+        }
+    }
+
+    #[inline]
+    pub fn htlc(&self, hash_lock: &CryptoHash) -> Option<&Htlc> {
+        self.htlcs.get(hash_lock)
+    }
+
+    /// Returns `false` if HTLC with given hash lock already exists
+    #[inline]
+    pub fn add_htlc(&mut self, hash_lock: CryptoHash, htlc: Htlc) -> bool {
+        if self.htlcs.contains_key(&hash_lock) {
+            return false;
+        }
+        self.htlcs.insert(hash_lock, htlc);
+        true
+    }
+
+    #[inline]
+    pub fn iter_htlc_hash_locks(&self) -> impl Iterator<Item = &CryptoHash> {
+        self.htlcs.keys()
+    }
+
+    #[inline]
+    pub fn remove_htlc(&mut self, hash_lock: &CryptoHash) -> Option<Htlc> {
+        self.htlcs.remove(hash_lock)
+    }
+
+    #[inline]
+    pub fn approval(&self, token_id: &TokenId, account_id: &AccountIdRef) -> Option<&Approval> {
+        self.approvals.get(token_id)?.get(account_id)
+    }
+
+    /// Replaces existing approval, returns new `approval_id`
+    pub fn approve(&mut self, token_id: TokenId, account_id: AccountId, amount: u128) -> u64 {
+        let approval_id = self.next_approval_id;
+        self.next_approval_id += 1;
+        self.approvals.entry(token_id).or_default().insert(
+            account_id,
+            Approval {
+                approval_id,
+                amount: U128(amount),
+            },
+        );
+        approval_id
+    }
+
+    /// Returns `false` if there was no such approval
+    pub fn revoke(&mut self, token_id: &TokenId, account_id: &AccountIdRef) -> bool {
+        let Some(approvals) = self.approvals.get_mut(token_id) else {
+            return false;
+        };
+        let revoked = approvals.remove(account_id).is_some();
+        if approvals.is_empty() {
+            self.approvals.remove(token_id);
+        }
+        revoked
+    }
+
+    /// Returns `false` if there were no approvals for the token
+    #[inline]
+    pub fn revoke_all(&mut self, token_id: &TokenId) -> bool {
+        self.approvals.remove(token_id).is_some()
+    }
+
+    /// Decreases approved amount. Fully spent approvals are kept until
+    /// [`Self::resolve_approval`], so that they can be restored on refunds.
+    pub fn spend_approval(
+        &mut self,
+        token_id: &TokenId,
+        account_id: &AccountIdRef,
+        approval_id: u64,
+        amount: u128,
+    ) -> Result<()> {
+        let approval = self
+            .approvals
+            .get_mut(token_id)
+            .and_then(|approvals| approvals.get_mut(account_id))
+            .filter(|approval| approval.approval_id == approval_id)
+            .ok_or(DefuseError::ApprovalNotFound)?;
+        approval.amount.0 = approval
+            .amount
+            .0
+            .checked_sub(amount)
+            .ok_or(DefuseError::ApprovalExceeded)?;
+        Ok(())
+    }
+
+    /// Restores `refund` of the approval spent by a transfer and removes
+    /// it once fully spent. Approvals replaced or revoked since then are
+    /// left intact.
+    pub fn resolve_approval(
+        &mut self,
+        token_id: &TokenId,
+        account_id: &AccountIdRef,
+        approval_id: u64,
+        refund: u128,
+    ) {
+        let Some(approval) = self
+            .approvals
+            .get_mut(token_id)
+            .and_then(|approvals| approvals.get_mut(account_id))
+            .filter(|approval| approval.approval_id == approval_id)
+        else {
+            return;
+        };
+        approval.amount.0 = approval.amount.0.saturating_add(refund);
+        if approval.amount.0 == 0 {
+            self.revoke(token_id, account_id);
+        }
+    }
+
+    #[inline]
+    pub const fn storage_deposit(&self) -> NearToken {
+        self.storage_deposit
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn deposit_storage(&mut self, amount: NearToken) -> Option<NearToken> {
+        self.storage_deposit = self.storage_deposit.checked_add(amount)?;
+        Some(self.storage_deposit)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn withdraw_storage(&mut self, amount: NearToken) -> Option<NearToken> {
+        if amount > self.storage_available() {
+            return None;
+        }
+        self.storage_deposit = self.storage_deposit.checked_sub(amount)?;
+        Some(self.storage_deposit)
+    }
+
+    /// Total bytes occupied by the account: its entry in
+    /// [`Accounts`](super::Accounts) and all nested collections
+    #[inline]
+    pub fn storage_usage(&self) -> u64 {
+        Self::ENTRY_STORAGE_OVERHEAD
+            .saturating_add(
+                borsh::object_length(self)
+                    .unwrap_or_else(|_| unreachable!())
+                    .try_into()
+                    .unwrap_or_else(|_| unreachable!()),
+            )
+            .saturating_add(self.nested_storage_usage)
+    }
+
+    /// Minimum storage deposit required to register an account
+    #[inline]
+    pub fn min_storage_deposit() -> NearToken {
+        env::storage_byte_cost().saturating_mul(Self::MIN_STORAGE_USAGE.into())
+    }
+
+    /// Cost of [`Self::storage_usage`] not sponsored by the contract
+    /// at current storage price
+    #[inline]
+    pub fn storage_cost(&self) -> NearToken {
+        env::storage_byte_cost().saturating_mul(
+            self.storage_usage()
+                .saturating_sub(self.sponsored_storage_usage)
+                .into(),
+        )
+    }
+
+    #[inline]
+    pub const fn is_storage_sponsored(&self) -> bool {
+        self.storage_sponsored
+    }
+
+    /// Make the contract pay for storage of the account
+    #[inline]
+    pub fn sponsor_storage(&mut self) {
+        self.storage_sponsored = true;
+    }
+
+    /// Returns whether [`Self::storage_cost`] is covered either by
+    /// storage deposit or by the contract
+    #[inline]
+    pub fn is_storage_covered(&self) -> bool {
+        self.storage_sponsored || self.storage_deposit >= self.storage_cost()
+    }
+
+    /// Part of storage deposit not covering [`Self::storage_cost`]
+    #[inline]
+    pub fn storage_available(&self) -> NearToken {
+        self.storage_deposit.saturating_sub(self.storage_cost())
+    }
+
+    /// Writes pending changes of nested collections to storage and
+    /// accounts for bytes they added or freed
+    pub fn flush_storage(&mut self) {
+        let before = env::storage_usage();
+
+        self.nonces.as_inner_mut().flush();
+        self.public_keys.flush();
+        self.public_key_scopes.flush();
+        self.public_keys_added_at.flush();
+        self.state.token_balances.as_inner_mut().flush();
+        self.limit_orders.flush();
+        self.htlcs.flush();
+        self.approvals.flush();
+
+        let after = env::storage_usage();
+        self.nested_storage_usage = if after >= before {
+            self.nested_storage_usage.saturating_add(after - before)
+        } else {
+            self.nested_storage_usage.saturating_sub(before - after)
+        };
+    }
+}
+
+/// Layout of [`Account`] before it was stored in
+/// [`MaybeLegacy`](defuse_near_utils::MaybeLegacy)
+#[derive(Debug)]
+#[near(serializers = [borsh])]
+pub struct AccountV0 {
+    nonces: Nonces<LookupMap<U248, U256>>,
+
+    implicit_public_key_removed: bool,
+    public_keys: IterableSet<PublicKey>,
+
+    state: AccountState,
+
+    prefix: Vec<u8>,
+}
+
+impl From<AccountV0> for Lock<Account> {
+    fn from(
+        AccountV0 {
+            nonces,
+            implicit_public_key_removed,
+            public_keys,
+            state,
+            prefix,
+        }: AccountV0,
+    ) -> Self {
+        let mut account = Account {
+            nonces,
+            implicit_public_key_removed,
+            public_keys,
+            public_key_scopes: LookupMap::new(
+                prefix.as_slice().nest(AccountPrefix::PublicKeyScopes),
+            ),
+            public_keys_added_at: LookupMap::new(
+                prefix.as_slice().nest(AccountPrefix::PublicKeysAddedAt),
+            ),
+            threshold: 1,
+            lockdown: None,
+            guardians: None,
+            recovery: None,
+            state,
+            limit_orders: IterableMap::new(prefix.as_slice().nest(AccountPrefix::LimitOrders)),
+            limit_orders_prune_cursor: 0,
+            htlcs: IterableMap::new(prefix.as_slice().nest(AccountPrefix::Htlcs)),
+            approvals: IterableMap::new(prefix.as_slice().nest(AccountPrefix::Approvals)),
+            next_approval_id: 0,
+            storage_deposit: NearToken::from_yoctonear(0),
+            storage_sponsored: false,
+            sponsored_storage_usage: 0,
+            nested_storage_usage: 0,
+            prefix,
+        };
+        // only storage used so far is paid by the contract
+        account.sponsored_storage_usage = account.storage_usage();
+        Self::unlocked(account)
+    }
 }
 
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -140,4 +719,9 @@ enum AccountPrefix {
     Nonces,
     PublicKeys,
     State,
+    LimitOrders,
+    PublicKeyScopes,
+    Approvals,
+    Htlcs,
+    PublicKeysAddedAt,
 }
@@ -1,21 +1,34 @@
 mod account;
 mod state;
+mod storage_management;
 
 pub use self::{account::*, state::*};
 
-use std::collections::HashSet;
+use core::{
+    mem,
+    ops::{Deref, DerefMut},
+};
+use std::collections::{HashMap, HashSet};
 
-use defuse_core::{crypto::PublicKey, DefuseError, Nonce};
-use defuse_near_utils::{NestPrefix, PREDECESSOR_ACCOUNT_ID};
+use defuse_core::{
+    accounts::{AccountEvent, Guardians, Lockdown, PublicKeyScope, Recovery},
+    crypto::PublicKey,
+    events::{DefuseEvent, DefuseIntentEmit},
+    DefuseError, Nonce, Result,
+};
+use defuse_near_utils::{
+    Lock, MaybeLegacy, NestPrefix, UnwrapOrPanic, CURRENT_ACCOUNT_ID, PREDECESSOR_ACCOUNT_ID,
+};
 use defuse_serde_utils::base64::AsBase64;
+use near_plugins::{access_control_any, AccessControllable};
 use near_sdk::{
     assert_one_yocto, borsh::BorshSerialize, near, store::IterableMap, AccountId, AccountIdRef,
-    BorshStorageKey, FunctionError, IntoStorageKey,
+    BorshStorageKey, FunctionError, IntoStorageKey, NearToken,
 };
 
 use crate::{
-    accounts::AccountManager,
-    contract::{Contract, ContractExt},
+    accounts::{AccountForceLocker, AccountManager},
+    contract::{Contract, ContractExt, Role},
 };
 
 #[near]
@@ -23,7 +36,11 @@ impl AccountManager for Contract {
     fn has_public_key(&self, account_id: &AccountId, public_key: &PublicKey) -> bool {
         self.accounts.get(account_id).map_or_else(
             || account_id == &public_key.to_implicit_account_id(),
-            |account| account.has_public_key(account_id, public_key),
+            |account| {
+                account
+                    .as_inner_unchecked()
+                    .has_public_key(account_id, public_key)
+            },
         )
     }
 
@@ -34,16 +51,35 @@ impl AccountManager for Contract {
                     .into_iter()
                     .collect()
             },
-            |account| account.iter_public_keys(account_id).collect(),
+            |account| {
+                account
+                    .as_inner_unchecked()
+                    .iter_public_keys(account_id)
+                    .collect()
+            },
         )
     }
 
+    fn public_key_scope_of(
+        &self,
+        account_id: &AccountId,
+        public_key: &PublicKey,
+    ) -> Option<PublicKeyScope> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().public_key_scope(public_key))
+            .cloned()
+    }
+
     #[payable]
     fn add_public_key(&mut self, public_key: PublicKey) {
         assert_one_yocto();
         if !self
             .accounts
             .get_or_create(PREDECESSOR_ACCOUNT_ID.clone())
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic()
             .add_public_key(&PREDECESSOR_ACCOUNT_ID, public_key)
         {
             DefuseError::PublicKeyExists.panic()
@@ -57,22 +93,120 @@ impl AccountManager for Contract {
             .accounts
             // create account if doesn't exist, so the user can opt out of implicit public key
             .get_or_create(PREDECESSOR_ACCOUNT_ID.clone())
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic()
             .remove_public_key(&PREDECESSOR_ACCOUNT_ID, public_key)
         {
             DefuseError::PublicKeyNotExist.panic()
         }
     }
 
-    fn is_nonce_used(&self, account_id: &AccountId, nonce: AsBase64<Nonce>) -> bool {
+    fn threshold_of(&self, account_id: &AccountId) -> u16 {
         self.accounts
             .get(account_id)
-            .is_some_and(move |account| account.is_nonce_used(nonce.into_inner()))
+            .map_or(1, |account| account.as_inner_unchecked().threshold())
+    }
+
+    #[payable]
+    fn set_threshold(&mut self, threshold: u16) {
+        assert_one_yocto();
+        let account = self
+            .accounts
+            .get_or_create(PREDECESSOR_ACCOUNT_ID.clone())
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic();
+        if threshold == 0
+            || account.iter_public_keys(&PREDECESSOR_ACCOUNT_ID).count() < usize::from(threshold)
+        {
+            DefuseError::InvalidThreshold.panic()
+        }
+        account.set_threshold(&PREDECESSOR_ACCOUNT_ID, threshold);
+    }
+
+    fn lockdown_of(&self, account_id: &AccountId) -> Option<Lockdown> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().lockdown())
+            .filter(|lockdown| lockdown.is_active())
+            .cloned()
+    }
+
+    fn guardians_of(&self, account_id: &AccountId) -> Option<Guardians> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().guardians())
+            .cloned()
+    }
+
+    #[payable]
+    fn set_guardians(&mut self, guardians: Guardians) {
+        assert_one_yocto();
+        if !guardians.is_valid(&PREDECESSOR_ACCOUNT_ID) {
+            DefuseError::InvalidGuardians.panic()
+        }
+        self.accounts
+            .get_or_create(PREDECESSOR_ACCOUNT_ID.clone())
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic()
+            .set_guardians(&PREDECESSOR_ACCOUNT_ID, guardians);
+    }
+
+    fn recovery_of(&self, account_id: &AccountId) -> Option<Recovery> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().recovery())
+            .cloned()
+    }
+
+    #[payable]
+    fn cancel_recovery(&mut self) {
+        assert_one_yocto();
+        if !self
+            .accounts
+            .get_mut(&PREDECESSOR_ACCOUNT_ID)
+            .ok_or(DefuseError::RecoveryNotFound)
+            .unwrap_or_panic()
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic()
+            .cancel_recovery(&PREDECESSOR_ACCOUNT_ID)
+        {
+            DefuseError::RecoveryNotFound.panic()
+        }
+    }
+
+    fn finalize_recovery(&mut self, account_id: AccountId) -> PublicKey {
+        self.accounts
+            .get_mut(&account_id)
+            .ok_or(DefuseError::RecoveryNotReady)
+            .unwrap_or_panic()
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic()
+            .finalize_recovery(&account_id)
+            .unwrap_or_panic()
+    }
+
+    fn is_nonce_used(&self, account_id: &AccountId, nonce: AsBase64<Nonce>) -> bool {
+        self.accounts.get(account_id).is_some_and(move |account| {
+            account
+                .as_inner_unchecked()
+                .is_nonce_used(nonce.into_inner())
+        })
     }
 
     #[payable]
     fn invalidate_nonces(&mut self, nonces: Vec<AsBase64<Nonce>>) {
         assert_one_yocto();
-        let account = self.accounts.get_or_create(PREDECESSOR_ACCOUNT_ID.clone());
+        let account = self
+            .accounts
+            .get_or_create(PREDECESSOR_ACCOUNT_ID.clone())
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic();
         for n in nonces.into_iter().map(AsBase64::into_inner) {
             if !account.commit_nonce(n) {
                 DefuseError::NonceUsed.panic()
@@ -81,11 +215,45 @@ impl AccountManager for Contract {
     }
 }
 
+#[near]
+impl AccountForceLocker for Contract {
+    fn is_account_locked(&self, account_id: &AccountId) -> bool {
+        self.accounts.get(account_id).is_some_and(Lock::is_locked)
+    }
+
+    #[access_control_any(roles(Role::DAO, Role::UnrestrictedAccountLocker))]
+    #[payable]
+    fn force_lock_account(&mut self, account_id: AccountId) -> bool {
+        assert_one_yocto();
+        self.accounts.lock(account_id)
+    }
+
+    #[access_control_any(roles(Role::DAO, Role::UnrestrictedAccountLocker))]
+    #[payable]
+    fn force_unlock_account(&mut self, account_id: &AccountId) -> bool {
+        assert_one_yocto();
+        let unlocked = self
+            .accounts
+            .get_mut(account_id)
+            .and_then(Lock::unlock)
+            .is_some();
+        if unlocked {
+            DefuseEvent::AccountUnlocked(AccountEvent::new(account_id.as_ref(), ())).emit();
+        }
+        unlocked
+    }
+}
+
 #[derive(Debug)]
 #[near(serializers = [borsh])]
 pub struct Accounts {
-    accounts: IterableMap<AccountId, Account>,
+    accounts: IterableMap<AccountId, MaybeLegacy<Lock<Account>, AccountV0>>,
     prefix: Vec<u8>,
+
+    /// Accounts modified during current call along with their storage
+    /// usage before the first modification, see [`Self::flush`]
+    #[borsh(skip)]
+    touched: HashMap<AccountId, u64>,
 }
 
 impl Accounts {
@@ -98,32 +266,129 @@ impl Accounts {
         Self {
             accounts: IterableMap::new(prefix.as_slice().nest(AccountsPrefix::Accounts)),
             prefix,
+            touched: HashMap::new(),
         }
     }
 
     #[inline]
-    pub fn get(&self, account_id: &AccountIdRef) -> Option<&Account> {
-        self.accounts.get(account_id)
+    pub fn get(&self, account_id: &AccountIdRef) -> Option<&Lock<Account>> {
+        self.accounts.get(account_id).map(Deref::deref)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, account_id: &AccountIdRef) -> Option<&mut Lock<Account>> {
+        let account = self.accounts.get_mut(account_id)?;
+        if !self.touched.contains_key(account_id) {
+            self.touched.insert(
+                account_id.to_owned(),
+                account.as_inner_unchecked().storage_usage(),
+            );
+        }
+        Some(account.deref_mut())
     }
 
+    /// Gets registered account to receive deposits or transfers.
+    /// The contract itself is always treated as registered.
     #[inline]
-    pub fn get_mut(&mut self, account_id: &AccountIdRef) -> Option<&mut Account> {
-        self.accounts.get_mut(account_id)
+    pub fn get_registered_mut(&mut self, account_id: AccountId) -> Result<&mut Lock<Account>> {
+        if account_id == *CURRENT_ACCOUNT_ID {
+            return Ok(self.get_or_create(account_id));
+        }
+        self.get_mut(&account_id)
+            .ok_or(DefuseError::AccountNotRegistered)
+    }
+
+    /// Removes unlocked account, see [`Account::close`].
+    /// Returns reclaimable storage deposit.
+    pub fn close(&mut self, account_id: &AccountIdRef) -> Result<NearToken> {
+        let refund = self
+            .accounts
+            .get_mut(account_id)
+            .ok_or(DefuseError::AccountNotFound)?
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)?
+            .close(account_id)?;
+        self.accounts.remove(account_id);
+        self.touched.remove(account_id);
+        Ok(refund)
+    }
+
+    /// Locks an account, creating it if doesn't exist, so that it can be
+    /// locked in advance. Returns `false` if it was already locked.
+    pub fn lock(&mut self, account_id: AccountId) -> bool {
+        // accounts can be locked in advance at the expense of the contract
+        let locked = self
+            .get_or_create_unchecked(account_id.clone())
+            .lock()
+            .is_some();
+        if locked {
+            DefuseEvent::AccountLocked(AccountEvent::new(account_id, ())).emit();
+        }
+        locked
     }
 
+    /// Gets or creates an account, new accounts are unlocked
     #[inline]
-    pub fn get_or_create(&mut self, account_id: AccountId) -> &mut Account {
+    pub fn get_or_create(&mut self, account_id: AccountId) -> &mut Lock<Account> {
+        if !self.touched.contains_key(&account_id) {
+            // new accounts have to cover all their storage
+            let usage = self
+                .get(&account_id)
+                .map_or(0, |account| account.as_inner_unchecked().storage_usage());
+            self.touched.insert(account_id.clone(), usage);
+        }
         self.accounts
             .entry(account_id)
             .or_insert_with_key(|account_id| {
-                Account::new(
+                Lock::unlocked(Account::new(
                     self.prefix
                         .as_slice()
                         .nest(AccountsPrefix::Account(account_id)),
                     account_id,
-                )
+                ))
+                .into()
             })
     }
+
+    /// Same as [`Self::get_or_create`], but storage usage of the account
+    /// is not checked on [`Self::flush`]. Only for callbacks refunding
+    /// tokens, which must not fail, and for locking accounts in advance.
+    #[inline]
+    pub fn get_or_create_unchecked(&mut self, account_id: AccountId) -> &mut Lock<Account> {
+        // storage usage never exceeds it, so it is not checked
+        self.touched.insert(account_id.clone(), u64::MAX);
+        self.get_or_create(account_id)
+    }
+}
+
+impl Accounts {
+    /// Writes pending changes of touched accounts to storage. Fails if
+    /// storage usage of any of them grew and is not covered by its
+    /// storage deposit. Called on drop at the end of each call, so that
+    /// no entry point can skip it.
+    fn flush(&mut self) -> Result<()> {
+        for (account_id, usage_before) in mem::take(&mut self.touched) {
+            let Some(account) = self.accounts.get_mut(&account_id) else {
+                continue;
+            };
+            let account = account.as_inner_unchecked_mut();
+            account.flush_storage();
+            // storage of the contract itself is paid by the contract
+            if account_id != *CURRENT_ACCOUNT_ID
+                && account.storage_usage() > usage_before
+                && !account.is_storage_covered()
+            {
+                return Err(DefuseError::InsufficientStorageDeposit);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Accounts {
+    fn drop(&mut self) {
+        self.flush().unwrap_or_panic();
+    }
 }
 
 #[derive(BorshSerialize, BorshStorageKey)]
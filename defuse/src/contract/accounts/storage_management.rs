@@ -0,0 +1,151 @@
+use defuse_core::{DefuseError, Result};
+use defuse_near_utils::{UnwrapOrPanic, PREDECESSOR_ACCOUNT_ID};
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_plugins::{pause, Pausable};
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, AccountIdRef, NearToken, Promise};
+
+use crate::contract::{Contract, ContractExt};
+
+use super::Account;
+
+#[near]
+impl StorageManagement for Contract {
+    #[pause]
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(|| PREDECESSOR_ACCOUNT_ID.clone());
+
+        let refund = if registration_only.unwrap_or_default() {
+            if self.accounts.get(&account_id).is_some() {
+                amount
+            } else {
+                amount.saturating_sub(Account::min_storage_deposit())
+            }
+        } else {
+            NearToken::from_yoctonear(0)
+        };
+
+        let deposit = amount.saturating_sub(refund);
+        if !deposit.is_zero() || self.accounts.get(&account_id).is_none() {
+            self.internal_storage_deposit(account_id.clone(), deposit)
+                .unwrap_or_panic();
+        }
+
+        if !refund.is_zero() {
+            // detach promise
+            let _ = Promise::new(PREDECESSOR_ACCOUNT_ID.clone()).transfer(refund);
+        }
+
+        self.internal_storage_balance_of(&account_id)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    #[pause]
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        assert_one_yocto();
+
+        let account = self
+            .accounts
+            .get_mut(&PREDECESSOR_ACCOUNT_ID)
+            .ok_or(DefuseError::AccountNotRegistered)
+            .unwrap_or_panic()
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic();
+
+        let amount = amount.unwrap_or_else(|| account.storage_available());
+        require!(
+            account.withdraw_storage(amount).is_some(),
+            "amount exceeds available storage balance"
+        );
+
+        if !amount.is_zero() {
+            // detach promise
+            let _ = Promise::new(PREDECESSOR_ACCOUNT_ID.clone()).transfer(amount);
+        }
+
+        self.internal_storage_balance_of(&PREDECESSOR_ACCOUNT_ID)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    #[pause]
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        require!(
+            !force.unwrap_or_default(),
+            "force unregister is not supported"
+        );
+        if self.accounts.get(&PREDECESSOR_ACCOUNT_ID).is_none() {
+            return false;
+        }
+        self.internal_close_account(&PREDECESSOR_ACCOUNT_ID, PREDECESSOR_ACCOUNT_ID.clone())
+            .unwrap_or_panic();
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: Account::min_storage_deposit(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(&account_id)
+    }
+}
+
+impl Contract {
+    /// Credits storage deposit to `account_id`, registering it if needed.
+    /// Registration requires at least [`Account::min_storage_deposit`].
+    pub(crate) fn internal_storage_deposit(
+        &mut self,
+        account_id: AccountId,
+        amount: NearToken,
+    ) -> Result<()> {
+        if self.accounts.get(&account_id).is_none() && amount < Account::min_storage_deposit() {
+            return Err(DefuseError::InsufficientStorageDeposit);
+        }
+        // locked accounts can still receive storage deposits
+        self.accounts
+            .get_or_create(account_id)
+            .as_inner_unchecked_mut()
+            .deposit_storage(amount)
+            .ok_or(DefuseError::BalanceOverflow)?;
+        Ok(())
+    }
+
+    /// Closes the account and refunds reclaimable storage deposit
+    /// to `receiver_id`
+    pub(crate) fn internal_close_account(
+        &mut self,
+        account_id: &AccountIdRef,
+        receiver_id: AccountId,
+    ) -> Result<()> {
+        let refund = self.accounts.close(account_id)?;
+        if !refund.is_zero() {
+            // detach promise
+            let _ = Promise::new(receiver_id).transfer(refund);
+        }
+        Ok(())
+    }
+
+    fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.accounts.get(account_id).map(|account| {
+            let account = account.as_inner_unchecked();
+            StorageBalance {
+                total: account.storage_deposit(),
+                available: account.storage_available(),
+            }
+        })
+    }
+}
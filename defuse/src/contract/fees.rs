@@ -1,12 +1,17 @@
-use core::mem;
-use std::borrow::Cow;
+use core::{iter, mem};
+use std::{borrow::Cow, collections::BTreeMap};
 
 use defuse_core::{
-    events::DefuseIntentEmit,
-    fees::{FeeChangedEvent, FeeCollectorChangedEvent, Pips},
+    accounts::AccountEvent,
+    events::{DefuseEvent, DefuseIntentEmit},
+    fees::{
+        FeeChangedEvent, FeeCollectorChangedEvent, FeeDiscountChangedEvent, FeeMode,
+        FeeModeChangedEvent, PairFeeChangedEvent, Pips, TokenFeeChangedEvent, TokenFees, TokenPair,
+    },
+    tokens::TokenId,
 };
 use near_plugins::{access_control_any, pause, AccessControllable, Pausable};
-use near_sdk::{assert_one_yocto, near, require, AccountId};
+use near_sdk::{assert_one_yocto, env, json_types::U128, near, require, AccountId};
 
 use crate::fees::FeesManager;
 
@@ -32,6 +37,125 @@ impl FeesManager for Contract {
         self.fees.fee
     }
 
+    #[pause(name = "intents")]
+    #[access_control_any(roles(Role::DAO, Role::FeesManager))]
+    #[payable]
+    fn set_token_fee(&mut self, token_id: TokenId, fee: Option<Pips>) {
+        assert_one_yocto();
+        let old_fee = if let Some(fee) = fee {
+            self.fee_overrides.token_fees.insert(token_id.clone(), fee)
+        } else {
+            self.fee_overrides.token_fees.remove(&token_id)
+        };
+        require!(old_fee != fee, "same");
+        TokenFeeChangedEvent {
+            token_id,
+            old_fee,
+            new_fee: fee,
+        }
+        .emit();
+    }
+
+    fn token_fee(&self, token_id: TokenId) -> Option<Pips> {
+        self.fee_overrides.token_fees.get(&token_id).copied()
+    }
+
+    #[pause(name = "intents")]
+    #[access_control_any(roles(Role::DAO, Role::FeesManager))]
+    #[payable]
+    fn set_pair_fee(&mut self, token_a: TokenId, token_b: TokenId, fee: Option<Pips>) {
+        assert_one_yocto();
+        let pair = TokenPair::new(token_a, token_b)
+            .unwrap_or_else(|| env::panic_str("pair of the same token"));
+        let old_fee = if let Some(fee) = fee {
+            self.fee_overrides.pair_fees.insert(pair.clone(), fee)
+        } else {
+            self.fee_overrides.pair_fees.remove(&pair)
+        };
+        require!(old_fee != fee, "same");
+        PairFeeChangedEvent {
+            pair,
+            old_fee,
+            new_fee: fee,
+        }
+        .emit();
+    }
+
+    fn pair_fee(&self, token_a: TokenId, token_b: TokenId) -> Option<Pips> {
+        TokenPair::new(token_a, token_b)
+            .and_then(|pair| self.fee_overrides.pair_fees.get(&pair).copied())
+    }
+
+    fn fee_for(&self, token_in: TokenId, token_out: Option<TokenId>) -> Pips {
+        self.fees
+            .with_overrides(&self.fee_overrides)
+            .token_fee(&token_in, token_out.as_ref())
+    }
+
+    #[pause(name = "intents")]
+    #[access_control_any(roles(Role::DAO, Role::FeesManager, Role::FeeDiscountsManager))]
+    #[payable]
+    fn set_fee_discount(&mut self, account_id: AccountId, discount: Option<Pips>) {
+        assert_one_yocto();
+        let old_discount = if let Some(discount) = discount {
+            self.fee_discounts.insert(account_id.clone(), discount)
+        } else {
+            self.fee_discounts.remove(&account_id)
+        };
+        require!(old_discount != discount, "same");
+        DefuseEvent::FeeDiscountChanged(AccountEvent::new(
+            account_id,
+            FeeDiscountChangedEvent {
+                old_discount,
+                new_discount: discount,
+            },
+        ))
+        .emit();
+    }
+
+    fn fee_discount(&self, account_id: AccountId) -> Pips {
+        self.fee_discounts
+            .get(&account_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[pause(name = "intents")]
+    #[access_control_any(roles(Role::DAO, Role::FeesManager))]
+    #[payable]
+    fn set_referral_fee(&mut self, #[allow(unused_mut)] mut referral_fee: Pips) {
+        assert_one_yocto();
+        require!(self.fees.referral_fee != referral_fee, "same");
+        mem::swap(&mut self.fees.referral_fee, &mut referral_fee);
+        DefuseEvent::ReferralFeeChanged(FeeChangedEvent {
+            old_fee: referral_fee,
+            new_fee: self.fees.referral_fee,
+        })
+        .emit();
+    }
+
+    fn referral_fee(&self) -> Pips {
+        self.fees.referral_fee
+    }
+
+    #[pause(name = "intents")]
+    #[access_control_any(roles(Role::DAO, Role::FeesManager))]
+    #[payable]
+    fn set_fee_mode(&mut self, #[allow(unused_mut)] mut fee_mode: FeeMode) {
+        assert_one_yocto();
+        require!(self.fees.fee_mode != fee_mode, "same");
+        mem::swap(&mut self.fees.fee_mode, &mut fee_mode);
+        FeeModeChangedEvent {
+            old_mode: fee_mode,
+            new_mode: self.fees.fee_mode,
+        }
+        .emit();
+    }
+
+    fn fee_mode(&self) -> FeeMode {
+        self.fees.fee_mode
+    }
+
     #[pause(name = "intents")]
     #[access_control_any(roles(Role::DAO, Role::FeesManager))]
     #[payable]
@@ -39,9 +163,16 @@ impl FeesManager for Contract {
         assert_one_yocto();
         require!(self.fees.fee_collector != fee_collector, "same");
         mem::swap(&mut self.fees.fee_collector, &mut fee_collector);
+        require!(
+            self.fees.are_fee_collector_shares_valid(),
+            "invalid fee collector shares"
+        );
+        self.register_fee_collectors();
         FeeCollectorChangedEvent {
             old_fee_collector: fee_collector.into(),
             new_fee_collector: Cow::Borrowed(self.fees.fee_collector.as_ref()),
+            old_shares: None,
+            new_shares: None,
         }
         .emit();
     }
@@ -49,4 +180,90 @@ impl FeesManager for Contract {
     fn fee_collector(&self) -> &AccountId {
         &self.fees.fee_collector
     }
+
+    #[pause(name = "intents")]
+    #[access_control_any(roles(Role::DAO, Role::FeesManager))]
+    #[payable]
+    fn set_fee_collector_shares(
+        &mut self,
+        #[allow(unused_mut)] mut shares: BTreeMap<AccountId, Pips>,
+    ) {
+        assert_one_yocto();
+        require!(self.fees.fee_collector_shares != shares, "same");
+        mem::swap(&mut self.fees.fee_collector_shares, &mut shares);
+        require!(
+            self.fees.are_fee_collector_shares_valid(),
+            "invalid fee collector shares"
+        );
+        self.register_fee_collectors();
+        FeeCollectorChangedEvent {
+            old_fee_collector: Cow::Borrowed(self.fees.fee_collector.as_ref()),
+            new_fee_collector: Cow::Borrowed(self.fees.fee_collector.as_ref()),
+            old_shares: Some(shares),
+            new_shares: Some(self.fees.fee_collector_shares.clone()),
+        }
+        .emit();
+    }
+
+    fn fee_collector_shares(&self) -> &BTreeMap<AccountId, Pips> {
+        &self.fees.fee_collector_shares
+    }
+
+    fn fees_collected(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<(TokenId, U128)> {
+        paginate(self.fees_collected.iter(), from_index, limit)
+    }
+
+    fn token_fees_collected(&self, token_id: TokenId) -> U128 {
+        U128(self.fees_collected.balance_of(&token_id))
+    }
+
+    fn referral_fees_collected(
+        &self,
+        referral: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<(TokenId, U128)> {
+        self.referral_fees_collected
+            .get(&referral)
+            .map(|collected| paginate(collected.iter(), from_index, limit))
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    /// Fee collectors receive fees as regular deposits, so they need to
+    /// be registered. Their storage is paid by the contract.
+    pub(crate) fn register_fee_collectors(&mut self) {
+        for collector in iter::once(&self.state.fees.fee_collector)
+            .chain(self.state.fees.fee_collector_shares.keys())
+        {
+            self.accounts
+                .get_or_create(collector.clone())
+                .as_inner_unchecked_mut()
+                .sponsor_storage();
+        }
+    }
+}
+
+/// Number of tokens returned when `limit` is not specified
+const DEFAULT_LIMIT: u32 = 100;
+/// Maximum number of tokens returned at once
+const MAX_LIMIT: u32 = 500;
+
+fn paginate<'a>(
+    amounts: impl Iterator<Item = (&'a TokenId, &'a u128)>,
+    from_index: Option<U128>,
+    limit: Option<u32>,
+) -> Vec<(TokenId, U128)> {
+    amounts
+        .skip(from_index.map_or(0, |U128(i)| i.try_into().unwrap_or(usize::MAX)))
+        .take(
+            limit
+                .unwrap_or(DEFAULT_LIMIT)
+                .min(MAX_LIMIT)
+                .try_into()
+                .unwrap_or(usize::MAX),
+        )
+        .map(|(token_id, amount)| (token_id.clone(), U128(*amount)))
+        .collect()
 }
@@ -5,8 +5,10 @@ use defuse_core::{
     engine::Inspector,
     events::DefuseEvent,
     intents::{
-        token_diff::{TokenDiff, TokenDiffEvent},
-        tokens::Transfer,
+        htlc::{HtlcClaimedEvent, HtlcLock, HtlcRefund},
+        limit_order::LimitOrder,
+        token_diff::{ReferralNotRegisteredEvent, TokenDiff, TokenDiffEvent},
+        tokens::{BatchTransfer, Transfer, Withdrawal},
         IntentEvent,
     },
     tokens::TokenAmounts,
@@ -41,12 +43,31 @@ impl Inspector for ExecuteInspector {
         .emit();
     }
 
+    #[inline]
+    fn on_batch_transfer(
+        &mut self,
+        sender_id: &AccountIdRef,
+        batch: &BatchTransfer,
+        intent_hash: CryptoHash,
+    ) {
+        DefuseEvent::BatchTransfer(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(batch)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
     #[inline]
     fn on_token_diff(
         &mut self,
         owner_id: &AccountIdRef,
         token_diff: &TokenDiff,
         fees_collected: &TokenAmounts,
+        referral_fees: &TokenAmounts,
         intent_hash: CryptoHash,
     ) {
         DefuseEvent::TokenDiff(
@@ -56,6 +77,116 @@ impl Inspector for ExecuteInspector {
                     TokenDiffEvent {
                         diff: Cow::Borrowed(token_diff),
                         fees_collected: fees_collected.clone(),
+                        referral_fees: referral_fees.clone(),
+                    },
+                ),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
+    #[inline]
+    fn on_referral_not_registered(
+        &mut self,
+        owner_id: &AccountIdRef,
+        referral: &AccountIdRef,
+        intent_hash: CryptoHash,
+    ) {
+        DefuseEvent::ReferralNotRegistered(
+            [IntentEvent::new(
+                AccountEvent::new(
+                    owner_id,
+                    ReferralNotRegisteredEvent {
+                        referral: referral.to_owned(),
+                    },
+                ),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
+    #[inline]
+    fn on_withdraw(
+        &mut self,
+        _owner_id: &AccountIdRef,
+        _withdrawal: &Withdrawal<'_>,
+        _intent_hash: CryptoHash,
+    ) {
+        // withdrawals are logged as `mt_burn` events on execution
+    }
+
+    #[inline]
+    fn on_limit_order_placed(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order: &LimitOrder,
+        intent_hash: CryptoHash,
+    ) {
+        DefuseEvent::LimitOrderPlaced(
+            [IntentEvent::new(
+                AccountEvent::new(maker_id, Cow::Borrowed(order)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
+    #[inline]
+    fn on_limit_order_cancelled(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash) {
+        DefuseEvent::LimitOrderCancelled(
+            [IntentEvent::new(
+                AccountEvent::new(maker_id, ()),
+                order_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
+    #[inline]
+    fn on_htlc_locked(
+        &mut self,
+        sender_id: &AccountIdRef,
+        lock: &HtlcLock,
+        intent_hash: CryptoHash,
+    ) {
+        DefuseEvent::HtlcLocked(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(lock)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
+    #[inline]
+    fn on_htlc_claimed(
+        &mut self,
+        receiver_id: &AccountIdRef,
+        sender_id: &AccountIdRef,
+        hash_lock: CryptoHash,
+        preimage: &[u8],
+        intent_hash: CryptoHash,
+    ) {
+        DefuseEvent::HtlcClaimed(
+            [IntentEvent::new(
+                AccountEvent::new(
+                    receiver_id,
+                    HtlcClaimedEvent {
+                        sender_id: sender_id.to_owned(),
+                        hash_lock,
+                        preimage: preimage.to_vec(),
                     },
                 ),
                 intent_hash,
@@ -66,6 +197,24 @@ impl Inspector for ExecuteInspector {
         .emit();
     }
 
+    #[inline]
+    fn on_htlc_refunded(
+        &mut self,
+        sender_id: &AccountIdRef,
+        refund: &HtlcRefund,
+        intent_hash: CryptoHash,
+    ) {
+        DefuseEvent::HtlcRefunded(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(refund)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        )
+        .emit();
+    }
+
     #[inline]
     fn on_intent_executed(&mut self, signer_id: &AccountIdRef, intent_hash: CryptoHash) {
         self.intents_executed.push(IntentEvent::new(
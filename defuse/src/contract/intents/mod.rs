@@ -4,18 +4,22 @@ mod simulate;
 mod state;
 
 use defuse_core::{
-    engine::{Engine, StateView},
-    payload::multi::MultiPayload,
+    engine::{cached::CachedState, Engine, StateView},
+    intents::{htlc::Htlc, limit_order::LimitOrderState},
+    payload::{multisig::MultiSigPayload, PayloadVersion},
     DefuseError,
 };
 use defuse_near_utils::UnwrapOrPanic;
 use defuse_nep245::MtEvent;
 use execute::ExecuteInspector;
 use near_plugins::{pause, Pausable};
-use near_sdk::{near, FunctionError};
+use near_sdk::{json_types::Base58CryptoHash, near, AccountId, FunctionError};
 use simulate::SimulateInspector;
 
-use crate::intents::{Intents, SimulationOutput, StateOutput};
+use crate::intents::{
+    Intents, PayloadError, SimulationOutput, StateOutput, StateOverrides, TokenFeeOutput,
+    UnsignedPayload,
+};
 
 use super::{Contract, ContractExt};
 
@@ -23,7 +27,7 @@ use super::{Contract, ContractExt};
 impl Intents for Contract {
     #[pause(name = "intents")]
     #[inline]
-    fn execute_intents(&mut self, signed: Vec<MultiPayload>) {
+    fn execute_intents(&mut self, signed: Vec<MultiSigPayload>) {
         Engine::new(self, ExecuteInspector::default())
             .execute_signed_intents(signed)
             .unwrap_or_panic()
@@ -34,11 +38,111 @@ impl Intents for Contract {
 
     #[pause(name = "intents")]
     #[inline]
-    fn simulate_intents(&self, signed: Vec<MultiPayload>) -> SimulationOutput {
+    fn simulate_intents(&self, signed: Vec<MultiSigPayload>) -> SimulationOutput {
+        self.internal_simulate_intents(
+            signed,
+            StateOverrides::default(),
+            false,
+            |engine, signed| engine.execute_signed_intent(signed),
+        )
+    }
+
+    #[pause(name = "intents")]
+    #[inline]
+    fn simulate_intents_with_overrides(
+        &self,
+        signed: Vec<MultiSigPayload>,
+        overrides: StateOverrides,
+        collect_errors: Option<bool>,
+    ) -> SimulationOutput {
+        self.internal_simulate_intents(
+            signed,
+            overrides,
+            collect_errors.unwrap_or_default(),
+            |engine, signed| engine.execute_signed_intent(signed),
+        )
+    }
+
+    #[pause(name = "intents")]
+    #[inline]
+    fn simulate_unsigned_intents(
+        &self,
+        payloads: Vec<UnsignedPayload>,
+        collect_errors: Option<bool>,
+    ) -> SimulationOutput {
+        self.internal_simulate_intents(
+            payloads,
+            StateOverrides::default(),
+            collect_errors.unwrap_or_default(),
+            |engine, unsigned| {
+                engine.simulate_unsigned_intent(unsigned.payload, &unsigned.public_keys)
+            },
+        )
+    }
+
+    #[inline]
+    fn limit_order(
+        &self,
+        maker_id: AccountId,
+        order_hash: Base58CryptoHash,
+    ) -> Option<LimitOrderState> {
+        StateView::limit_order(self, &maker_id, order_hash.into())
+    }
+
+    #[inline]
+    fn htlc(&self, sender_id: AccountId, hash_lock: Base58CryptoHash) -> Option<Htlc> {
+        StateView::htlc(self, &sender_id, &hash_lock.into())
+    }
+
+    #[inline]
+    fn supported_payload_versions(&self) -> Vec<PayloadVersion> {
+        PayloadVersion::SUPPORTED.to_vec()
+    }
+}
+
+impl Contract {
+    fn internal_simulate_intents<'a, T>(
+        &'a self,
+        payloads: Vec<T>,
+        overrides: StateOverrides,
+        collect_errors: bool,
+        mut execute: impl FnMut(
+            &mut Engine<CachedState<&'a Self>, &mut SimulateInspector>,
+            T,
+        ) -> defuse_core::Result<()>,
+    ) -> SimulationOutput {
+        let fee_override = overrides.fee;
+        let mut state = self.cached();
+        simulate::apply_overrides(&mut state, overrides);
+
         let mut inspector = SimulateInspector::default();
-        let engine = Engine::new(self.cached(), &mut inspector);
+        let mut engine = Engine::new(state, &mut inspector);
+
+        let mut errors = Vec::new();
+        for (index, payload) in (0..).zip(payloads) {
+            // only changes of the current payload are recorded
+            engine.state.checkpoint();
+            let checkpoint = engine.inspector.checkpoint();
+            if let Err(err) = execute(&mut engine, payload) {
+                if !collect_errors {
+                    err.panic();
+                }
+                // discard partial changes of the failed payload
+                engine.state.rollback();
+                engine.inspector.rollback(checkpoint);
+                errors.push(PayloadError {
+                    index,
+                    error: err.to_string(),
+                });
+                continue;
+            }
+
+            if let Some(outcome) = engine.inspector.outcomes.last_mut() {
+                outcome.event.event.balance_deltas = engine.state.as_inner().balance_deltas();
+            }
+        }
 
-        let invariant_violated = match engine.execute_signed_intents(signed) {
+        let invariant_violated = match engine.finalize() {
             // do not log transfers
             Ok(_) => None,
             Err(DefuseError::InvariantViolated(v)) => Some(v),
@@ -49,7 +153,33 @@ impl Intents for Contract {
             intents_executed: inspector.intents_executed,
             min_deadline: inspector.min_deadline,
             invariant_violated,
-            state: StateOutput { fee: self.fee() },
+            outcomes: inspector.outcomes,
+            errors,
+            state: StateOutput {
+                fee: fee_override.unwrap_or_else(|| self.fee()),
+                fee_mode: self.fees.fee_mode,
+                token_fees: inspector
+                    .token_diffs
+                    .iter()
+                    .flat_map(|(signer_id, diff)| {
+                        let fee_discount = StateView::fee_discount(self, signer_id);
+                        diff.iter()
+                            .filter(|(_, delta)| self.fees.fee_mode.is_charged_on(**delta))
+                            .map(move |(token_id, _)| {
+                                let counterpart = diff.counterpart(token_id);
+                                TokenFeeOutput {
+                                    token_id: token_id.clone(),
+                                    counterpart: counterpart.cloned(),
+                                    fee: fee_override
+                                        .unwrap_or_else(|| {
+                                            StateView::fee_for(self, token_id, counterpart)
+                                        })
+                                        .discounted(fee_discount),
+                                }
+                            })
+                    })
+                    .collect(),
+            },
         }
     }
 }
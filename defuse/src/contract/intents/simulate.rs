@@ -1,15 +1,87 @@
+use std::{borrow::Cow, mem};
+
 use defuse_core::{
     accounts::AccountEvent,
-    engine::Inspector,
-    intents::{token_diff::TokenDiff, tokens::Transfer, IntentEvent},
+    engine::{cached::CachedState, Inspector, State, StateView},
+    events::DefuseEvent,
+    intents::{
+        htlc::{HtlcClaimedEvent, HtlcLock, HtlcRefund},
+        limit_order::LimitOrder,
+        token_diff::{ReferralNotRegisteredEvent, TokenDiff, TokenDiffEvent},
+        tokens::{BatchTransfer, Transfer, Withdrawal},
+        IntentEvent,
+    },
     tokens::TokenAmounts,
     Deadline,
 };
-use near_sdk::{AccountIdRef, CryptoHash};
+use near_sdk::{AccountId, AccountIdRef, CryptoHash};
+
+use crate::intents::{IntentOutcome, StateOverrides};
+
+/// Applies `overrides` on top of the cached state before simulation
+pub fn apply_overrides<W: StateView>(state: &mut CachedState<W>, overrides: StateOverrides) {
+    for (account_id, balances) in overrides.balances {
+        for (token_id, amount) in balances {
+            state.override_balance(account_id.clone(), token_id, amount);
+        }
+    }
+    for (account_id, public_keys) in overrides.public_keys {
+        for public_key in public_keys {
+            // public key might be already added
+            let _ = state.add_public_key(account_id.clone(), public_key);
+        }
+    }
+    if let Some(fee) = overrides.fee {
+        state.override_fee(fee);
+    }
+}
 
 pub struct SimulateInspector {
     pub intents_executed: Vec<IntentEvent<AccountEvent<'static, ()>>>,
     pub min_deadline: Deadline,
+    /// Executed [`TokenDiff`]s along with their signers
+    pub token_diffs: Vec<(AccountId, TokenDiff)>,
+    /// Outcomes of executed signed intents, balance deltas are
+    /// filled in by the caller
+    pub outcomes: Vec<IntentEvent<AccountEvent<'static, IntentOutcome>>>,
+    /// Outcome of the signed intent being executed
+    current: IntentOutcome,
+}
+
+/// Position of [`SimulateInspector`] to roll back to
+#[derive(Debug, Clone, Copy)]
+pub struct InspectorCheckpoint {
+    intents_executed: usize,
+    min_deadline: Deadline,
+    token_diffs: usize,
+    outcomes: usize,
+}
+
+impl SimulateInspector {
+    #[inline]
+    pub fn checkpoint(&self) -> InspectorCheckpoint {
+        InspectorCheckpoint {
+            intents_executed: self.intents_executed.len(),
+            min_deadline: self.min_deadline,
+            token_diffs: self.token_diffs.len(),
+            outcomes: self.outcomes.len(),
+        }
+    }
+
+    /// Discards everything recorded since `checkpoint`, e.g. for
+    /// payloads which failed to execute
+    pub fn rollback(&mut self, checkpoint: InspectorCheckpoint) {
+        self.intents_executed.truncate(checkpoint.intents_executed);
+        self.min_deadline = checkpoint.min_deadline;
+        self.token_diffs.truncate(checkpoint.token_diffs);
+        self.outcomes.truncate(checkpoint.outcomes);
+        self.current = IntentOutcome::default();
+    }
+
+    #[inline]
+    fn record_event(&mut self, event: DefuseEvent<'_>) {
+        self.current.events.push(event.to_json());
+    }
 }
 
 impl Default for SimulateInspector {
@@ -17,6 +89,9 @@ impl Default for SimulateInspector {
         Self {
             intents_executed: Vec::new(),
             min_deadline: Deadline::MAX,
+            token_diffs: Vec::new(),
+            outcomes: Vec::new(),
+            current: IntentOutcome::default(),
         }
     }
 }
@@ -30,20 +105,201 @@ impl Inspector for SimulateInspector {
     #[inline]
     fn on_transfer(
         &mut self,
-        _sender_id: &AccountIdRef,
-        _transfer: &Transfer,
-        _intent_hash: CryptoHash,
+        sender_id: &AccountIdRef,
+        transfer: &Transfer,
+        intent_hash: CryptoHash,
+    ) {
+        self.record_event(DefuseEvent::Transfer(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(transfer)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_batch_transfer(
+        &mut self,
+        sender_id: &AccountIdRef,
+        batch: &BatchTransfer,
+        intent_hash: CryptoHash,
     ) {
+        self.record_event(DefuseEvent::BatchTransfer(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(batch)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
     }
 
     #[inline]
     fn on_token_diff(
+        &mut self,
+        owner_id: &AccountIdRef,
+        token_diff: &TokenDiff,
+        fees_collected: &TokenAmounts,
+        referral_fees: &TokenAmounts,
+        intent_hash: CryptoHash,
+    ) {
+        self.token_diffs
+            .push((owner_id.to_owned(), token_diff.clone()));
+
+        for (token_id, amount) in fees_collected {
+            // can't overflow, since these fees were already collected
+            let _ = self
+                .current
+                .fees_collected
+                .deposit(token_id.clone(), *amount);
+        }
+        for (token_id, amount) in referral_fees {
+            let _ = self
+                .current
+                .referral_fees
+                .deposit(token_id.clone(), *amount);
+        }
+
+        self.record_event(DefuseEvent::TokenDiff(
+            [IntentEvent::new(
+                AccountEvent::new(
+                    owner_id,
+                    TokenDiffEvent {
+                        diff: Cow::Borrowed(token_diff),
+                        fees_collected: fees_collected.clone(),
+                        referral_fees: referral_fees.clone(),
+                    },
+                ),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_referral_not_registered(
+        &mut self,
+        owner_id: &AccountIdRef,
+        referral: &AccountIdRef,
+        intent_hash: CryptoHash,
+    ) {
+        self.record_event(DefuseEvent::ReferralNotRegistered(
+            [IntentEvent::new(
+                AccountEvent::new(
+                    owner_id,
+                    ReferralNotRegisteredEvent {
+                        referral: referral.to_owned(),
+                    },
+                ),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_withdraw(
         &mut self,
         _owner_id: &AccountIdRef,
-        _token_diff: &TokenDiff,
-        _fees_collected: &TokenAmounts,
+        withdrawal: &Withdrawal<'_>,
         _intent_hash: CryptoHash,
     ) {
+        self.current
+            .withdrawals
+            .push(withdrawal.clone().into_owned());
+    }
+
+    #[inline]
+    fn on_limit_order_placed(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order: &LimitOrder,
+        intent_hash: CryptoHash,
+    ) {
+        self.record_event(DefuseEvent::LimitOrderPlaced(
+            [IntentEvent::new(
+                AccountEvent::new(maker_id, Cow::Borrowed(order)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_limit_order_cancelled(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash) {
+        self.record_event(DefuseEvent::LimitOrderCancelled(
+            [IntentEvent::new(
+                AccountEvent::new(maker_id, ()),
+                order_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_htlc_locked(
+        &mut self,
+        sender_id: &AccountIdRef,
+        lock: &HtlcLock,
+        intent_hash: CryptoHash,
+    ) {
+        self.record_event(DefuseEvent::HtlcLocked(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(lock)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_htlc_claimed(
+        &mut self,
+        receiver_id: &AccountIdRef,
+        sender_id: &AccountIdRef,
+        hash_lock: CryptoHash,
+        preimage: &[u8],
+        intent_hash: CryptoHash,
+    ) {
+        self.record_event(DefuseEvent::HtlcClaimed(
+            [IntentEvent::new(
+                AccountEvent::new(
+                    receiver_id,
+                    HtlcClaimedEvent {
+                        sender_id: sender_id.to_owned(),
+                        hash_lock,
+                        preimage: preimage.to_vec(),
+                    },
+                ),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
+    }
+
+    #[inline]
+    fn on_htlc_refunded(
+        &mut self,
+        sender_id: &AccountIdRef,
+        refund: &HtlcRefund,
+        intent_hash: CryptoHash,
+    ) {
+        self.record_event(DefuseEvent::HtlcRefunded(
+            [IntentEvent::new(
+                AccountEvent::new(sender_id, Cow::Borrowed(refund)),
+                intent_hash,
+            )]
+            .as_slice()
+            .into(),
+        ));
     }
 
     #[inline]
@@ -52,5 +308,9 @@ impl Inspector for SimulateInspector {
             AccountEvent::new(signer_id.to_owned(), ()),
             intent_hash,
         ));
+        self.outcomes.push(IntentEvent::new(
+            AccountEvent::new(signer_id.to_owned(), mem::take(&mut self.current)),
+            intent_hash,
+        ));
     }
 }
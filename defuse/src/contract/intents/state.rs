@@ -1,16 +1,24 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 use defuse_core::{
+    accounts::{Guardians, Lockdown, PublicKeyScope, Recovery},
     crypto::PublicKey,
     engine::{State, StateView},
-    fees::Pips,
-    intents::tokens::{FtWithdraw, MtWithdraw, NativeWithdraw, NftWithdraw},
-    tokens::TokenId,
-    DefuseError, Nonce, Result,
+    fees::{FeeMode, Pips, TokenFees},
+    intents::{
+        htlc::Htlc,
+        limit_order::LimitOrderState,
+        tokens::{Call, FtWithdraw, MtWithdraw, NativeWithdraw, NftWithdraw, StorageDeposit},
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline, DefuseError, Nonce, Result,
 };
-use defuse_near_utils::CURRENT_ACCOUNT_ID;
+use defuse_near_utils::{Lock, CURRENT_ACCOUNT_ID};
 use defuse_wnear::{ext_wnear, NEAR_WITHDRAW_GAS};
-use near_sdk::{json_types::U128, AccountId, AccountIdRef, NearToken};
+use near_sdk::{env, json_types::U128, AccountId, AccountIdRef, CryptoHash, NearToken};
 
 use crate::contract::Contract;
 
@@ -30,21 +38,57 @@ impl StateView for Contract {
         self.state.fees.fee
     }
 
+    #[inline]
+    fn fee_for(&self, token_in: &TokenId, token_out: Option<&TokenId>) -> Pips {
+        self.state
+            .fees
+            .with_overrides(&self.state.fee_overrides)
+            .token_fee(token_in, token_out)
+    }
+
+    #[inline]
+    fn referral_fee(&self) -> Pips {
+        self.state.fees.referral_fee
+    }
+
+    #[inline]
+    fn fee_mode(&self) -> FeeMode {
+        self.state.fees.fee_mode
+    }
+
+    #[inline]
+    fn fee_discount(&self, account_id: &AccountIdRef) -> Pips {
+        self.state
+            .fee_discounts
+            .get(account_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
     #[inline]
     fn fee_collector(&self) -> Cow<'_, AccountIdRef> {
         Cow::Borrowed(self.state.fees.fee_collector.as_ref())
     }
 
+    #[inline]
+    fn fee_collector_shares(&self) -> Cow<'_, BTreeMap<AccountId, Pips>> {
+        Cow::Borrowed(&self.state.fees.fee_collector_shares)
+    }
+
     #[inline]
     fn has_public_key(&self, account_id: &AccountIdRef, public_key: &PublicKey) -> bool {
         self.accounts.get(account_id).map_or_else(
             || account_id == public_key.to_implicit_account_id(),
-            |account| account.has_public_key(account_id, public_key),
+            |account| {
+                account
+                    .as_inner_unchecked()
+                    .has_public_key(account_id, public_key)
+            },
         )
     }
 
     fn iter_public_keys(&self, account_id: &AccountIdRef) -> impl Iterator<Item = PublicKey> + '_ {
-        let account = self.accounts.get(account_id);
+        let account = self.accounts.get(account_id).map(Lock::as_inner_unchecked);
         account
             .map(|account| account.iter_public_keys(account_id))
             .into_iter()
@@ -56,20 +100,130 @@ impl StateView for Contract {
             })
     }
 
+    #[inline]
+    fn public_key_scope(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<PublicKeyScope> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().public_key_scope(public_key))
+            .cloned()
+    }
+
+    #[inline]
+    fn public_key_added_at(
+        &self,
+        account_id: &AccountIdRef,
+        public_key: &PublicKey,
+    ) -> Option<Deadline> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().public_key_added_at(public_key))
+    }
+
+    #[inline]
+    fn threshold(&self, account_id: &AccountIdRef) -> u16 {
+        self.accounts
+            .get(account_id)
+            .map_or(1, |account| account.as_inner_unchecked().threshold())
+    }
+
+    #[inline]
+    fn is_account_registered(&self, account_id: &AccountIdRef) -> bool {
+        // the contract itself is always treated as registered
+        account_id == CURRENT_ACCOUNT_ID.as_ref() || self.accounts.get(account_id).is_some()
+    }
+
+    #[inline]
+    fn is_account_locked(&self, account_id: &AccountIdRef) -> bool {
+        self.accounts.get(account_id).is_some_and(Lock::is_locked)
+    }
+
+    #[inline]
+    fn lockdown(&self, account_id: &AccountIdRef) -> Option<Lockdown> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().lockdown())
+            .cloned()
+    }
+
+    #[inline]
+    fn guardians(&self, account_id: &AccountIdRef) -> Option<Guardians> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().guardians())
+            .cloned()
+    }
+
+    #[inline]
+    fn recovery(&self, account_id: &AccountIdRef) -> Option<Recovery> {
+        self.accounts
+            .get(account_id)
+            .and_then(|account| account.as_inner_unchecked().recovery())
+            .cloned()
+    }
+
     #[inline]
     fn is_nonce_used(&self, account_id: &AccountIdRef, nonce: Nonce) -> bool {
         self.accounts
             .get(account_id)
-            .is_some_and(|account| account.is_nonce_used(nonce))
+            .is_some_and(|account| account.as_inner_unchecked().is_nonce_used(nonce))
     }
 
     #[inline]
     fn balance_of(&self, account_id: &AccountIdRef, token_id: &TokenId) -> u128 {
         self.accounts
             .get(account_id)
-            .map(|account| account.token_balances.balance_of(token_id))
+            .map(|account| {
+                account
+                    .as_inner_unchecked()
+                    .token_balances
+                    .balance_of(token_id)
+            })
             .unwrap_or_default()
     }
+
+    fn iter_token_ids(&self, account_id: &AccountIdRef) -> impl Iterator<Item = TokenId> + '_ {
+        self.accounts
+            .get(account_id)
+            .into_iter()
+            .flat_map(|account| account.as_inner_unchecked().token_balances.iter())
+            .filter(|(_, amount)| **amount != 0)
+            .map(|(token_id, _)| token_id.clone())
+    }
+
+    #[inline]
+    fn limit_order(
+        &self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+    ) -> Option<LimitOrderState> {
+        self.accounts
+            .get(maker_id)
+            .and_then(|account| account.as_inner_unchecked().limit_order(&order_hash))
+            .cloned()
+    }
+
+    #[inline]
+    fn htlc(&self, sender_id: &AccountIdRef, hash_lock: &CryptoHash) -> Option<Htlc> {
+        self.accounts
+            .get(sender_id)
+            .and_then(|account| account.as_inner_unchecked().htlc(hash_lock))
+            .cloned()
+    }
+
+    fn iter_htlc_hash_locks(
+        &self,
+        sender_id: &AccountIdRef,
+    ) -> impl Iterator<Item = CryptoHash> + '_ {
+        self.accounts
+            .get(sender_id)
+            .into_iter()
+            .flat_map(|account| account.as_inner_unchecked().iter_htlc_hash_locks())
+            .copied()
+    }
 }
 
 impl State for Contract {
@@ -78,6 +232,7 @@ impl State for Contract {
     fn add_public_key(&mut self, account_id: AccountId, public_key: PublicKey) -> bool {
         self.accounts
             .get_or_create(account_id.clone())
+            .as_inner_unchecked_mut()
             .add_public_key(&account_id, public_key)
     }
 
@@ -86,13 +241,186 @@ impl State for Contract {
     fn remove_public_key(&mut self, account_id: AccountId, public_key: PublicKey) -> bool {
         self.accounts
             .get_or_create(account_id.clone())
+            .as_inner_unchecked_mut()
             .remove_public_key(&account_id, &public_key)
     }
 
+    #[inline]
+    fn set_public_key_scope(
+        &mut self,
+        account_id: AccountId,
+        public_key: PublicKey,
+        scope: PublicKeyScope,
+    ) {
+        self.accounts
+            .get_or_create(account_id)
+            .as_inner_unchecked_mut()
+            .set_public_key_scope(public_key, scope);
+    }
+
+    #[inline]
+    fn set_threshold(&mut self, account_id: AccountId, threshold: u16) {
+        self.accounts
+            .get_or_create(account_id.clone())
+            .as_inner_unchecked_mut()
+            .set_threshold(&account_id, threshold);
+    }
+
+    #[inline]
+    fn set_lockdown(&mut self, account_id: AccountId, lockdown: Lockdown) {
+        self.accounts
+            .get_or_create(account_id.clone())
+            .as_inner_unchecked_mut()
+            .set_lockdown(&account_id, lockdown);
+    }
+
+    #[must_use]
+    #[inline]
+    fn lock_account(&mut self, account_id: AccountId) -> bool {
+        self.accounts.lock(account_id)
+    }
+
+    #[inline]
+    fn set_guardians(&mut self, account_id: AccountId, guardians: Guardians) {
+        self.accounts
+            .get_or_create(account_id.clone())
+            .as_inner_unchecked_mut()
+            .set_guardians(&account_id, guardians);
+    }
+
+    #[inline]
+    fn approve_recovery(
+        &mut self,
+        account_id: AccountId,
+        guardian_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<()> {
+        self.accounts
+            .get_mut(&account_id)
+            .ok_or(DefuseError::NotGuardian)?
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)?
+            .approve_recovery(&account_id, guardian_id, public_key)
+    }
+
+    #[must_use]
+    #[inline]
+    fn cancel_recovery(&mut self, account_id: AccountId) -> bool {
+        self.accounts.get_mut(&account_id).is_some_and(|account| {
+            account
+                .as_inner_unchecked_mut()
+                .cancel_recovery(&account_id)
+        })
+    }
+
+    #[inline]
+    fn close_account(&mut self, account_id: AccountId, receiver_id: AccountId) -> Result<()> {
+        self.internal_close_account(&account_id, receiver_id)
+    }
+
     #[must_use]
     #[inline]
     fn commit_nonce(&mut self, account_id: AccountId, nonce: Nonce) -> bool {
-        self.accounts.get_or_create(account_id).commit_nonce(nonce)
+        self.accounts
+            .get_or_create(account_id)
+            .as_inner_unchecked_mut()
+            .commit_nonce(nonce)
+    }
+
+    #[must_use]
+    #[inline]
+    fn place_limit_order(
+        &mut self,
+        maker_id: AccountId,
+        order_hash: CryptoHash,
+        order: LimitOrderState,
+    ) -> bool {
+        self.accounts
+            .get_or_create(maker_id)
+            .as_inner_unchecked_mut()
+            .place_limit_order(order_hash, order)
+    }
+
+    #[inline]
+    fn fill_limit_order(
+        &mut self,
+        maker_id: &AccountIdRef,
+        order_hash: CryptoHash,
+        amount_in: u128,
+    ) -> Result<LimitOrderState> {
+        self.accounts
+            .get_mut(maker_id)
+            .ok_or(DefuseError::LimitOrderNotFound)?
+            .as_inner_unchecked_mut()
+            .fill_limit_order(order_hash, amount_in)
+    }
+
+    #[must_use]
+    #[inline]
+    fn cancel_limit_order(&mut self, maker_id: &AccountIdRef, order_hash: CryptoHash) -> bool {
+        self.accounts.get_mut(maker_id).is_some_and(|account| {
+            account
+                .as_inner_unchecked_mut()
+                .cancel_limit_order(&order_hash)
+        })
+    }
+
+    fn lock_htlc(&mut self, hash_lock: CryptoHash, htlc: Htlc) -> Result<()> {
+        // fails for locked senders
+        self.internal_withdraw(&htlc.sender_id, htlc.tokens.clone())?;
+        if !self
+            .accounts
+            .get_mut(&htlc.sender_id)
+            .ok_or(DefuseError::AccountNotFound)?
+            .as_inner_unchecked_mut()
+            .add_htlc(hash_lock, htlc)
+        {
+            return Err(DefuseError::HtlcExists);
+        }
+        Ok(())
+    }
+
+    fn release_htlc(
+        &mut self,
+        sender_id: &AccountIdRef,
+        hash_lock: &CryptoHash,
+        receiver_id: AccountId,
+    ) -> Result<Htlc> {
+        let htlc = self
+            .accounts
+            .get_mut(sender_id)
+            .and_then(|account| account.as_inner_unchecked_mut().remove_htlc(hash_lock))
+            .ok_or(DefuseError::HtlcNotFound)?;
+        self.internal_deposit(receiver_id, htlc.tokens.clone())?;
+        Ok(htlc)
+    }
+
+    fn record_fees_collected(
+        &mut self,
+        fees_collected: &TokenAmounts,
+        referral_fees: Option<(&AccountIdRef, &TokenAmounts)>,
+    ) -> Result<()> {
+        for (token_id, amount) in fees_collected {
+            self.state
+                .fees_collected
+                .deposit(token_id.clone(), *amount)
+                .ok_or(DefuseError::BalanceOverflow)?;
+        }
+        if let Some((referral, referral_fees)) =
+            referral_fees.filter(|(_, referral_fees)| !referral_fees.is_empty())
+        {
+            let collected = self
+                .state
+                .referral_fees_collected
+                .entry(referral.to_owned())
+                .or_default();
+            for (token_id, amount) in referral_fees {
+                collected
+                    .deposit(token_id.clone(), *amount)
+                    .ok_or(DefuseError::BalanceOverflow)?;
+            }
+        }
+        Ok(())
     }
 
     fn internal_deposit(
@@ -100,7 +428,11 @@ impl State for Contract {
         owner_id: AccountId,
         tokens: impl IntoIterator<Item = (TokenId, u128)>,
     ) -> Result<()> {
-        let owner = self.accounts.get_or_create(owner_id);
+        // locked accounts can still receive deposits
+        let owner = self
+            .accounts
+            .get_registered_mut(owner_id)?
+            .as_inner_unchecked_mut();
         for (token_id, amount) in tokens {
             if amount == 0 {
                 return Err(DefuseError::InvalidIntent);
@@ -121,7 +453,9 @@ impl State for Contract {
         let owner = self
             .accounts
             .get_mut(owner_id)
-            .ok_or(DefuseError::AccountNotFound)?;
+            .ok_or(DefuseError::AccountNotFound)?
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)?;
         for (token_id, amount) in tokens {
             if amount == 0 {
                 return Err(DefuseError::InvalidIntent);
@@ -136,19 +470,19 @@ impl State for Contract {
     }
 
     fn ft_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: FtWithdraw) -> Result<()> {
-        self.internal_ft_withdraw(owner_id.to_owned(), withdraw)
+        self.internal_ft_withdraw(owner_id.to_owned(), withdraw, false)
             // detach promise
             .map(|_promise| ())
     }
 
     fn nft_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: NftWithdraw) -> Result<()> {
-        self.internal_nft_withdraw(owner_id.to_owned(), withdraw)
+        self.internal_nft_withdraw(owner_id.to_owned(), withdraw, false)
             // detach promise
             .map(|_promise| ())
     }
 
     fn mt_withdraw(&mut self, owner_id: &AccountIdRef, withdraw: MtWithdraw) -> Result<()> {
-        self.internal_mt_withdraw(owner_id.to_owned(), withdraw)
+        self.internal_mt_withdraw(owner_id.to_owned(), withdraw, false)
             // detach promise
             .map(|_promise| ())
     }
@@ -161,6 +495,7 @@ impl State for Contract {
                 withdraw.amount.as_yoctonear(),
             )],
             Some("withdraw"),
+            false,
         )?;
 
         // detach promise
@@ -177,4 +512,95 @@ impl State for Contract {
 
         Ok(())
     }
+
+    fn storage_deposit(&mut self, owner_id: &AccountIdRef, deposit: StorageDeposit) -> Result<()> {
+        self.withdraw(
+            owner_id,
+            [(
+                TokenId::Nep141(self.wnear_id().into_owned()),
+                deposit.amount.as_yoctonear(),
+            )],
+            Some("storage_deposit"),
+            false,
+        )?;
+        self.internal_storage_deposit(deposit.account_id, deposit.amount)?;
+
+        // detach promise
+        let _ = ext_wnear::ext(self.wnear_id.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(NEAR_WITHDRAW_GAS)
+            .near_withdraw(U128(deposit.amount.as_yoctonear()));
+
+        Ok(())
+    }
+
+    fn call(&mut self, owner_id: &AccountIdRef, call: Call) -> Result<()> {
+        // make sure that refund callbacks will be able to execute
+        let mt_contracts: BTreeSet<_> = call
+            .tokens
+            .iter()
+            .filter_map(|(token_id, _)| match token_id {
+                TokenId::Nep245(token, _) => Some(token),
+                _ => None,
+            })
+            .collect();
+        let nep141_count = call
+            .tokens
+            .iter()
+            .filter(|(token_id, _)| matches!(token_id, TokenId::Nep141(_)))
+            .count();
+        let required_gas = Self::ft_transfer_call_with_refund_gas(&call)
+            .saturating_mul(nep141_count.try_into().unwrap_or(u64::MAX))
+            .saturating_add(
+                Self::mt_batch_transfer_call_with_refund_gas(&call)
+                    .saturating_mul(mt_contracts.len().try_into().unwrap_or(u64::MAX)),
+            );
+        if env::prepaid_gas().saturating_sub(env::used_gas()) < required_gas {
+            return Err(DefuseError::InsufficientGas);
+        }
+
+        self.withdraw(
+            owner_id,
+            call.tokens
+                .iter()
+                .map(|(token_id, amount)| (token_id.clone(), *amount)),
+            Some("withdraw"),
+            false,
+        )?;
+
+        // batch NEP-245 tokens per token contract
+        let mut mt_batches: BTreeMap<AccountId, (Vec<defuse_nep245::TokenId>, Vec<U128>)> =
+            BTreeMap::new();
+        for (token_id, amount) in call.tokens.iter() {
+            match token_id {
+                TokenId::Nep141(token) => {
+                    // detach promise
+                    let _ = Self::ft_transfer_call_with_refund(
+                        owner_id.to_owned(),
+                        token.clone(),
+                        *amount,
+                        &call,
+                    );
+                }
+                TokenId::Nep245(token, token_id) => {
+                    let (token_ids, amounts) = mt_batches.entry(token.clone()).or_default();
+                    token_ids.push(token_id.clone());
+                    amounts.push(U128(*amount));
+                }
+                TokenId::Nep171(_, _) => return Err(DefuseError::InvalidIntent),
+            }
+        }
+        for (token, (token_ids, amounts)) in mt_batches {
+            // detach promise
+            let _ = Self::mt_batch_transfer_call_with_refund(
+                owner_id.to_owned(),
+                token,
+                token_ids,
+                amounts,
+                &call,
+            );
+        }
+
+        Ok(())
+    }
 }
@@ -14,6 +14,7 @@ use core::iter;
 
 use defuse_core::Result;
 
+use defuse_near_utils::MaybeLegacy;
 use events::PostponedMtBurnEvents;
 use impl_tools::autoimpl;
 use near_plugins::{access_control, AccessControlRole, AccessControllable, Pausable};
@@ -26,7 +27,7 @@ use crate::Defuse;
 use self::{
     accounts::Accounts,
     config::{DefuseConfig, RolesConfig},
-    state::ContractState,
+    state::{ContractState, ContractStateV0},
 };
 
 #[near(serializers = [json])]
@@ -35,9 +36,12 @@ pub enum Role {
     DAO,
 
     FeesManager,
+    FeeDiscountsManager,
     RelayerKeysManager,
+    MetadataManager,
 
     UnrestrictedWithdrawer,
+    UnrestrictedAccountLocker,
 
     PauseManager,
     Upgrader,
@@ -58,7 +62,7 @@ pub enum Role {
 #[autoimpl(DerefMut using self.state)]
 pub struct Contract {
     accounts: Accounts,
-    state: ContractState,
+    state: MaybeLegacy<ContractState, ContractStateV0>,
 
     relayer_keys: LookupSet<near_sdk::PublicKey>,
 
@@ -71,13 +75,18 @@ impl Contract {
     #[must_use]
     #[init]
     pub fn new(config: DefuseConfig) -> Self {
+        require!(
+            config.fees.are_fee_collector_shares_valid(),
+            "invalid fee collector shares"
+        );
         let mut contract = Self {
             accounts: Accounts::new(Prefix::Accounts),
-            state: ContractState::new(Prefix::State, config.wnear_id, config.fees),
+            state: ContractState::new(Prefix::State, config.wnear_id, config.fees).into(),
             relayer_keys: LookupSet::new(Prefix::RelayerKeys),
             postponed_burns: PostponedMtBurnEvents::new(),
         };
         contract.init_acl(config.roles);
+        contract.register_fee_collectors();
         contract
     }
 
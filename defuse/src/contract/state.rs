@@ -1,10 +1,16 @@
+use std::collections::BTreeMap;
+
 use defuse_core::{
-    fees::FeesConfig,
+    fees::{FeeMode, FeeOverrides, FeesConfig, Pips, TokenPair},
     tokens::{TokenAmounts, TokenId},
 };
 use defuse_near_utils::NestPrefix;
+use defuse_nep245::metadata::MTTokenMetadataAll;
 use near_sdk::{
-    borsh::BorshSerialize, near, store::IterableMap, AccountId, BorshStorageKey, IntoStorageKey,
+    borsh::BorshSerialize,
+    near,
+    store::{IterableMap, LookupMap},
+    AccountId, BorshStorageKey, IntoStorageKey,
 };
 
 pub type TokenBalances = TokenAmounts<IterableMap<TokenId, u128>>;
@@ -17,6 +23,22 @@ pub struct ContractState {
     pub wnear_id: AccountId,
 
     pub fees: FeesConfig,
+
+    /// Per-token and per-pair overrides of `fees.fee`
+    pub fee_overrides: FeeOverrides<LookupMap<TokenId, Pips>, LookupMap<TokenPair, Pips>>,
+
+    /// Share of protocol fee waived for whitelisted accounts
+    pub fee_discounts: LookupMap<AccountId, Pips>,
+
+    /// Cumulative fees collected on `TokenDiff` intents per token,
+    /// including referral shares
+    pub fees_collected: TokenBalances,
+
+    /// Cumulative fees paid to referrals per token
+    pub referral_fees_collected: LookupMap<AccountId, TokenAmounts>,
+
+    /// NEP-245 metadata fetched from underlying token contracts
+    pub token_metadata: LookupMap<TokenId, MTTokenMetadataAll>,
 }
 
 impl ContractState {
@@ -25,12 +47,67 @@ impl ContractState {
     where
         S: IntoStorageKey,
     {
+        let prefix = prefix.into_storage_key();
         Self {
             total_supplies: TokenBalances::new(IterableMap::new(
-                prefix.into_storage_key().nest(Prefix::TotalSupplies),
+                prefix.as_slice().nest(Prefix::TotalSupplies),
             )),
             wnear_id,
             fees,
+            fee_overrides: FeeOverrides {
+                token_fees: LookupMap::new(prefix.as_slice().nest(Prefix::TokenFees)),
+                pair_fees: LookupMap::new(prefix.as_slice().nest(Prefix::PairFees)),
+            },
+            fee_discounts: LookupMap::new(prefix.as_slice().nest(Prefix::FeeDiscounts)),
+            fees_collected: TokenBalances::new(IterableMap::new(
+                prefix.as_slice().nest(Prefix::FeesCollected),
+            )),
+            referral_fees_collected: LookupMap::new(
+                prefix.as_slice().nest(Prefix::ReferralFeesCollected),
+            ),
+            token_metadata: LookupMap::new(prefix.as_slice().nest(Prefix::TokenMetadata)),
+        }
+    }
+}
+
+/// Layout of [`ContractState`] before it was stored in
+/// [`MaybeLegacy`](defuse_near_utils::MaybeLegacy)
+#[near(serializers = [borsh])]
+#[derive(Debug)]
+pub struct ContractStateV0 {
+    total_supplies: TokenBalances,
+    wnear_id: AccountId,
+    fees: FeesConfigV0,
+}
+
+#[near(serializers = [borsh])]
+#[derive(Debug)]
+struct FeesConfigV0 {
+    fee: Pips,
+    fee_collector: AccountId,
+}
+
+impl From<ContractStateV0> for ContractState {
+    fn from(
+        ContractStateV0 {
+            total_supplies,
+            wnear_id,
+            fees: FeesConfigV0 { fee, fee_collector },
+        }: ContractStateV0,
+    ) -> Self {
+        Self {
+            total_supplies,
+            ..Self::new(
+                super::Prefix::State,
+                wnear_id,
+                FeesConfig {
+                    fee,
+                    fee_collector,
+                    fee_collector_shares: BTreeMap::new(),
+                    referral_fee: Pips::ZERO,
+                    fee_mode: FeeMode::default(),
+                },
+            )
         }
     }
 }
@@ -39,4 +116,10 @@ impl ContractState {
 #[borsh(crate = "::near_sdk::borsh")]
 enum Prefix {
     TotalSupplies,
+    FeeDiscounts,
+    FeesCollected,
+    ReferralFeesCollected,
+    TokenMetadata,
+    TokenFees,
+    PairFees,
 }
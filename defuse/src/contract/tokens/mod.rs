@@ -19,7 +19,11 @@ impl Contract {
         tokens: impl IntoIterator<Item = (TokenId, u128)>,
         memo: Option<&str>,
     ) -> Result<()> {
-        let owner = self.accounts.get_or_create(owner_id.clone());
+        // locked accounts can still receive deposits
+        let owner = self
+            .accounts
+            .get_registered_mut(owner_id.clone())?
+            .as_inner_unchecked_mut();
 
         let mut mint_event = MtMintEvent {
             owner_id: owner_id.into(),
@@ -51,16 +55,37 @@ impl Contract {
         Ok(())
     }
 
+    /// Same as [`Self::deposit`], but re-creates the owner account if
+    /// it was closed meanwhile and doesn't check its storage deposit,
+    /// e.g. for refunds, which must not fail
+    pub(crate) fn deposit_unchecked(
+        &mut self,
+        owner_id: AccountId,
+        tokens: impl IntoIterator<Item = (TokenId, u128)>,
+        memo: Option<&str>,
+    ) -> Result<()> {
+        let mut tokens = tokens.into_iter().peekable();
+        if tokens.peek().is_none() {
+            // don't re-create closed accounts for nothing
+            return Ok(());
+        }
+        self.accounts.get_or_create_unchecked(owner_id.clone());
+        self.deposit(owner_id, tokens, memo)
+    }
+
     pub(crate) fn withdraw(
         &mut self,
         owner_id: &AccountIdRef,
         token_amounts: impl IntoIterator<Item = (TokenId, u128)>,
         memo: Option<impl Into<String>>,
+        force: bool,
     ) -> Result<()> {
         let owner = self
             .accounts
             .get_mut(owner_id)
-            .ok_or(DefuseError::AccountNotFound)?;
+            .ok_or(DefuseError::AccountNotFound)?
+            .get_mut_maybe_forced(force)
+            .ok_or(DefuseError::AccountLocked)?;
 
         let mut burn_event = MtBurnEvent {
             owner_id: Cow::Owned(owner_id.to_owned()),
@@ -1,6 +1,11 @@
 use core::iter;
 
-use defuse_core::{engine::StateView, intents::tokens::FtWithdraw, tokens::TokenId, Result};
+use defuse_core::{
+    engine::StateView,
+    intents::tokens::{Call, FtWithdraw},
+    tokens::TokenId,
+    Result,
+};
 use defuse_near_utils::{
     UnwrapOrPanic, UnwrapOrPanicError, CURRENT_ACCOUNT_ID, PREDECESSOR_ACCOUNT_ID,
 };
@@ -49,6 +54,7 @@ impl FungibleTokenWithdrawer for Contract {
                 msg,
                 storage_deposit: None,
             },
+            false,
         )
         .unwrap_or_panic()
     }
@@ -59,6 +65,7 @@ impl Contract {
         &mut self,
         owner_id: AccountId,
         withdraw: FtWithdraw,
+        force: bool,
     ) -> Result<PromiseOrValue<U128>> {
         self.withdraw(
             &owner_id,
@@ -71,6 +78,7 @@ impl Contract {
                 }),
             ),
             Some("withdraw"),
+            force,
         )?;
 
         let is_call = withdraw.msg.is_some();
@@ -99,6 +107,38 @@ impl Contract {
         )
         .into())
     }
+
+    /// Gas reserved by [`Self::ft_transfer_call_with_refund`],
+    /// including the refund callback
+    #[inline]
+    pub(crate) fn ft_transfer_call_with_refund_gas(call: &Call) -> Gas {
+        call.gas
+            .unwrap_or(FT_TRANSFER_CALL_GAS)
+            .saturating_add(Self::FT_RESOLVE_WITHDRAW_GAS)
+    }
+
+    /// Delivers already withdrawn `amount` of `token` via `ft_transfer_call()`
+    /// and refunds unused amount back to `owner_id`
+    pub(crate) fn ft_transfer_call_with_refund(
+        owner_id: AccountId,
+        token: AccountId,
+        amount: u128,
+        call: &Call,
+    ) -> Promise {
+        Promise::new(token.clone())
+            .ft_transfer_call(
+                &call.receiver_id,
+                amount,
+                call.memo.as_deref(),
+                &call.msg,
+                call.gas.unwrap_or(FT_TRANSFER_CALL_GAS),
+            )
+            .then(
+                Contract::ext(CURRENT_ACCOUNT_ID.clone())
+                    .with_static_gas(Contract::FT_RESOLVE_WITHDRAW_GAS)
+                    .ft_resolve_withdraw(token, owner_id, U128(amount), true),
+            )
+    }
 }
 
 #[near]
@@ -132,6 +172,7 @@ impl Contract {
                 withdraw.amount.0,
                 withdraw.memo.as_deref(),
                 msg,
+                FT_TRANSFER_CALL_GAS,
             )
         } else {
             p.ft_transfer(
@@ -182,7 +223,7 @@ impl FungibleTokenWithdrawResolver for Contract {
 
         let refund = amount.0.saturating_sub(used);
         if refund > 0 {
-            self.deposit(
+            self.deposit_unchecked(
                 sender_id,
                 [(TokenId::Nep141(token), refund)],
                 Some("refund"),
@@ -218,6 +259,7 @@ impl FungibleTokenForceWithdrawer for Contract {
                 msg,
                 storage_deposit: None,
             },
+            true,
         )
         .unwrap_or_panic()
     }
@@ -231,6 +273,7 @@ pub trait FtExt {
         amount: u128,
         memo: Option<&str>,
         msg: &str,
+        gas: Gas,
     ) -> Self;
 }
 
@@ -255,6 +298,7 @@ impl FtExt for Promise {
         amount: u128,
         memo: Option<&str>,
         msg: &str,
+        gas: Gas,
     ) -> Self {
         self.function_call(
             "ft_transfer_call".to_string(),
@@ -266,7 +310,7 @@ impl FtExt for Promise {
             }))
             .unwrap_or_panic_display(),
             NearToken::from_yoctonear(1),
-            FT_TRANSFER_CALL_GAS,
+            gas,
         )
     }
 }
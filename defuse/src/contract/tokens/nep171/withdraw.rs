@@ -49,6 +49,7 @@ impl NonFungibleTokenWithdrawer for Contract {
                 msg,
                 storage_deposit: None,
             },
+            false,
         )
         .unwrap_or_panic()
     }
@@ -59,6 +60,7 @@ impl Contract {
         &mut self,
         owner_id: AccountId,
         withdraw: NftWithdraw,
+        force: bool,
     ) -> Result<PromiseOrValue<bool>> {
         self.withdraw(
             &owner_id,
@@ -73,6 +75,7 @@ impl Contract {
                 )
             })),
             Some("withdraw"),
+            force,
         )?;
 
         let is_call = withdraw.msg.is_some();
@@ -172,7 +175,7 @@ impl NonFungibleTokenWithdrawResolver for Contract {
         };
 
         if !used {
-            self.deposit(
+            self.deposit_unchecked(
                 sender_id,
                 [(TokenId::Nep171(token, token_id), 1)],
                 Some("refund"),
@@ -208,6 +211,7 @@ impl NonFungibleTokenForceWithdrawer for Contract {
                 msg,
                 storage_deposit: None,
             },
+            true,
         )
         .unwrap_or_panic()
     }
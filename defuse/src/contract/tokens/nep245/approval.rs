@@ -0,0 +1,236 @@
+use defuse_core::{tokens::TokenId, DefuseError, Result};
+use defuse_near_utils::{Lock, UnwrapOrPanic, UnwrapOrPanicError, PREDECESSOR_ACCOUNT_ID};
+use defuse_nep245::{
+    approval::{ext_mt_approval_receiver, Approval, MultiTokenApproval},
+    ClearedApproval,
+};
+use near_plugins::{pause, Pausable};
+use near_sdk::{
+    assert_one_yocto, json_types::U128, near, require, AccountId, AccountIdRef, Promise,
+};
+
+use crate::contract::{Contract, ContractExt};
+
+#[near]
+impl MultiTokenApproval for Contract {
+    #[pause(name = "mt_transfer")]
+    #[payable]
+    fn mt_approve(
+        &mut self,
+        token_ids: Vec<defuse_nep245::TokenId>,
+        amounts: Vec<U128>,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        assert_one_yocto();
+        require!(
+            !token_ids.is_empty() && token_ids.len() == amounts.len(),
+            "invalid args"
+        );
+        require!(
+            account_id != *PREDECESSOR_ACCOUNT_ID,
+            "can't approve to yourself"
+        );
+
+        let owner = self
+            .accounts
+            .get_mut(&PREDECESSOR_ACCOUNT_ID)
+            .ok_or(DefuseError::AccountNotFound)
+            .unwrap_or_panic()
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)
+            .unwrap_or_panic();
+
+        let approval_ids = token_ids
+            .iter()
+            .zip(&amounts)
+            .map(|(token_id, amount)| {
+                let token_id: TokenId = token_id.parse().unwrap_or_panic_display();
+                owner.approve(token_id, account_id.clone(), amount.0)
+            })
+            .collect();
+
+        msg.map(|msg| {
+            ext_mt_approval_receiver::ext(account_id).mt_on_approve(
+                token_ids,
+                amounts,
+                PREDECESSOR_ACCOUNT_ID.clone(),
+                approval_ids,
+                msg,
+            )
+        })
+    }
+
+    #[pause(name = "mt_transfer")]
+    #[payable]
+    fn mt_revoke(&mut self, token_ids: Vec<defuse_nep245::TokenId>, account_id: AccountId) {
+        assert_one_yocto();
+
+        let owner = self
+            .accounts
+            .get_mut(&PREDECESSOR_ACCOUNT_ID)
+            .ok_or(DefuseError::AccountNotFound)
+            .unwrap_or_panic()
+            // locked accounts can still revoke approvals
+            .as_inner_unchecked_mut();
+        for token_id in token_ids {
+            owner.revoke(&token_id.parse().unwrap_or_panic_display(), &account_id);
+        }
+    }
+
+    #[pause(name = "mt_transfer")]
+    #[payable]
+    fn mt_revoke_all(&mut self, token_ids: Vec<defuse_nep245::TokenId>) {
+        assert_one_yocto();
+
+        let owner = self
+            .accounts
+            .get_mut(&PREDECESSOR_ACCOUNT_ID)
+            .ok_or(DefuseError::AccountNotFound)
+            .unwrap_or_panic()
+            // locked accounts can still revoke approvals
+            .as_inner_unchecked_mut();
+        for token_id in token_ids {
+            owner.revoke_all(&token_id.parse().unwrap_or_panic_display());
+        }
+    }
+
+    fn mt_is_approved(
+        &self,
+        owner_id: AccountId,
+        token_ids: Vec<defuse_nep245::TokenId>,
+        approved_account_id: AccountId,
+        amounts: Vec<U128>,
+        approval_ids: Option<Vec<u64>>,
+    ) -> bool {
+        if token_ids.len() != amounts.len()
+            || approval_ids
+                .as_ref()
+                .is_some_and(|ids| ids.len() != token_ids.len())
+        {
+            return false;
+        }
+
+        token_ids
+            .into_iter()
+            .zip(amounts)
+            .enumerate()
+            .all(|(i, (token_id, amount))| {
+                self.internal_mt_approval_of(&owner_id, &token_id, &approved_account_id)
+                    .is_some_and(|approval| {
+                        approval.amount.0 >= amount.0
+                            && approval_ids
+                                .as_ref()
+                                .is_none_or(|ids| ids[i] == approval.approval_id)
+                    })
+            })
+    }
+
+    fn mt_approval_of(
+        &self,
+        owner_id: AccountId,
+        token_id: defuse_nep245::TokenId,
+        approved_account_id: AccountId,
+    ) -> Option<Approval> {
+        self.internal_mt_approval_of(&owner_id, &token_id, &approved_account_id)
+    }
+}
+
+impl Contract {
+    fn internal_mt_approval_of(
+        &self,
+        owner_id: &AccountIdRef,
+        token_id: &defuse_nep245::TokenId,
+        approved_account_id: &AccountIdRef,
+    ) -> Option<Approval> {
+        self.accounts
+            .get(owner_id)?
+            .as_inner_unchecked()
+            .approval(&token_id.parse().ok()?, approved_account_id)
+            .copied()
+    }
+
+    /// Spends approvals given to the predecessor for transferring tokens.
+    /// All tokens in a batch must be owned by the same account, which is
+    /// returned along with spent approvals to be resolved by
+    /// [`Self::internal_mt_resolve_approvals`].
+    pub(crate) fn internal_mt_spend_approvals(
+        &mut self,
+        token_ids: &[defuse_nep245::TokenId],
+        amounts: &[U128],
+        approvals: Vec<Option<(AccountId, u64)>>,
+    ) -> Result<(AccountId, Vec<Option<Vec<ClearedApproval>>>)> {
+        if approvals.len() != token_ids.len() || token_ids.len() != amounts.len() {
+            return Err(DefuseError::InvalidIntent);
+        }
+        let mut approvals = approvals.into_iter().zip(token_ids.iter().zip(amounts));
+
+        let Some((Some((owner_id, approval_id)), (token_id, amount))) = approvals.next() else {
+            return Err(DefuseError::InvalidIntent);
+        };
+        let owner = self
+            .accounts
+            .get_mut(&owner_id)
+            .ok_or(DefuseError::AccountNotFound)?
+            .as_unlocked_mut()
+            .ok_or(DefuseError::AccountLocked)?;
+        owner.spend_approval(
+            &token_id.parse()?,
+            &PREDECESSOR_ACCOUNT_ID,
+            approval_id,
+            amount.0,
+        )?;
+        let mut cleared = vec![Some(vec![(
+            PREDECESSOR_ACCOUNT_ID.clone(),
+            approval_id,
+            *amount,
+        )])];
+
+        for (approval, (token_id, amount)) in approvals {
+            let (approval_owner_id, approval_id) = approval.ok_or(DefuseError::InvalidIntent)?;
+            if approval_owner_id != owner_id {
+                return Err(DefuseError::InvalidIntent);
+            }
+            owner.spend_approval(
+                &token_id.parse()?,
+                &PREDECESSOR_ACCOUNT_ID,
+                approval_id,
+                amount.0,
+            )?;
+            cleared.push(Some(vec![(
+                PREDECESSOR_ACCOUNT_ID.clone(),
+                approval_id,
+                *amount,
+            )]));
+        }
+
+        Ok((owner_id, cleared))
+    }
+
+    /// Restores `refunds` of approvals spent by
+    /// [`Self::internal_mt_spend_approvals`] and removes fully spent ones
+    pub(crate) fn internal_mt_resolve_approvals(
+        &mut self,
+        owner_id: &AccountIdRef,
+        token_ids: &[defuse_nep245::TokenId],
+        approvals: Vec<Option<Vec<ClearedApproval>>>,
+        refunds: impl IntoIterator<Item = u128>,
+    ) {
+        let Some(owner) = self
+            .accounts
+            .get_mut(owner_id)
+            // refunds are not restricted by locks
+            .map(Lock::as_inner_unchecked_mut)
+        else {
+            return;
+        };
+        for ((token_id, approvals), refund) in token_ids.iter().zip(approvals).zip(refunds) {
+            let Ok(token_id) = token_id.parse::<TokenId>() else {
+                continue;
+            };
+            for (account_id, approval_id, _amount) in approvals.into_iter().flatten() {
+                owner.resolve_approval(&token_id, &account_id, approval_id, refund);
+            }
+        }
+    }
+}
@@ -1,10 +1,12 @@
+use core::iter;
+
 use defuse_core::{engine::StateView, tokens::TokenId, DefuseError, Result};
 use defuse_near_utils::{UnwrapOrPanic, CURRENT_ACCOUNT_ID, PREDECESSOR_ACCOUNT_ID};
-use defuse_nep245::{receiver::ext_mt_receiver, MtEvent, MtTransferEvent, MultiTokenCore};
-use near_plugins::{pause, Pausable};
-use near_sdk::{
-    assert_one_yocto, json_types::U128, near, require, AccountId, AccountIdRef, PromiseOrValue,
+use defuse_nep245::{
+    receiver::ext_mt_receiver, ClearedApproval, MtEvent, MtTransferEvent, MultiTokenCore,
 };
+use near_plugins::{pause, Pausable};
+use near_sdk::{assert_one_yocto, json_types::U128, near, AccountId, AccountIdRef, PromiseOrValue};
 
 use crate::contract::{Contract, ContractExt};
 
@@ -41,16 +43,32 @@ impl MultiTokenCore for Contract {
         memo: Option<String>,
     ) {
         assert_one_yocto();
-        require!(approvals.is_none(), "approvals are not supported");
+
+        let owner_id = approvals
+            .map(|approvals| -> Result<_> {
+                let (owner_id, approvals) =
+                    self.internal_mt_spend_approvals(&token_ids, &amounts, approvals)?;
+                // nothing to refund here
+                self.internal_mt_resolve_approvals(
+                    &owner_id,
+                    &token_ids,
+                    approvals,
+                    iter::repeat(0),
+                );
+                Ok(owner_id)
+            })
+            .transpose()
+            .unwrap_or_panic();
 
         self.internal_mt_batch_transfer(
-            &PREDECESSOR_ACCOUNT_ID,
+            owner_id.as_deref().unwrap_or(&**PREDECESSOR_ACCOUNT_ID),
+            owner_id.is_some().then_some(&**PREDECESSOR_ACCOUNT_ID),
             receiver_id,
             token_ids,
             amounts,
             memo.as_deref(),
         )
-        .unwrap_or_panic()
+        .unwrap_or_panic();
     }
 
     #[pause(name = "mt_transfer")]
@@ -86,13 +104,21 @@ impl MultiTokenCore for Contract {
         msg: String,
     ) -> PromiseOrValue<Vec<U128>> {
         assert_one_yocto();
-        require!(approvals.is_none(), "approvals are not supported");
 
+        let (owner_id, approvals) = approvals
+            .map(|approvals| self.internal_mt_spend_approvals(&token_ids, &amounts, approvals))
+            .transpose()
+            .unwrap_or_panic()
+            .unzip();
+
+        let authorized_id = owner_id.is_some().then(|| PREDECESSOR_ACCOUNT_ID.clone());
         self.internal_mt_batch_transfer_call(
-            PREDECESSOR_ACCOUNT_ID.clone(),
+            owner_id.unwrap_or_else(|| PREDECESSOR_ACCOUNT_ID.clone()),
+            authorized_id,
             receiver_id,
             token_ids,
             amounts,
+            approvals,
             memo.as_deref(),
             msg,
         )
@@ -158,9 +184,12 @@ impl Contract {
         self.balance_of(account_id, &token_id)
     }
 
+    /// `authorized_id` is set when tokens are transferred on behalf of
+    /// `sender_id` via approval
     pub(crate) fn internal_mt_batch_transfer(
         &mut self,
         sender_id: &AccountIdRef,
+        authorized_id: Option<&AccountIdRef>,
         receiver_id: AccountId,
         token_ids: Vec<defuse_nep245::TokenId>,
         amounts: Vec<U128>,
@@ -179,11 +208,15 @@ impl Contract {
             self.accounts
                 .get_mut(sender_id)
                 .ok_or(DefuseError::AccountNotFound)?
+                .as_unlocked_mut()
+                .ok_or(DefuseError::AccountLocked)?
                 .token_balances
                 .withdraw(token_id.clone(), amount)
                 .ok_or(DefuseError::BalanceOverflow)?;
             self.accounts
-                .get_or_create(receiver_id.clone())
+                .get_registered_mut(receiver_id.clone())?
+                // locked accounts can still receive transfers
+                .as_inner_unchecked_mut()
                 .token_balances
                 .deposit(token_id, amount)
                 .ok_or(DefuseError::BalanceOverflow)?;
@@ -191,7 +224,7 @@ impl Contract {
 
         MtEvent::MtTransfer(
             [MtTransferEvent {
-                authorized_id: None,
+                authorized_id: authorized_id.map(Into::into),
                 old_owner_id: sender_id.into(),
                 new_owner_id: receiver_id.into(),
                 token_ids: token_ids.into(),
@@ -206,17 +239,22 @@ impl Contract {
         Ok(())
     }
 
+    /// Spent `approvals` are restored on refunds, see
+    /// [`Self::internal_mt_spend_approvals`]
     pub(crate) fn internal_mt_batch_transfer_call(
         &mut self,
         sender_id: AccountId,
+        authorized_id: Option<AccountId>,
         receiver_id: AccountId,
         token_ids: Vec<defuse_nep245::TokenId>,
         amounts: Vec<U128>,
+        approvals: Option<Vec<Option<Vec<ClearedApproval>>>>,
         memo: Option<&str>,
         msg: String,
     ) -> Result<PromiseOrValue<Vec<U128>>> {
         self.internal_mt_batch_transfer(
             &sender_id,
+            authorized_id.as_deref(),
             receiver_id.clone(),
             token_ids.clone(),
             amounts.clone(),
@@ -227,7 +265,7 @@ impl Contract {
 
         Ok(ext_mt_receiver::ext(receiver_id.clone())
             .mt_on_transfer(
-                sender_id,
+                authorized_id.unwrap_or_else(|| sender_id.clone()),
                 previous_owner_ids.clone(),
                 token_ids.clone(),
                 amounts.clone(),
@@ -236,7 +274,13 @@ impl Contract {
             .then(
                 Contract::ext(CURRENT_ACCOUNT_ID.clone())
                     .with_static_gas(MT_RESOLVE_TRANSFER_GAS)
-                    .mt_resolve_transfer(previous_owner_ids, receiver_id, token_ids, amounts, None),
+                    .mt_resolve_transfer(
+                        previous_owner_ids,
+                        receiver_id,
+                        token_ids,
+                        amounts,
+                        approvals,
+                    ),
             )
             .into())
     }
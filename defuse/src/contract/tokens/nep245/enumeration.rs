@@ -0,0 +1,64 @@
+use defuse_core::tokens::TokenId;
+use defuse_nep245::{enumeration::MultiTokenEnumeration, Token};
+use near_sdk::{json_types::U128, near, AccountId};
+
+use crate::contract::{Contract, ContractExt};
+
+/// Number of tokens returned when `limit` is not specified
+const DEFAULT_LIMIT: u32 = 100;
+/// Maximum number of tokens returned at once
+const MAX_LIMIT: u32 = 500;
+
+#[near]
+impl MultiTokenEnumeration for Contract {
+    fn mt_tokens(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<Token> {
+        paginate(
+            self.total_supplies.iter().map(|(token_id, _)| token_id),
+            from_index,
+            limit,
+        )
+    }
+
+    fn mt_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<Token> {
+        self.accounts
+            .get(&account_id)
+            .map(|account| {
+                paginate(
+                    account
+                        .as_inner_unchecked()
+                        .token_balances
+                        .iter()
+                        .map(|(token_id, _)| token_id),
+                    from_index,
+                    limit,
+                )
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn paginate<'a>(
+    token_ids: impl Iterator<Item = &'a TokenId>,
+    from_index: Option<U128>,
+    limit: Option<u32>,
+) -> Vec<Token> {
+    token_ids
+        .skip(from_index.map_or(0, |U128(i)| i.try_into().unwrap_or(usize::MAX)))
+        .take(
+            limit
+                .unwrap_or(DEFAULT_LIMIT)
+                .min(MAX_LIMIT)
+                .try_into()
+                .unwrap_or(usize::MAX),
+        )
+        .map(|token_id| Token {
+            token_id: token_id.to_string(),
+            owner_id: None,
+        })
+        .collect()
+}
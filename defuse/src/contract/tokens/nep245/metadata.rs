@@ -0,0 +1,292 @@
+use defuse_core::tokens::TokenId;
+use defuse_near_utils::{UnwrapOrPanicError, CURRENT_ACCOUNT_ID, PREDECESSOR_ACCOUNT_ID};
+use defuse_nep245::metadata::{
+    MTBaseTokenMetadata, MTContractMetadata, MTTokenMetadata, MTTokenMetadataAll,
+    MultiTokenMetadataProvider, MT_METADATA_SPEC,
+};
+use near_contract_standards::{
+    fungible_token::metadata::FungibleTokenMetadata,
+    non_fungible_token::{metadata::NFTContractMetadata, Token as NonFungibleToken},
+};
+use near_plugins::{access_control_any, pause, AccessControllable, Pausable};
+use near_sdk::{
+    assert_one_yocto, borsh, env, near, require,
+    serde::de::DeserializeOwned,
+    serde_json::{self, json},
+    AccountId, Gas, NearToken, Promise, PromiseResult,
+};
+
+use crate::{
+    contract::{Contract, ContractExt, Role},
+    tokens::nep245::{MultiTokenMetadataFetcher, MultiTokenMetadataForceFetcher},
+};
+
+const FETCH_METADATA_GAS: Gas = Gas::from_tgas(10);
+
+/// Metadata is stored on-chain, so we limit its size
+const MAX_METADATA_SIZE: usize = 16 * 1024;
+/// Upper bound for storage usage of cached metadata, including its key
+const MAX_METADATA_STORAGE_USAGE: u64 = 16 * 1024 + 512;
+
+#[near]
+impl MultiTokenMetadataProvider for Contract {
+    fn mt_metadata_contract(&self) -> MTContractMetadata {
+        MTContractMetadata {
+            spec: MT_METADATA_SPEC.to_string(),
+            name: "Defuse".to_string(),
+        }
+    }
+
+    fn mt_metadata_token_all(
+        &self,
+        token_ids: Vec<defuse_nep245::TokenId>,
+    ) -> Vec<Option<MTTokenMetadataAll>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| self.internal_mt_metadata(&token_id).cloned())
+            .collect()
+    }
+
+    fn mt_metadata_token_by_token_id(
+        &self,
+        token_ids: Vec<defuse_nep245::TokenId>,
+    ) -> Vec<Option<MTTokenMetadata>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| {
+                self.internal_mt_metadata(&token_id)
+                    .map(|metadata| metadata.token.clone())
+            })
+            .collect()
+    }
+
+    fn mt_metadata_base_by_token_id(
+        &self,
+        token_ids: Vec<defuse_nep245::TokenId>,
+    ) -> Vec<Option<MTBaseTokenMetadata>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| {
+                self.internal_mt_metadata(&token_id)
+                    .map(|metadata| metadata.base.clone())
+            })
+            .collect()
+    }
+
+    fn mt_metadata_base_by_metadata_id(
+        &self,
+        base_metadata_ids: Vec<String>,
+    ) -> Vec<Option<MTBaseTokenMetadata>> {
+        // each token has its own base metadata with `id` equal to token_id
+        self.mt_metadata_base_by_token_id(base_metadata_ids)
+    }
+}
+
+#[near]
+impl MultiTokenMetadataFetcher for Contract {
+    #[pause]
+    #[payable]
+    fn mt_fetch_metadata(&mut self, token_id: defuse_nep245::TokenId) -> Promise {
+        let token_id: TokenId = token_id.parse().unwrap_or_panic_display();
+        require!(
+            !self.token_metadata.contains_key(&token_id),
+            "metadata is already cached"
+        );
+        let deposit = env::attached_deposit();
+        require!(
+            deposit >= env::storage_byte_cost().saturating_mul(MAX_METADATA_STORAGE_USAGE.into()),
+            "insufficient deposit for metadata storage"
+        );
+
+        self.internal_mt_fetch_metadata(token_id, Some((PREDECESSOR_ACCOUNT_ID.clone(), deposit)))
+    }
+}
+
+#[near]
+impl MultiTokenMetadataForceFetcher for Contract {
+    #[pause(name = "mt_fetch_metadata")]
+    #[access_control_any(roles(Role::DAO, Role::MetadataManager))]
+    #[payable]
+    fn mt_force_fetch_metadata(&mut self, token_id: defuse_nep245::TokenId) -> Promise {
+        assert_one_yocto();
+        self.internal_mt_fetch_metadata(token_id.parse().unwrap_or_panic_display(), None)
+    }
+}
+
+#[near]
+impl Contract {
+    const MT_RESOLVE_FETCH_METADATA_GAS: Gas = Gas::from_tgas(10);
+
+    /// Caches fetched metadata and refunds `storage_deposit` not spent on
+    /// its storage. `None` means that the metadata is stored at the
+    /// expense of the contract and can overwrite cached one.
+    #[private]
+    pub fn mt_resolve_fetch_metadata(
+        &mut self,
+        token_id: TokenId,
+        storage_deposit: Option<(AccountId, NearToken)>,
+    ) -> bool {
+        let usage_before = env::storage_usage();
+        let metadata = fetched_metadata(&token_id)
+            .filter(|metadata| {
+                borsh::object_length(metadata).is_ok_and(|len| len <= MAX_METADATA_SIZE)
+            })
+            // metadata cached in the meantime can only be overwritten by admins
+            .filter(|_| storage_deposit.is_none() || !self.token_metadata.contains_key(&token_id));
+        let cached = metadata.is_some();
+        if let Some(metadata) = metadata {
+            self.token_metadata.insert(token_id, metadata);
+            self.token_metadata.flush();
+        }
+
+        if let Some((sender_id, deposit)) = storage_deposit {
+            let refund = deposit.saturating_sub(
+                env::storage_byte_cost()
+                    .saturating_mul(env::storage_usage().saturating_sub(usage_before).into()),
+            );
+            if !refund.is_zero() {
+                // detach promise
+                let _ = Promise::new(sender_id).transfer(refund);
+            }
+        }
+        cached
+    }
+}
+
+impl Contract {
+    fn internal_mt_fetch_metadata(
+        &mut self,
+        token_id: TokenId,
+        storage_deposit: Option<(AccountId, NearToken)>,
+    ) -> Promise {
+        require!(
+            self.total_supplies.contains_key(&token_id),
+            "token not found"
+        );
+
+        match &token_id {
+            TokenId::Nep141(contract_id) => Promise::new(contract_id.clone()).function_call(
+                "ft_metadata".to_string(),
+                b"{}".to_vec(),
+                NearToken::from_yoctonear(0),
+                FETCH_METADATA_GAS,
+            ),
+            TokenId::Nep171(contract_id, nft_id) => Promise::new(contract_id.clone())
+                .function_call(
+                    "nft_metadata".to_string(),
+                    b"{}".to_vec(),
+                    NearToken::from_yoctonear(0),
+                    FETCH_METADATA_GAS,
+                )
+                .and(
+                    Promise::new(contract_id.clone()).function_call(
+                        "nft_token".to_string(),
+                        serde_json::to_vec(&json!({
+                            "token_id": nft_id,
+                        }))
+                        .unwrap_or_panic_display(),
+                        NearToken::from_yoctonear(0),
+                        FETCH_METADATA_GAS,
+                    ),
+                ),
+            TokenId::Nep245(contract_id, mt_id) => Promise::new(contract_id.clone()).function_call(
+                "mt_metadata_token_all".to_string(),
+                serde_json::to_vec(&json!({
+                    "token_ids": [mt_id],
+                }))
+                .unwrap_or_panic_display(),
+                NearToken::from_yoctonear(0),
+                FETCH_METADATA_GAS,
+            ),
+        }
+        .then(
+            Contract::ext(CURRENT_ACCOUNT_ID.clone())
+                .with_static_gas(Contract::MT_RESOLVE_FETCH_METADATA_GAS)
+                .mt_resolve_fetch_metadata(token_id, storage_deposit),
+        )
+    }
+
+    fn internal_mt_metadata(
+        &self,
+        token_id: &defuse_nep245::TokenId,
+    ) -> Option<&MTTokenMetadataAll> {
+        self.token_metadata.get(&token_id.parse().ok()?)
+    }
+}
+
+/// Converts metadata returned by the underlying token contract
+fn fetched_metadata(token_id: &TokenId) -> Option<MTTokenMetadataAll> {
+    let id = token_id.to_string();
+    Some(match token_id {
+        TokenId::Nep141(_) => {
+            let ft: FungibleTokenMetadata = promise_result_json(0)?;
+            MTTokenMetadataAll {
+                base: MTBaseTokenMetadata {
+                    name: ft.name.clone(),
+                    id,
+                    symbol: Some(ft.symbol),
+                    icon: ft.icon,
+                    decimals: Some(ft.decimals.to_string()),
+                    base_uri: None,
+                    reference: ft.reference,
+                    copies: None,
+                    reference_hash: ft.reference_hash,
+                },
+                token: MTTokenMetadata {
+                    title: Some(ft.name),
+                    ..Default::default()
+                },
+            }
+        }
+        TokenId::Nep171(..) => {
+            let nft: NFTContractMetadata = promise_result_json(0)?;
+            // token metadata is optional
+            let token = promise_result_json::<Option<NonFungibleToken>>(1)
+                .flatten()
+                .and_then(|token| token.metadata);
+            MTTokenMetadataAll {
+                base: MTBaseTokenMetadata {
+                    name: nft.name,
+                    id,
+                    symbol: Some(nft.symbol),
+                    icon: nft.icon,
+                    decimals: None,
+                    base_uri: nft.base_uri,
+                    reference: nft.reference,
+                    copies: token.as_ref().and_then(|token| token.copies),
+                    reference_hash: nft.reference_hash,
+                },
+                token: token
+                    .map(|token| MTTokenMetadata {
+                        title: token.title,
+                        description: token.description,
+                        media: token.media,
+                        media_hash: token.media_hash,
+                        issued_at: token.issued_at,
+                        expires_at: token.expires_at,
+                        starts_at: token.starts_at,
+                        updated_at: token.updated_at,
+                        extra: token.extra,
+                        reference: token.reference,
+                        reference_hash: token.reference_hash,
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+        TokenId::Nep245(..) => {
+            let mut metadata = promise_result_json::<Vec<Option<MTTokenMetadataAll>>>(0)?
+                .into_iter()
+                .next()
+                .flatten()?;
+            metadata.base.id = id;
+            metadata
+        }
+    })
+}
+
+fn promise_result_json<T: DeserializeOwned>(result_idx: u64) -> Option<T> {
+    let PromiseResult::Successful(value) = env::promise_result(result_idx) else {
+        return None;
+    };
+    serde_json::from_slice(&value).ok()
+}
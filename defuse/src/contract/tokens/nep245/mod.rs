@@ -1,4 +1,7 @@
+mod approval;
 mod core;
 mod deposit;
+mod enumeration;
+mod metadata;
 mod resolver;
 mod withdraw;
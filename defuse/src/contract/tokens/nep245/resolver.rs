@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use defuse_near_utils::{UnwrapOrPanic, UnwrapOrPanicError};
+use defuse_near_utils::{Lock, UnwrapOrPanic, UnwrapOrPanicError};
 use defuse_nep245::{
     resolver::MultiTokenResolver, ClearedApproval, MtEventEmit, MtTransferEvent, TokenId,
 };
@@ -21,11 +21,13 @@ impl MultiTokenResolver for Contract {
         #[allow(unused_mut)] mut amounts: Vec<U128>,
         approvals: Option<Vec<Option<Vec<ClearedApproval>>>>,
     ) -> Vec<U128> {
-        require!(approvals.is_none(), "approvals are not supported");
         require!(
             !token_ids.is_empty()
                 && previous_owner_ids.len() == token_ids.len()
-                && amounts.len() == token_ids.len(),
+                && amounts.len() == token_ids.len()
+                && approvals
+                    .as_ref()
+                    .is_none_or(|approvals| approvals.len() == token_ids.len()),
             "inavlid args"
         );
 
@@ -47,13 +49,19 @@ impl MultiTokenResolver for Contract {
         {
             require!(
                 sender_id == previous_owner_id,
-                "approvals are not supported"
+                "all tokens must be owned by the same account"
             );
 
             refund.0 = refund.0.min(amount.0);
-            let Some(receiver) = self.accounts.get_mut(&receiver_id) else {
+            let Some(receiver) = self
+                .accounts
+                .get_mut(&receiver_id)
+                // refunds are not restricted by locks
+                .map(Lock::as_inner_unchecked_mut)
+            else {
                 // receiver doesn't have an account, so nowhere to refund from
-                return amounts;
+                refund.0 = 0;
+                continue;
             };
             let receiver_balance = receiver.token_balances.balance_of(&token_id);
             // refund maximum what we can
@@ -69,7 +77,10 @@ impl MultiTokenResolver for Contract {
                 .withdraw(token_id.clone(), refund.0)
                 .unwrap_or_panic();
             // deposit refund
-            let previous_owner = self.accounts.get_or_create(previous_owner_id);
+            let previous_owner = self
+                .accounts
+                .get_or_create_unchecked(previous_owner_id)
+                .as_inner_unchecked_mut();
             previous_owner
                 .token_balances
                 .deposit(token_id, refund.0)
@@ -79,6 +90,16 @@ impl MultiTokenResolver for Contract {
             amount.0 -= refund.0;
         }
 
+        if let Some(approvals) = approvals {
+            // restore approvals spent in `mt_batch_transfer_call()`
+            self.internal_mt_resolve_approvals(
+                &sender_id,
+                &token_ids,
+                approvals,
+                refunds.iter().map(|refund| refund.0),
+            );
+        }
+
         let (refunded_token_ids, refunded_amounts): (Vec<_>, Vec<_>) = token_ids
             .into_iter()
             .zip(refunds)
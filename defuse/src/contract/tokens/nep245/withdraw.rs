@@ -3,7 +3,10 @@
 use core::iter;
 
 use defuse_core::{
-    engine::StateView, intents::tokens::MtWithdraw, tokens::TokenId, DefuseError, Result,
+    engine::StateView,
+    intents::tokens::{Call, MtWithdraw},
+    tokens::TokenId,
+    DefuseError, Result,
 };
 use defuse_near_utils::{
     UnwrapOrPanic, UnwrapOrPanicError, CURRENT_ACCOUNT_ID, PREDECESSOR_ACCOUNT_ID,
@@ -52,6 +55,7 @@ impl MultiTokenWithdrawer for Contract {
                 msg,
                 storage_deposit: None,
             },
+            false,
         )
         .unwrap_or_panic()
     }
@@ -62,6 +66,7 @@ impl Contract {
         &mut self,
         owner_id: AccountId,
         withdraw: MtWithdraw,
+        force: bool,
     ) -> Result<PromiseOrValue<Vec<U128>>> {
         if withdraw.token_ids.len() != withdraw.amounts.len() || withdraw.token_ids.is_empty() {
             return Err(DefuseError::InvalidIntent);
@@ -80,6 +85,7 @@ impl Contract {
                     )
                 })),
             Some("withdraw"),
+            force,
         )?;
 
         let is_call = withdraw.msg.is_some();
@@ -114,6 +120,40 @@ impl Contract {
         )
         .into())
     }
+
+    /// Gas reserved by [`Self::mt_batch_transfer_call_with_refund`],
+    /// including the refund callback
+    #[inline]
+    pub(crate) fn mt_batch_transfer_call_with_refund_gas(call: &Call) -> Gas {
+        call.gas
+            .unwrap_or(MT_BATCH_TRANSFER_CALL_GAS)
+            .saturating_add(Self::MT_RESOLVE_WITHDRAW_GAS)
+    }
+
+    /// Delivers already withdrawn `amounts` of `token` via
+    /// `mt_batch_transfer_call()` and refunds unused amounts back to `owner_id`
+    pub(crate) fn mt_batch_transfer_call_with_refund(
+        owner_id: AccountId,
+        token: AccountId,
+        token_ids: Vec<defuse_nep245::TokenId>,
+        amounts: Vec<U128>,
+        call: &Call,
+    ) -> Promise {
+        Promise::new(token.clone())
+            .mt_batch_transfer_call(
+                &call.receiver_id,
+                &token_ids,
+                &amounts,
+                call.memo.as_deref(),
+                &call.msg,
+                call.gas.unwrap_or(MT_BATCH_TRANSFER_CALL_GAS),
+            )
+            .then(
+                Contract::ext(CURRENT_ACCOUNT_ID.clone())
+                    .with_static_gas(Contract::MT_RESOLVE_WITHDRAW_GAS)
+                    .mt_resolve_withdraw(token, owner_id, token_ids, amounts, true),
+            )
+    }
 }
 
 #[near]
@@ -147,6 +187,7 @@ impl Contract {
                 &withdraw.amounts,
                 withdraw.memo.as_deref(),
                 msg,
+                MT_BATCH_TRANSFER_CALL_GAS,
             )
         } else {
             p.mt_batch_transfer(
@@ -202,7 +243,7 @@ impl MultiTokenWithdrawResolver for Contract {
             }
         };
 
-        self.deposit(
+        self.deposit_unchecked(
             sender_id,
             token_ids
                 .into_iter()
@@ -252,6 +293,7 @@ impl MultiTokenForceWithdrawer for Contract {
                 msg,
                 storage_deposit: None,
             },
+            true,
         )
         .unwrap_or_panic()
     }
@@ -273,6 +315,7 @@ pub trait MtExt {
         amounts: &[U128],
         memo: Option<&str>,
         msg: &str,
+        gas: Gas,
     ) -> Self;
 }
 
@@ -305,6 +348,7 @@ impl MtExt for Promise {
         amounts: &[U128],
         memo: Option<&str>,
         msg: &str,
+        gas: Gas,
     ) -> Self {
         self.function_call(
             "mt_batch_transfer_call".to_string(),
@@ -317,7 +361,7 @@ impl MtExt for Promise {
             }))
             .unwrap_or_panic_display(),
             NearToken::from_yoctonear(1),
-            MT_BATCH_TRANSFER_CALL_GAS,
+            gas,
         )
     }
 }
@@ -28,6 +28,11 @@ impl ControllerUpgradable for Contract {
             )
     }
 
+    /// Legacy state is migrated lazily on read, see
+    /// [`MaybeLegacy`](defuse_near_utils::MaybeLegacy), so that
+    /// it only needs to be written back here
     #[private]
-    fn state_migrate(&mut self) {}
+    fn state_migrate(&mut self) {
+        self.register_fee_collectors();
+    }
 }
@@ -1,6 +1,11 @@
-use defuse_core::fees::Pips;
+use std::collections::BTreeMap;
+
+use defuse_core::{
+    fees::{FeeMode, Pips},
+    tokens::TokenId,
+};
 use near_plugins::AccessControllable;
-use near_sdk::{ext_contract, AccountId};
+use near_sdk::{ext_contract, json_types::U128, AccountId};
 
 #[ext_contract(ext_fees_manager)]
 #[allow(clippy::module_name_repetitions)]
@@ -9,6 +14,52 @@ pub trait FeesManager: AccessControllable {
     fn set_fee(&mut self, fee: Pips);
     fn fee(&self) -> Pips;
 
+    /// Override fee for given `token_id` or remove the override if `None`
+    fn set_token_fee(&mut self, token_id: TokenId, fee: Option<Pips>);
+    fn token_fee(&self, token_id: TokenId) -> Option<Pips>;
+
+    /// Override fee for swaps between given pair of tokens in any direction
+    /// or remove the override if `None`
+    fn set_pair_fee(&mut self, token_a: TokenId, token_b: TokenId, fee: Option<Pips>);
+    fn pair_fee(&self, token_a: TokenId, token_b: TokenId) -> Option<Pips>;
+
+    /// Returns effective fee taken on `token_in` when it's swapped
+    /// for `token_out`
+    fn fee_for(&self, token_in: TokenId, token_out: Option<TokenId>) -> Pips;
+
+    /// Waive given share of protocol fee for `account_id`, i.e.
+    /// [`Pips::MAX`] exempts it from fees completely.
+    /// Removes the discount if `None`.
+    fn set_fee_discount(&mut self, account_id: AccountId, discount: Option<Pips>);
+    fn fee_discount(&self, account_id: AccountId) -> Pips;
+
+    /// Set share of collected fees to be paid to referrals
+    fn set_referral_fee(&mut self, referral_fee: Pips);
+    fn referral_fee(&self) -> Pips;
+
+    /// Set side of `TokenDiff` intents to charge fees on
+    fn set_fee_mode(&mut self, fee_mode: FeeMode);
+    fn fee_mode(&self) -> FeeMode;
+
     fn set_fee_collector(&mut self, fee_collector: AccountId);
     fn fee_collector(&self) -> &AccountId;
+
+    /// Set additional fee collectors with their shares of collected fees.
+    /// Shares should sum up to at most [`Pips::MAX`], the rest goes to
+    /// [`fee_collector`](FeesManager::fee_collector).
+    fn set_fee_collector_shares(&mut self, shares: BTreeMap<AccountId, Pips>);
+    fn fee_collector_shares(&self) -> &BTreeMap<AccountId, Pips>;
+
+    /// Returns cumulative fees collected on `TokenDiff` intents per token,
+    /// including referral shares
+    fn fees_collected(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<(TokenId, U128)>;
+    fn token_fees_collected(&self, token_id: TokenId) -> U128;
+
+    /// Returns cumulative fees paid to given `referral` per token
+    fn referral_fees_collected(
+        &self,
+        referral: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<(TokenId, U128)>;
 }
@@ -1,19 +1,102 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use defuse_core::{
-    accounts::AccountEvent, engine::deltas::InvariantViolated, fees::Pips, intents::IntentEvent,
-    payload::multi::MultiPayload, Deadline, Result,
+    accounts::AccountEvent,
+    crypto::PublicKey as DefusePublicKey,
+    engine::deltas::InvariantViolated,
+    fees::{FeeMode, Pips},
+    intents::{
+        htlc::Htlc, limit_order::LimitOrderState, token_diff::TokenDeltas, tokens::Withdrawal,
+        IntentEvent,
+    },
+    payload::{multi::UnsignedMultiPayload, multisig::MultiSigPayload, PayloadVersion},
+    tokens::{TokenAmounts, TokenId},
+    Deadline, Result,
 };
 
 use near_plugins::AccessControllable;
-use near_sdk::{ext_contract, near, Promise, PublicKey};
-use serde_with::serde_as;
+use near_sdk::{
+    ext_contract, json_types::Base58CryptoHash, near, serde_json, AccountId, Promise, PublicKey,
+};
+use serde_with::{serde_as, DisplayFromStr};
+use thiserror::Error as ThisError;
 
 use crate::fees::FeesManager;
 
 #[ext_contract(ext_intents)]
 pub trait Intents: FeesManager {
-    fn execute_intents(&mut self, signed: Vec<MultiPayload>);
+    fn execute_intents(&mut self, signed: Vec<MultiSigPayload>);
+
+    fn simulate_intents(&self, signed: Vec<MultiSigPayload>) -> SimulationOutput;
+
+    /// Same as [`simulate_intents`](Intents::simulate_intents), but with
+    /// hypothetical changes applied to the state before execution.
+    /// If `collect_errors` is set, failed payloads are reported in
+    /// [`SimulationOutput::errors`] instead of failing the simulation.
+    fn simulate_intents_with_overrides(
+        &self,
+        signed: Vec<MultiSigPayload>,
+        overrides: StateOverrides,
+        collect_errors: Option<bool>,
+    ) -> SimulationOutput;
 
-    fn simulate_intents(&self, signed: Vec<MultiPayload>) -> SimulationOutput;
+    /// Same as [`simulate_intents`](Intents::simulate_intents), but for
+    /// payloads which are not signed yet. Signatures are not verified,
+    /// so intents are executed on behalf of claimed `signer_id` in each
+    /// payload as if it was signed with claimed public keys. This allows
+    /// to show the exact outcome before asking the user to sign.
+    /// Intent hashes in events match the ones of signed payloads, while
+    /// nonces are only checked but never committed, so the same nonce
+    /// can be reused across simulated payloads.
+    /// If `collect_errors` is set, failed payloads are reported in
+    /// [`SimulationOutput::errors`] instead of failing the simulation.
+    fn simulate_unsigned_intents(
+        &self,
+        payloads: Vec<UnsignedPayload>,
+        collect_errors: Option<bool>,
+    ) -> SimulationOutput;
+
+    /// Returns current state of the limit order placed by `maker_id`,
+    /// or `None` if it doesn't exist or was already fully filled
+    fn limit_order(
+        &self,
+        maker_id: AccountId,
+        order_hash: Base58CryptoHash,
+    ) -> Option<LimitOrderState>;
+
+    /// Returns hash-time-locked escrow by its sender and hash lock
+    fn htlc(&self, sender_id: AccountId, hash_lock: Base58CryptoHash) -> Option<Htlc>;
+
+    /// Returns versions of signed payloads this contract can execute
+    fn supported_payload_versions(&self) -> Vec<PayloadVersion>;
+}
+
+/// Hypothetical changes to the state, e.g. to simulate a fill before
+/// the deposit has landed
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [json])]
+#[derive(Debug, Clone, Default)]
+pub struct StateOverrides {
+    /// Token balances to set for accounts
+    #[serde_as(as = "BTreeMap<_, TokenAmounts<BTreeMap<_, DisplayFromStr>>>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub balances: BTreeMap<AccountId, TokenAmounts>,
+
+    /// Public keys to add to accounts
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub public_keys: BTreeMap<AccountId, BTreeSet<DefusePublicKey>>,
+
+    /// Fee to charge on all tokens and pairs instead of the current ones.
+    /// Fee discounts are still applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Pips>,
 }
 
 #[cfg_attr(
@@ -38,23 +121,127 @@ pub struct SimulationOutput {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub invariant_violated: Option<InvariantViolated>,
 
+    /// Outcomes of simulated intents in order of their execution,
+    /// along with corresponding signers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outcomes: Vec<IntentEvent<AccountEvent<'static, IntentOutcome>>>,
+
+    /// Payloads which failed to execute in order of their execution,
+    /// only reported if requested. Changes made by them are discarded,
+    /// while the rest of payloads are still simulated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<PayloadError>,
+
     /// Additional info about current state
     pub state: StateOutput,
 }
 
 impl SimulationOutput {
-    pub fn into_result(self) -> Result<(), InvariantViolated> {
+    pub fn into_result(self) -> Result<(), SimulationError> {
+        if let Some(error) = self.errors.into_iter().next() {
+            return Err(SimulationError::Payload(error));
+        }
         if let Some(unmatched_deltas) = self.invariant_violated {
-            return Err(unmatched_deltas);
+            return Err(SimulationError::InvariantViolated(unmatched_deltas));
         }
         Ok(())
     }
 }
 
+/// Payload which is not signed yet
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct UnsignedPayload {
+    /// Payload in the standard it is going to be signed under
+    #[serde(flatten)]
+    pub payload: UnsignedMultiPayload,
+    /// Public keys the payload is going to be signed with
+    pub public_keys: BTreeSet<DefusePublicKey>,
+}
+
+/// Error of a payload which failed to execute during simulation
+#[near(serializers = [json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadError {
+    /// Index of the payload among simulated ones
+    pub index: u32,
+    pub error: String,
+}
+
+#[derive(Debug, ThisError)]
+pub enum SimulationError {
+    #[error("payload #{}: {}", .0.index, .0.error)]
+    Payload(PayloadError),
+    #[error(
+        "invariant violated: {}",
+        near_sdk::serde_json::to_string(.0).unwrap_or_else(|_| unreachable!())
+    )]
+    InvariantViolated(InvariantViolated),
+}
+
+#[cfg_attr(
+    all(feature = "abi", not(target_arch = "wasm32")),
+    serde_as(schemars = true)
+)]
+#[cfg_attr(
+    not(all(feature = "abi", not(target_arch = "wasm32"))),
+    serde_as(schemars = false)
+)]
+#[near(serializers = [json])]
+#[derive(Debug, Clone, Default)]
+pub struct IntentOutcome {
+    /// Resulting changes of token balances per account
+    #[serde_as(as = "BTreeMap<_, TokenAmounts<BTreeMap<_, DisplayFromStr>>>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub balance_deltas: BTreeMap<AccountId, TokenDeltas>,
+
+    /// Fees collected on `token_diff` intents
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    #[serde(default, skip_serializing_if = "TokenAmounts::is_empty")]
+    pub fees_collected: TokenAmounts,
+
+    /// Part of `fees_collected` paid to referrals
+    #[serde_as(as = "TokenAmounts<BTreeMap<_, DisplayFromStr>>")]
+    #[serde(default, skip_serializing_if = "TokenAmounts::is_empty")]
+    pub referral_fees: TokenAmounts,
+
+    /// Withdrawals that would be scheduled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub withdrawals: Vec<Withdrawal<'static>>,
+
+    /// Events that would be emitted by the intents themselves
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<serde_json::Value>,
+}
+
 #[near(serializers = [json])]
 #[derive(Debug, Clone)]
 pub struct StateOutput {
     pub fee: Pips,
+
+    /// Side of `token_diff` intents fees are charged on
+    #[serde(default)]
+    pub fee_mode: FeeMode,
+
+    /// Effective fees taken on `token_in` (or `token_out`, depending on
+    /// `fee_mode`) of simulated `token_diff` intents in order of their
+    /// execution, including fee discounts of their signers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_fees: Vec<TokenFeeOutput>,
+}
+
+/// Effective fee taken on `token_id` of a simulated `token_diff` intent
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct TokenFeeOutput {
+    pub token_id: TokenId,
+
+    /// The other token of the pair if the intent swaps exactly one
+    /// token for another, so that pair fee overrides apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counterpart: Option<TokenId>,
+
+    pub fee: Pips,
 }
 
 #[ext_contract(ext_relayer_keys)]
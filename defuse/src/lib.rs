@@ -11,21 +11,27 @@ pub use defuse_nep245 as nep245;
 
 use defuse_admin_utils::full_access_keys::FullAccessKeys;
 use defuse_controller::ControllerUpgradable;
-use defuse_nep245::{receiver::MultiTokenReceiver, MultiTokenCore};
+use defuse_nep245::{
+    approval::MultiTokenApproval, enumeration::MultiTokenEnumeration,
+    metadata::MultiTokenMetadataProvider, receiver::MultiTokenReceiver, MultiTokenCore,
+};
 use near_contract_standards::{
     fungible_token::receiver::FungibleTokenReceiver,
-    non_fungible_token::core::NonFungibleTokenReceiver,
+    non_fungible_token::core::NonFungibleTokenReceiver, storage_management::StorageManagement,
 };
 use near_plugins::{AccessControllable, Pausable};
 use near_sdk::ext_contract;
 
 use self::{
-    accounts::AccountManager,
+    accounts::{AccountForceLocker, AccountManager},
     intents::{Intents, RelayerKeys},
     tokens::{
         nep141::{FungibleTokenForceWithdrawer, FungibleTokenWithdrawer},
         nep171::{NonFungibleTokenForceWithdrawer, NonFungibleTokenWithdrawer},
-        nep245::{MultiTokenForceWithdrawer, MultiTokenWithdrawer},
+        nep245::{
+            MultiTokenForceWithdrawer, MultiTokenMetadataFetcher, MultiTokenMetadataForceFetcher,
+            MultiTokenWithdrawer,
+        },
     },
 };
 
@@ -34,7 +40,13 @@ pub trait Defuse:
     Intents
     + RelayerKeys
     + AccountManager
+    // NEP-145
+    + StorageManagement
     + MultiTokenCore
+    + MultiTokenApproval
+    + MultiTokenEnumeration
+    + MultiTokenMetadataProvider
+    + MultiTokenMetadataFetcher
     // NEP-141 deposits/withdrawals
     + FungibleTokenReceiver
     + FungibleTokenWithdrawer
@@ -49,6 +61,8 @@ pub trait Defuse:
     + FungibleTokenForceWithdrawer
     + NonFungibleTokenForceWithdrawer
     + MultiTokenForceWithdrawer
+    + MultiTokenMetadataForceFetcher
+    + AccountForceLocker
     + Pausable
     + ControllerUpgradable
     + FullAccessKeys
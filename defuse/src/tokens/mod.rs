@@ -7,7 +7,7 @@ use core::{
     str::FromStr,
 };
 
-use defuse_core::payload::multi::MultiPayload;
+use defuse_core::payload::multisig::MultiSigPayload;
 use defuse_near_utils::UnwrapOrPanicError;
 use near_account_id::ParseAccountError;
 use near_sdk::{near, serde_json, AccountId};
@@ -19,7 +19,7 @@ pub struct DepositMessage {
     pub receiver_id: AccountId,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub execute_intents: Vec<MultiPayload>,
+    pub execute_intents: Vec<MultiSigPayload>,
 
     #[serde(default, skip_serializing_if = "::core::ops::Not::not")]
     pub refund_if_fails: bool,
@@ -38,8 +38,12 @@ impl DepositMessage {
 
     #[must_use]
     #[inline]
-    pub fn with_execute_intents(mut self, intents: impl IntoIterator<Item = MultiPayload>) -> Self {
-        self.execute_intents.extend(intents);
+    pub fn with_execute_intents(
+        mut self,
+        intents: impl IntoIterator<Item = impl Into<MultiSigPayload>>,
+    ) -> Self {
+        self.execute_intents
+            .extend(intents.into_iter().map(Into::into));
         self
     }
 
@@ -2,7 +2,7 @@
 
 use defuse_nep245::{receiver::MultiTokenReceiver, TokenId};
 use near_plugins::AccessControllable;
-use near_sdk::{ext_contract, json_types::U128, AccountId, PromiseOrValue};
+use near_sdk::{ext_contract, json_types::U128, AccountId, Promise, PromiseOrValue};
 
 #[ext_contract(ext_mt_withdraw)]
 pub trait MultiTokenWithdrawer: MultiTokenReceiver + MultiTokenWithdrawResolver {
@@ -35,6 +35,28 @@ pub trait MultiTokenWithdrawResolver {
     ) -> Vec<U128>;
 }
 
+#[ext_contract(ext_mt_metadata_fetcher)]
+pub trait MultiTokenMetadataFetcher {
+    /// Fetches metadata of the underlying token from its contract and
+    /// caches it to be served via NEP-245 metadata methods. Only tokens
+    /// with non-zero total supply and no cached metadata are supported.
+    ///
+    /// Attached deposit pays for storage of the metadata, the rest of
+    /// it is refunded to the predecessor.
+    ///
+    /// Returns whether the metadata was cached.
+    fn mt_fetch_metadata(&mut self, token_id: TokenId) -> Promise;
+}
+
+#[ext_contract(ext_mt_metadata_force_fetcher)]
+pub trait MultiTokenMetadataForceFetcher: MultiTokenMetadataFetcher + AccessControllable {
+    /// Same as [`MultiTokenMetadataFetcher::mt_fetch_metadata`], but
+    /// overwrites already cached metadata at the expense of the contract.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn mt_force_fetch_metadata(&mut self, token_id: TokenId) -> Promise;
+}
+
 #[ext_contract(ext_mt_force_withdraw)]
 pub trait MultiTokenForceWithdrawer: MultiTokenWithdrawer + AccessControllable {
     fn mt_force_withdraw(
@@ -0,0 +1,135 @@
+use core::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use near_sdk::borsh::{
+    io::{self, Read},
+    BorshDeserialize, BorshSerialize,
+};
+
+/// Borsh wrapper for lazy migration of values stored in a legacy layout.
+///
+/// `T` is always serialized after [`MaybeLegacy::MAGIC_PREFIX`], while
+/// values stored without it are deserialized as legacy `L` and
+/// converted into `T`. So the first 4 bytes of `L` must never be equal
+/// to the prefix, e.g. when `L` starts with a length of a collection.
+#[derive(Debug)]
+pub struct MaybeLegacy<T, L> {
+    value: T,
+    _legacy: PhantomData<fn() -> L>,
+}
+
+impl<T, L> MaybeLegacy<T, L> {
+    pub const MAGIC_PREFIX: u32 = u32::MAX;
+
+    #[must_use]
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            _legacy: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, L> From<T> for MaybeLegacy<T, L> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T, L> Deref for MaybeLegacy<T, L> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T, L> DerefMut for MaybeLegacy<T, L> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T, L> BorshSerialize for MaybeLegacy<T, L>
+where
+    T: BorshSerialize,
+{
+    #[inline]
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        Self::MAGIC_PREFIX.serialize(writer)?;
+        self.value.serialize(writer)
+    }
+}
+
+impl<T, L> BorshDeserialize for MaybeLegacy<T, L>
+where
+    T: BorshDeserialize,
+    L: BorshDeserialize + Into<T>,
+{
+    #[inline]
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let prefix = u32::deserialize_reader(reader)?;
+        let value = if prefix == Self::MAGIC_PREFIX {
+            T::deserialize_reader(reader)?
+        } else {
+            // put back already consumed bytes of the legacy value
+            L::deserialize_reader(&mut prefix.to_le_bytes().as_slice().chain(reader))?.into()
+        };
+        Ok(Self::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::borsh;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+    #[borsh(crate = "::near_sdk::borsh")]
+    struct V0 {
+        items: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+    #[borsh(crate = "::near_sdk::borsh")]
+    struct V1 {
+        items: Vec<u8>,
+        flag: bool,
+    }
+
+    impl From<V0> for V1 {
+        fn from(V0 { items }: V0) -> Self {
+            Self { items, flag: false }
+        }
+    }
+
+    #[test]
+    fn migrates_legacy() {
+        let legacy = borsh::to_vec(&(V0 { items: vec![1, 2] }, 7u32)).unwrap();
+        let (migrated, rest): (MaybeLegacy<V1, V0>, u32) = borsh::from_slice(&legacy).unwrap();
+        assert_eq!(
+            *migrated,
+            V1 {
+                items: vec![1, 2],
+                flag: false
+            }
+        );
+        assert_eq!(rest, 7);
+
+        let serialized = borsh::to_vec(&migrated).unwrap();
+        let latest: MaybeLegacy<V1, V0> = borsh::from_slice(&serialized).unwrap();
+        assert_eq!(latest.into_inner(), migrated.into_inner());
+    }
+}
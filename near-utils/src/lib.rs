@@ -1,10 +1,11 @@
 mod cache;
 mod gas;
+mod legacy;
 mod lock;
 mod panic;
 mod prefix;
 
-pub use self::{cache::*, gas::*, lock::*, panic::*, prefix::*};
+pub use self::{cache::*, gas::*, legacy::*, lock::*, panic::*, prefix::*};
 
 #[macro_export]
 macro_rules! method_name {
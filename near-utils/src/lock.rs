@@ -106,6 +106,41 @@ impl<T> Lock<T> {
         self.locked = false;
         &mut self.value
     }
+
+    /// Returns inner value regardless of whether it's locked or not
+    #[inline]
+    pub const fn as_inner_unchecked(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns inner value regardless of whether it's locked or not
+    #[inline]
+    pub fn as_inner_unchecked_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    #[inline]
+    pub fn into_inner_unchecked(self) -> T {
+        self.value
+    }
+
+    /// Returns inner value if it's unlocked or `force` is set
+    #[inline]
+    pub const fn get_maybe_forced(&self, force: bool) -> Option<&T> {
+        if self.locked && !force {
+            return None;
+        }
+        Some(&self.value)
+    }
+
+    /// Returns inner value if it's unlocked or `force` is set
+    #[inline]
+    pub fn get_mut_maybe_forced(&mut self, force: bool) -> Option<&mut T> {
+        if self.locked && !force {
+            return None;
+        }
+        Some(&mut self.value)
+    }
 }
 
 impl<T> From<T> for Lock<T> {
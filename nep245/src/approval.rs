@@ -0,0 +1,67 @@
+use near_sdk::{ext_contract, json_types::U128, near, AccountId, Promise, PromiseOrValue};
+
+use super::TokenId;
+
+/// Approval for an account to transfer given amount of owner's tokens
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Approval {
+    pub approval_id: u64,
+    /// Remaining amount allowed to be transferred
+    pub amount: U128,
+}
+
+#[ext_contract(ext_mt_approval)]
+pub trait MultiTokenApproval {
+    /// Approve `account_id` to transfer `amounts` of `token_ids` on behalf
+    /// of the predecessor, replacing existing approvals for these tokens.
+    /// If `msg` is given, `mt_on_approve()` is called on `account_id`.
+    ///
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn mt_approve(
+        &mut self,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise>;
+
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn mt_revoke(&mut self, token_ids: Vec<TokenId>, account_id: AccountId);
+
+    /// NOTE: MUST attach 1 yⓃ for security purposes.
+    fn mt_revoke_all(&mut self, token_ids: Vec<TokenId>);
+
+    /// Whether `approved_account_id` is allowed to transfer `amounts` of
+    /// `token_ids` owned by `owner_id`. Unlike for NFTs, a multi-token can
+    /// have many owners, so `owner_id` is required here.
+    fn mt_is_approved(
+        &self,
+        owner_id: AccountId,
+        token_ids: Vec<TokenId>,
+        approved_account_id: AccountId,
+        amounts: Vec<U128>,
+        approval_ids: Option<Vec<u64>>,
+    ) -> bool;
+
+    /// Returns current approval, which is needed to get `approval_id`
+    /// for transfers
+    fn mt_approval_of(
+        &self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        approved_account_id: AccountId,
+    ) -> Option<Approval>;
+}
+
+#[ext_contract(ext_mt_approval_receiver)]
+pub trait MultiTokenApprovalReceiver {
+    fn mt_on_approve(
+        &mut self,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        owner_id: AccountId,
+        approval_ids: Vec<u64>,
+        msg: String,
+    ) -> PromiseOrValue<String>;
+}
@@ -0,0 +1,19 @@
+use near_sdk::{ext_contract, json_types::U128, AccountId};
+
+use super::Token;
+
+#[ext_contract(ext_mt_enumeration)]
+pub trait MultiTokenEnumeration {
+    /// Get a list of all tokens with non-zero total supply.
+    /// Implementations may cap `limit` and apply a default one.
+    fn mt_tokens(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<Token>;
+
+    /// Get a list of all tokens with non-zero balance of `account_id`.
+    /// Implementations may cap `limit` and apply a default one.
+    fn mt_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<Token>;
+}
@@ -1,5 +1,8 @@
+pub mod approval;
 mod core;
+pub mod enumeration;
 mod events;
+pub mod metadata;
 pub mod receiver;
 pub mod resolver;
 mod token;
@@ -0,0 +1,74 @@
+use near_sdk::{ext_contract, json_types::Base64VecU8, near};
+
+use super::TokenId;
+
+pub const MT_METADATA_SPEC: &str = "mt-1.0.0";
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MTContractMetadata {
+    pub spec: String,
+    pub name: String,
+}
+
+/// Metadata shared by all tokens with the same `id`
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MTBaseTokenMetadata {
+    pub name: String,
+    pub id: String,
+    pub symbol: Option<String>,
+    /// Data URL
+    pub icon: Option<String>,
+    pub decimals: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub copies: Option<u64>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// Metadata specific to a single token
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MTTokenMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub issued_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub starts_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub extra: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MTTokenMetadataAll {
+    pub base: MTBaseTokenMetadata,
+    pub token: MTTokenMetadata,
+}
+
+#[ext_contract(ext_mt_metadata)]
+pub trait MultiTokenMetadataProvider {
+    fn mt_metadata_contract(&self) -> MTContractMetadata;
+
+    fn mt_metadata_token_all(&self, token_ids: Vec<TokenId>) -> Vec<Option<MTTokenMetadataAll>>;
+
+    fn mt_metadata_token_by_token_id(
+        &self,
+        token_ids: Vec<TokenId>,
+    ) -> Vec<Option<MTTokenMetadata>>;
+
+    fn mt_metadata_base_by_token_id(
+        &self,
+        token_ids: Vec<TokenId>,
+    ) -> Vec<Option<MTBaseTokenMetadata>>;
+
+    fn mt_metadata_base_by_metadata_id(
+        &self,
+        base_metadata_ids: Vec<String>,
+    ) -> Vec<Option<MTBaseTokenMetadata>>;
+}
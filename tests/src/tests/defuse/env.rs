@@ -8,7 +8,7 @@ use defuse::{
         config::{DefuseConfig, RolesConfig},
         Role,
     },
-    core::fees::{FeesConfig, Pips},
+    core::fees::{FeeMode, FeesConfig, Pips},
     tokens::DepositMessage,
 };
 use defuse_poa_factory::contract::Role as POAFactoryRole;
@@ -17,7 +17,7 @@ use near_workspaces::{Account, Contract};
 
 use crate::{
     tests::poa::factory::PoAFactoryExt,
-    utils::{ft::FtExt, wnear::WNearExt, Sandbox},
+    utils::{ft::FtExt, storage_management::StorageManagementExt, wnear::WNearExt, Sandbox},
 };
 
 use super::{accounts::AccountManagerExt, tokens::nep141::DefuseFtReceiver, DefuseExt};
@@ -49,6 +49,10 @@ impl Env {
         Self::builder().build().await
     }
 
+    pub async fn skip_blocks(&self, num_blocks: u64) {
+        self.sandbox.skip_blocks(num_blocks).await;
+    }
+
     pub async fn ft_storage_deposit(
         &self,
         token: &AccountId,
@@ -115,6 +119,8 @@ impl Deref for Env {
 pub struct EnvBuilder {
     fee: Pips,
     fee_collector: Option<AccountId>,
+    referral_fee: Pips,
+    fee_mode: FeeMode,
 
     // roles
     roles: RolesConfig,
@@ -133,6 +139,16 @@ impl EnvBuilder {
         self
     }
 
+    pub fn referral_fee(mut self, referral_fee: Pips) -> Self {
+        self.referral_fee = referral_fee;
+        self
+    }
+
+    pub fn fee_mode(mut self, fee_mode: FeeMode) -> Self {
+        self.fee_mode = fee_mode;
+        self
+    }
+
     pub fn super_admin(mut self, super_admin: AccountId) -> Self {
         self.roles.super_admins.insert(super_admin);
         self
@@ -206,6 +222,9 @@ impl EnvBuilder {
                         fees: FeesConfig {
                             fee: self.fee,
                             fee_collector: self.fee_collector.unwrap_or(root.id().clone()),
+                            fee_collector_shares: Default::default(),
+                            referral_fee: self.referral_fee,
+                            fee_mode: self.fee_mode,
                         },
                         roles: self.roles,
                     },
@@ -294,6 +313,16 @@ impl EnvBuilder {
             .unwrap();
         }
 
+        for user in [&s.user1, &s.user2, &s.user3] {
+            s.storage_deposit(
+                s.defuse.id(),
+                Some(user.id()),
+                NearToken::from_millinear(100),
+            )
+            .await
+            .unwrap();
+        }
+
         // NOTE: near_workspaces uses the same signer all subaccounts
         s.user1
             .add_public_key(
@@ -0,0 +1,87 @@
+use defuse::core::{
+    intents::{
+        tokens::{BatchTransfer, Transfer},
+        DefuseIntents,
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+#[tokio::test]
+async fn test_batch_transfer() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    env.defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [BatchTransfer {
+                    transfers: [(env.user2.id(), 300), (env.user3.id(), 700)]
+                        .into_iter()
+                        .map(|(receiver_id, amount)| Transfer {
+                            receiver_id: receiver_id.clone(),
+                            tokens: TokenAmounts::new(
+                                [(ft1.clone(), amount)].into_iter().collect(),
+                            ),
+                            memo: Some("payroll".to_string()),
+                        })
+                        .collect(),
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap();
+
+    for (account_id, balance) in [
+        (env.user1.id(), 0),
+        (env.user2.id(), 300),
+        (env.user3.id(), 700),
+    ] {
+        assert_eq!(
+            env.defuse
+                .mt_balance_of(account_id, &ft1.to_string())
+                .await
+                .unwrap(),
+            balance
+        );
+    }
+
+    // self-transfer legs are rejected
+    env.defuse
+        .execute_intents([env.user2.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [BatchTransfer {
+                    transfers: vec![Transfer {
+                        receiver_id: env.user2.id().clone(),
+                        tokens: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+                        memo: None,
+                    }],
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap_err();
+}
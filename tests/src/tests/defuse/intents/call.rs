@@ -0,0 +1,142 @@
+use defuse::core::{
+    intents::{tokens::Call, DefuseIntents},
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use near_sdk::Gas;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+#[tokio::test]
+async fn test_call_deposit_back() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // deliver tokens to verifying contract itself,
+    // so that it deposits them to user2
+    env.defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [Call {
+                    receiver_id: env.defuse.id().clone(),
+                    tokens: TokenAmounts::new([(ft1.clone(), 1000)].into_iter().collect()),
+                    msg: env.user2.id().to_string(),
+                    memo: None,
+                    gas: None,
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user2.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1000
+    );
+}
+
+#[tokio::test]
+async fn test_call_refund() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // receiver fails to parse msg, so that tokens get refunded
+    env.defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [Call {
+                    receiver_id: env.defuse.id().clone(),
+                    tokens: TokenAmounts::new([(ft1.clone(), 1000)].into_iter().collect()),
+                    msg: "{invalid".to_string(),
+                    memo: None,
+                    gas: None,
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1000
+    );
+}
+
+#[tokio::test]
+async fn test_call_gas_limit() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // leave no room for the refund callback
+    env.defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [Call {
+                    receiver_id: env.defuse.id().clone(),
+                    tokens: TokenAmounts::new([(ft1.clone(), 1000)].into_iter().collect()),
+                    msg: env.user2.id().to_string(),
+                    memo: None,
+                    gas: Some(Call::MAX_GAS.saturating_add(Gas::from_tgas(1))),
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1000
+    );
+}
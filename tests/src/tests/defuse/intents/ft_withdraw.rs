@@ -3,7 +3,7 @@ use std::time::Duration;
 use defuse::{
     contract::config::{DefuseConfig, RolesConfig},
     core::{
-        fees::{FeesConfig, Pips},
+        fees::{FeeMode, FeesConfig, Pips},
         intents::{tokens::FtWithdraw, DefuseIntents},
         tokens::TokenId,
         Deadline,
@@ -189,6 +189,9 @@ async fn test_ft_withdraw_intent_msg() {
                 fees: FeesConfig {
                     fee: Pips::ZERO,
                     fee_collector: env.id().clone(),
+                    fee_collector_shares: Default::default(),
+                    referral_fee: Pips::ZERO,
+                    fee_mode: FeeMode::TokenIn,
                 },
                 roles: RolesConfig::default(),
             },
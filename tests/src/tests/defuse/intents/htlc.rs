@@ -0,0 +1,110 @@
+use defuse::core::{
+    intents::{
+        htlc::{HtlcClaim, HtlcLock},
+        DefuseIntents,
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use near_sdk::env;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+#[tokio::test]
+async fn test_htlc_claim() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    for (account_id, amount) in [(env.user1.id(), 1000), (env.user3.id(), 100)] {
+        env.defuse_ft_mint(&env.ft1, amount, account_id)
+            .await
+            .unwrap();
+    }
+
+    let preimage: [u8; 32] = thread_rng().gen();
+    let hash_lock = env::sha256_array(&preimage);
+
+    // escrows of different senders don't collide even with the same hash lock
+    for (sender, amount) in [(&env.user3, 100), (&env.user1, 1000)] {
+        env.defuse
+            .execute_intents([sender.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [HtlcLock {
+                        hash_lock,
+                        receiver_id: env.user2.id().clone(),
+                        tokens: TokenAmounts::new([(ft1.clone(), amount)].into_iter().collect()),
+                        deadline: Deadline::MAX,
+                        memo: None,
+                    }
+                    .into()]
+                    .into(),
+                },
+            )])
+            .await
+            .unwrap();
+    }
+
+    // locked in escrow
+    for account_id in [env.user1.id(), env.user2.id(), env.user3.id()] {
+        assert_eq!(
+            env.defuse
+                .mt_balance_of(account_id, &ft1.to_string())
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    // only receiver can claim
+    env.defuse
+        .execute_intents([env.user3.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [HtlcClaim {
+                    sender_id: env.user1.id().clone(),
+                    preimage: preimage.to_vec(),
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap_err();
+
+    env.defuse
+        .execute_intents([env.user2.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [HtlcClaim {
+                    sender_id: env.user1.id().clone(),
+                    preimage: preimage.to_vec(),
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user2.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1000
+    );
+}
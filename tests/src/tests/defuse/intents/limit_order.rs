@@ -0,0 +1,240 @@
+use defuse::core::{
+    crypto::{Payload, PublicKey},
+    fees::FeeMode,
+    intents::{
+        account::{AddPublicKey, RemovePublicKey},
+        limit_order::{FillLimitOrder, LimitOrder, LimitOrderState},
+        token_diff::{TokenDeltas, TokenDiff},
+        DefuseIntents, Intent,
+    },
+    tokens::TokenId,
+    Deadline,
+};
+use near_sdk::json_types::{Base58CryptoHash, U128};
+use near_workspaces::{
+    types::{KeyType, SecretKey},
+    Account,
+};
+use rand::{thread_rng, Rng};
+use serde_json::json;
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+#[tokio::test]
+async fn test_limit_order_partial_fills() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+    let tokens = [ft1.to_string(), ft2.to_string()];
+
+    env.defuse_ft_mint(&env.ft1, 100, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft2, 200, env.user2.id())
+        .await
+        .unwrap();
+
+    let place = env.user1.sign_defuse_message(
+        env.defuse.id(),
+        thread_rng().gen(),
+        Deadline::MAX,
+        DefuseIntents {
+            intents: [LimitOrder {
+                token_in: ft1.clone(),
+                amount_in: U128(100),
+                token_out: ft2.clone(),
+                amount_out: U128(200),
+                deadline: Deadline::MAX,
+                memo: None,
+                fee_mode: FeeMode::TokenIn,
+            }
+            .into()]
+            .into(),
+        },
+    );
+    let order_hash = place.hash();
+    env.defuse.execute_intents([place]).await.unwrap();
+
+    for (amount_in, filled) in [(40, Some(40)), (60, None)] {
+        env.defuse
+            .execute_intents([env.user2.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [
+                        FillLimitOrder {
+                            maker_id: env.user1.id().clone(),
+                            order_hash,
+                            amount_in: U128(amount_in),
+                        }
+                        .into(),
+                        TokenDiff {
+                            diff: TokenDeltas::default()
+                                .with_add_deltas([
+                                    (ft1.clone(), amount_in.try_into().unwrap()),
+                                    (ft2.clone(), -i128::try_from(amount_in * 2).unwrap()),
+                                ])
+                                .unwrap(),
+                            memo: None,
+                            referral: None,
+                            fee_mode: FeeMode::TokenIn,
+                        }
+                        .into(),
+                    ]
+                    .into(),
+                },
+            )])
+            .await
+            .unwrap();
+
+        let order: Option<LimitOrderState> = env
+            .defuse
+            .view("limit_order")
+            .args_json(json!({
+                "maker_id": env.user1.id(),
+                "order_hash": Base58CryptoHash::from(order_hash),
+            }))
+            .await
+            .unwrap()
+            .json()
+            .unwrap();
+        assert_eq!(order.map(|o| o.filled.0), filled);
+    }
+
+    assert_eq!(
+        env.defuse
+            .mt_batch_balance_of(env.user1.id(), &tokens)
+            .await
+            .unwrap(),
+        [0, 200]
+    );
+    assert_eq!(
+        env.defuse
+            .mt_batch_balance_of(env.user2.id(), &tokens)
+            .await
+            .unwrap(),
+        [100, 0]
+    );
+}
+
+#[tokio::test]
+async fn test_limit_order_removed_key() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    env.defuse_ft_mint(&env.ft1, 100, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft2, 200, env.user2.id())
+        .await
+        .unwrap();
+
+    // HACK: near_worspaces does not expose near_crypto API
+    let public_key_of = |signer: &Account| -> PublicKey {
+        signer
+            .secret_key()
+            .public_key()
+            .to_string()
+            .parse()
+            .unwrap()
+    };
+    let sign = |signer: &Account, intent: Intent| {
+        signer.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [intent].into(),
+            },
+        )
+    };
+
+    // user1 adds a key, which later leaks
+    let mut leaked = env.user1.clone();
+    leaked.set_secret_key(SecretKey::from_random(KeyType::ED25519));
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            AddPublicKey {
+                public_key: public_key_of(&leaked),
+                scope: None,
+            }
+            .into(),
+        )])
+        .await
+        .unwrap();
+
+    let place = sign(
+        &leaked,
+        LimitOrder {
+            token_in: ft1.clone(),
+            amount_in: U128(100),
+            token_out: ft2.clone(),
+            amount_out: U128(1),
+            deadline: Deadline::MAX,
+            memo: None,
+            fee_mode: FeeMode::TokenIn,
+        }
+        .into(),
+    );
+    let order_hash = place.hash();
+    env.defuse.execute_intents([place]).await.unwrap();
+
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            RemovePublicKey {
+                public_key: public_key_of(&leaked),
+            }
+            .into(),
+        )])
+        .await
+        .unwrap();
+
+    // the order can't be filled once the key it was signed with is removed
+    env.defuse
+        .execute_intents([env.user2.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [
+                    FillLimitOrder {
+                        maker_id: env.user1.id().clone(),
+                        order_hash,
+                        amount_in: U128(100),
+                    }
+                    .into(),
+                    TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([(ft1.clone(), 100), (ft2.clone(), -1)])
+                            .unwrap(),
+                        memo: None,
+                        referral: None,
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into(),
+                ]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        100
+    );
+}
@@ -0,0 +1,403 @@
+use std::time::Duration;
+
+use defuse::{
+    contract::Role,
+    core::{
+        accounts::Lockdown,
+        crypto::PublicKey,
+        intents::{
+            account::{FreezeAccount, LockAccount},
+            tokens::Transfer,
+            DefuseIntents, Intent,
+        },
+        tokens::{TokenAmounts, TokenId},
+        Deadline,
+    },
+};
+use near_sdk::{AccountId, NearToken};
+use near_workspaces::{
+    types::{KeyType, SecretKey},
+    Account,
+};
+use rand::{thread_rng, Rng};
+use serde_json::json;
+
+use crate::{
+    tests::defuse::{accounts::AccountManagerExt, env::Env, DefuseSigner},
+    utils::{acl::AclExt, mt::MtExt},
+};
+
+use super::ExecuteIntentsExt;
+
+async fn set_account_locked(
+    locker: &Account,
+    defuse_id: &AccountId,
+    account_id: &AccountId,
+    locked: bool,
+) -> bool {
+    locker
+        .call(
+            defuse_id,
+            if locked {
+                "force_lock_account"
+            } else {
+                "force_unlock_account"
+            },
+        )
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "account_id": account_id,
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap()
+        .json()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_force_lock_account() {
+    let env = Env::builder().deployer_as_super_admin().build().await;
+
+    env.acl_grant_role(
+        env.defuse.id(),
+        Role::UnrestrictedAccountLocker,
+        env.user3.id(),
+    )
+    .await
+    .unwrap();
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft1, 1000, env.user2.id())
+        .await
+        .unwrap();
+
+    let transfer = |signer: &Account, receiver_id: &AccountId| {
+        signer.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [Transfer {
+                    receiver_id: receiver_id.clone(),
+                    tokens: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+                    memo: None,
+                }
+                .into()]
+                .into(),
+            },
+        )
+    };
+
+    // only accounts with the role can lock
+    env.user1
+        .call(env.defuse.id(), "force_lock_account")
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "account_id": env.user1.id(),
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap_err();
+
+    assert!(set_account_locked(&env.user3, env.defuse.id(), env.user1.id(), true).await);
+    // already locked
+    assert!(!set_account_locked(&env.user3, env.defuse.id(), env.user1.id(), true).await);
+    assert!(env
+        .defuse
+        .view("is_account_locked")
+        .args_json(json!({
+            "account_id": env.user1.id(),
+        }))
+        .await
+        .unwrap()
+        .json::<bool>()
+        .unwrap());
+
+    // locked account can't sign intents
+    env.defuse
+        .execute_intents([transfer(&env.user1, env.user2.id())])
+        .await
+        .unwrap_err();
+
+    // locked account can't transfer or withdraw tokens directly
+    env.user1
+        .mt_transfer(
+            env.defuse.id(),
+            env.user2.id(),
+            &ft1.to_string(),
+            100,
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+
+    // but it can still receive tokens
+    env.defuse
+        .execute_intents([transfer(&env.user2, env.user1.id())])
+        .await
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1100
+    );
+
+    assert!(set_account_locked(&env.user3, env.defuse.id(), env.user1.id(), false).await);
+    assert!(!set_account_locked(&env.user3, env.defuse.id(), env.user1.id(), false).await);
+
+    env.defuse
+        .execute_intents([transfer(&env.user1, env.user2.id())])
+        .await
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1000
+    );
+}
+
+#[tokio::test]
+async fn test_freeze_account_intent() {
+    let env = Env::builder().deployer_as_super_admin().build().await;
+
+    env.acl_grant_role(
+        env.defuse.id(),
+        Role::UnrestrictedAccountLocker,
+        env.user3.id(),
+    )
+    .await
+    .unwrap();
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft1, 1000, env.user2.id())
+        .await
+        .unwrap();
+
+    let sign = |signer: &Account, intents: Vec<Intent>| {
+        signer.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents { intents },
+        )
+    };
+    let transfer = |receiver_id: &AccountId| -> Intent {
+        Transfer {
+            receiver_id: receiver_id.clone(),
+            tokens: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+            memo: None,
+        }
+        .into()
+    };
+
+    // intents following the freeze in the same payload can't withdraw
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            [FreezeAccount {}.into(), transfer(env.user2.id())].into(),
+        )])
+        .await
+        .unwrap_err();
+
+    env.defuse
+        .execute_intents([sign(&env.user1, [FreezeAccount {}.into()].into())])
+        .await
+        .unwrap();
+    assert!(env
+        .defuse
+        .view("is_account_locked")
+        .args_json(json!({
+            "account_id": env.user1.id(),
+        }))
+        .await
+        .unwrap()
+        .json::<bool>()
+        .unwrap());
+
+    // frozen account can't sign intents
+    env.defuse
+        .execute_intents([sign(&env.user1, [transfer(env.user2.id())].into())])
+        .await
+        .unwrap_err();
+
+    // but it can still receive tokens
+    env.defuse
+        .execute_intents([sign(&env.user2, [transfer(env.user1.id())].into())])
+        .await
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1100
+    );
+
+    // only accounts with the role can unfreeze it
+    assert!(set_account_locked(&env.user3, env.defuse.id(), env.user1.id(), false).await);
+    env.defuse
+        .execute_intents([sign(&env.user1, [transfer(env.user2.id())].into())])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_lock_account_intent() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // HACK: near_worspaces does not expose near_crypto API
+    let public_key_of = |signer: &Account| -> PublicKey {
+        signer
+            .secret_key()
+            .public_key()
+            .to_string()
+            .parse()
+            .unwrap()
+    };
+
+    // user1 signs with the same account, but a different session key
+    let mut session = env.user1.clone();
+    session.set_secret_key(SecretKey::from_random(KeyType::ED25519));
+    env.user1
+        .add_public_key(env.defuse.id(), public_key_of(&session))
+        .await
+        .unwrap();
+
+    let sign = |signer: &Account, intent: Intent| {
+        signer.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [intent].into(),
+            },
+        )
+    };
+    let transfer = || -> Intent {
+        Transfer {
+            receiver_id: env.user2.id().clone(),
+            tokens: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+            memo: None,
+        }
+        .into()
+    };
+    let lock_account = |recovery_key: PublicKey, timeout: Duration| -> Intent {
+        LockAccount {
+            recovery_keys: [recovery_key].into(),
+            expires_at: Deadline::timeout(timeout),
+        }
+        .into()
+    };
+
+    // recently added key can't be used for recovery
+    env.defuse
+        .execute_intents([sign(
+            &session,
+            lock_account(public_key_of(&session), Duration::from_secs(60 * 60)),
+        )])
+        .await
+        .unwrap_err();
+
+    // roughly a second per block
+    env.skip_blocks(Lockdown::MIN_RECOVERY_KEY_AGE.as_secs() + 60)
+        .await;
+
+    env.defuse
+        .execute_intents([sign(&session, transfer())])
+        .await
+        .unwrap();
+
+    // recovery key must be registered for the account
+    env.defuse
+        .execute_intents([sign(
+            &session,
+            lock_account(public_key_of(&env.user2), Duration::from_secs(60 * 60)),
+        )])
+        .await
+        .unwrap_err();
+
+    // lockdown can't be too long
+    env.defuse
+        .execute_intents([sign(
+            &session,
+            lock_account(
+                public_key_of(&env.user1),
+                Lockdown::MAX_DURATION + Duration::from_secs(60 * 60),
+            ),
+        )])
+        .await
+        .unwrap_err();
+
+    // any key can lock the account
+    env.defuse
+        .execute_intents([sign(
+            &session,
+            lock_account(public_key_of(&env.user1), Duration::from_secs(60 * 60)),
+        )])
+        .await
+        .unwrap();
+
+    let lockdown: Lockdown = env
+        .defuse
+        .view("lockdown_of")
+        .args_json(json!({
+            "account_id": env.user1.id(),
+        }))
+        .await
+        .unwrap()
+        .json::<Option<Lockdown>>()
+        .unwrap()
+        .unwrap();
+    assert_eq!(lockdown.recovery_keys, [public_key_of(&env.user1)].into());
+
+    // active lockdown can't be extended
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            lock_account(public_key_of(&env.user1), Duration::from_secs(2 * 60 * 60)),
+        )])
+        .await
+        .unwrap_err();
+
+    // session key is disabled during lockdown
+    env.defuse
+        .execute_intents([sign(&session, transfer())])
+        .await
+        .unwrap_err();
+
+    // while the recovery key still works
+    env.defuse
+        .execute_intents([sign(&env.user1, transfer())])
+        .await
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        800
+    );
+}
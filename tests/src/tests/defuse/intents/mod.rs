@@ -1,22 +1,36 @@
 use defuse::{
     core::{
-        intents::{tokens::Transfer, DefuseIntents},
-        payload::multi::MultiPayload,
+        crypto::{Payload, PublicKey},
+        intents::{
+            tokens::{FtWithdraw, Transfer, Withdrawal},
+            DefuseIntents,
+        },
+        payload::multi::{MultiPayload, UnsignedMultiPayload},
         tokens::{TokenAmounts, TokenId},
         Deadline,
     },
-    intents::SimulationOutput,
+    intents::{SimulationOutput, UnsignedPayload},
 };
-use near_sdk::{AccountId, AccountIdRef};
+use near_sdk::{json_types::U128, AccountId, AccountIdRef, NearToken};
+use near_workspaces::Account;
 use rand::{thread_rng, Rng};
 use serde_json::json;
 
-use crate::utils::mt::MtExt;
+use crate::utils::{mt::MtExt, storage_management::StorageManagementExt};
 
 use super::{accounts::AccountManagerExt, env::Env, DefuseSigner};
 
+mod batch_transfer;
+mod call;
 mod ft_withdraw;
+mod htlc;
+mod limit_order;
+mod lock;
+mod multisig;
+mod recovery;
 mod relayers;
+mod require;
+mod scope;
 mod token_diff;
 
 pub trait ExecuteIntentsExt: AccountManagerExt {
@@ -183,6 +197,286 @@ async fn test_simulate_is_view_method() {
     );
 }
 
+#[tokio::test]
+async fn test_simulate_outcomes() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    let output = env
+        .defuse
+        .simulate_intents([
+            env.user1.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [Transfer {
+                        receiver_id: env.user2.id().clone(),
+                        tokens: TokenAmounts::new([(ft1.clone(), 300)].into_iter().collect()),
+                        memo: None,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+            env.user1.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [FtWithdraw {
+                        token: env.ft1.clone(),
+                        receiver_id: env.user1.id().clone(),
+                        amount: U128(100),
+                        memo: None,
+                        msg: None,
+                        storage_deposit: None,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(output.outcomes.len(), 2);
+
+    let transfer = &output.outcomes[0].event.event;
+    assert_eq!(
+        transfer.balance_deltas[env.user1.id()].balance_of(&ft1),
+        -300
+    );
+    assert_eq!(
+        transfer.balance_deltas[env.user2.id()].balance_of(&ft1),
+        300
+    );
+    assert_eq!(transfer.events.len(), 1);
+    assert!(transfer.withdrawals.is_empty());
+
+    let withdraw = &output.outcomes[1].event.event;
+    assert_eq!(withdraw.balance_deltas.len(), 1);
+    assert_eq!(
+        withdraw.balance_deltas[env.user1.id()].balance_of(&ft1),
+        -100
+    );
+    assert!(matches!(
+        withdraw.withdrawals.as_slice(),
+        [Withdrawal::Ft(w)] if w.amount.0 == 100
+    ));
+}
+
+#[tokio::test]
+async fn test_simulate_with_overrides() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    let args = json!({
+        "signed": [env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [Transfer {
+                    receiver_id: env.user2.id().clone(),
+                    tokens: TokenAmounts::new([(ft1.clone(), 300)].into_iter().collect()),
+                    memo: None,
+                }
+                .into()]
+                .into(),
+            },
+        )],
+        "overrides": {
+            "balances": {
+                env.user1.id().as_str(): {
+                    ft1.to_string(): "500",
+                },
+            },
+        },
+    });
+    let output: SimulationOutput = env
+        .defuse
+        .view("simulate_intents_with_overrides")
+        .args_json(args)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(output.invariant_violated.is_none());
+
+    // overridden balances are not reported as deltas
+    let transfer = &output.outcomes[0].event.event;
+    assert_eq!(
+        transfer.balance_deltas[env.user1.id()].balance_of(&ft1),
+        -300
+    );
+    assert_eq!(
+        transfer.balance_deltas[env.user2.id()].balance_of(&ft1),
+        300
+    );
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_simulate_unsigned_intents() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // HACK: near_worspaces does not expose near_crypto API
+    let public_key_of = |signer: &Account| -> PublicKey {
+        signer
+            .secret_key()
+            .public_key()
+            .to_string()
+            .parse()
+            .unwrap()
+    };
+    let intents = DefuseIntents {
+        intents: [Transfer {
+            receiver_id: env.user2.id().clone(),
+            tokens: TokenAmounts::new([(ft1.clone(), 300)].into_iter().collect()),
+            memo: None,
+        }
+        .into()]
+        .into(),
+    };
+    // strip signature from the payload signed under NEP-413
+    let unsigned = |signed: &MultiPayload, public_key| UnsignedPayload {
+        payload: UnsignedMultiPayload::from(signed.clone()),
+        public_keys: [public_key].into(),
+    };
+    let simulate = |payloads: Vec<UnsignedPayload>| {
+        let view = env
+            .defuse
+            .view("simulate_unsigned_intents")
+            .args_json(json!({
+                "payloads": payloads,
+                "collect_errors": true,
+            }));
+        async move { view.await.unwrap().json::<SimulationOutput>().unwrap() }
+    };
+
+    let signed = env.user1.sign_defuse_message(
+        env.defuse.id(),
+        thread_rng().gen(),
+        Deadline::MAX,
+        intents.clone(),
+    );
+    let output = simulate(vec![unsigned(&signed, public_key_of(&env.user1))]).await;
+    assert!(output.errors.is_empty());
+    assert!(output.invariant_violated.is_none());
+    assert_eq!(
+        output.outcomes[0].event.event.balance_deltas[env.user1.id()].balance_of(&ft1),
+        -300
+    );
+    // intent hash matches the one of the signed payload
+    assert_eq!(output.outcomes[0].intent_hash, signed.hash());
+
+    // public keys not added to the signer are rejected
+    let output = simulate(vec![unsigned(
+        &env.user1
+            .sign_defuse_message(env.defuse.id(), thread_rng().gen(), Deadline::MAX, intents),
+        public_key_of(&env.user2),
+    )])
+    .await;
+    assert_eq!(output.errors.len(), 1);
+    assert!(output.outcomes.is_empty());
+
+    // nonce is not committed, so it can be reused within the same simulation
+    let output = simulate(vec![
+        unsigned(&signed, public_key_of(&env.user1)),
+        unsigned(&signed, public_key_of(&env.user1)),
+    ])
+    .await;
+    assert!(output.errors.is_empty());
+    assert_eq!(output.outcomes.len(), 2);
+
+    // and the same payload can be signed afterwards
+    env.defuse.execute_intents([signed]).await.unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user2.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        300
+    );
+}
+
+#[tokio::test]
+async fn test_simulate_reports_failed_payloads() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    let sign = |verifying_contract: &AccountId| {
+        env.user1.sign_defuse_message(
+            verifying_contract,
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [Transfer {
+                    receiver_id: env.user2.id().clone(),
+                    tokens: TokenAmounts::new([(ft1.clone(), 300)].into_iter().collect()),
+                    memo: None,
+                }
+                .into()]
+                .into(),
+            },
+        )
+    };
+
+    // simulation fails on the first failed payload by default
+    env.defuse
+        .simulate_intents([sign(env.user2.id()), sign(env.defuse.id())])
+        .await
+        .unwrap_err();
+
+    let output: SimulationOutput = env
+        .defuse
+        .view("simulate_intents_with_overrides")
+        .args_json(json!({
+            "signed": [sign(env.user2.id()), sign(env.defuse.id())],
+            "overrides": {},
+            "collect_errors": true,
+        }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+
+    // failed payload is reported, while the rest are still simulated
+    assert_eq!(output.errors.len(), 1);
+    assert_eq!(output.errors[0].index, 0);
+    assert!(output.invariant_violated.is_none());
+    assert_eq!(output.outcomes.len(), 1);
+    assert_eq!(
+        output.outcomes[0].event.event.balance_deltas[env.user1.id()].balance_of(&ft1),
+        -300
+    );
+    assert!(output.into_result().is_err());
+}
+
 #[tokio::test]
 async fn test_webauthn() {
     const SIGNER_ID: &AccountIdRef =
@@ -192,6 +486,15 @@ async fn test_webauthn() {
 
     let ft1 = TokenId::Nep141(env.ft1.clone());
 
+    env.user1
+        .storage_deposit(
+            env.defuse.id(),
+            Some(&SIGNER_ID.to_owned()),
+            NearToken::from_millinear(100),
+        )
+        .await
+        .unwrap();
+
     // deposit
     env.defuse_ft_mint(&env.ft1, 2000, &SIGNER_ID.to_owned())
         .await
@@ -0,0 +1,162 @@
+use defuse::core::{
+    crypto::PublicKey,
+    intents::{
+        account::{RemovePublicKey, SetThreshold},
+        tokens::Transfer,
+        DefuseIntents, Intent,
+    },
+    payload::multisig::MultiSigPayload,
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use near_sdk::NearToken;
+use near_workspaces::{
+    types::{KeyType, SecretKey},
+    Account,
+};
+use rand::{thread_rng, Rng};
+use serde_json::json;
+
+use crate::{
+    tests::defuse::{accounts::AccountManagerExt, env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+#[tokio::test]
+async fn test_multisig_threshold() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // HACK: near_worspaces does not expose near_crypto API
+    let public_key_of = |signer: &Account| -> PublicKey {
+        signer
+            .secret_key()
+            .public_key()
+            .to_string()
+            .parse()
+            .unwrap()
+    };
+
+    // user1 gets two more keys, so it has 3 in total
+    let mut key2 = env.user1.clone();
+    key2.set_secret_key(SecretKey::from_random(KeyType::ED25519));
+    let mut key3 = env.user1.clone();
+    key3.set_secret_key(SecretKey::from_random(KeyType::ED25519));
+    for key in [&key2, &key3] {
+        env.user1
+            .add_public_key(env.defuse.id(), public_key_of(key))
+            .await
+            .unwrap();
+    }
+
+    env.user1
+        .call(env.defuse.id(), "set_threshold")
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "threshold": 2,
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    // all signers sign the same payload
+    let sign = |signers: &[&Account], intent: Intent| -> MultiSigPayload {
+        let nonce = thread_rng().gen();
+        MultiSigPayload::Multi(
+            signers
+                .iter()
+                .map(|signer| {
+                    signer.sign_defuse_message(
+                        env.defuse.id(),
+                        nonce,
+                        Deadline::MAX,
+                        DefuseIntents {
+                            intents: [intent.clone()].into(),
+                        },
+                    )
+                })
+                .collect(),
+        )
+    };
+    let defuse = &env.defuse;
+    let execute = |signed: MultiSigPayload| async move {
+        defuse
+            .call("execute_intents")
+            .args_json(json!({
+                "signed": [signed],
+            }))
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()
+            .map(|_| ())
+            .map_err(anyhow::Error::from)
+    };
+    let transfer = || -> Intent {
+        Transfer {
+            receiver_id: env.user2.id().clone(),
+            tokens: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+            memo: None,
+        }
+        .into()
+    };
+
+    // too few signatures
+    execute(sign(&[&key2], transfer())).await.unwrap_err();
+
+    // duplicate signatures of the same key are counted once
+    execute(sign(&[&key2, &key2], transfer()))
+        .await
+        .unwrap_err();
+
+    execute(sign(&[&key2, &key3], transfer())).await.unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user2.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        100
+    );
+
+    // threshold can't exceed the number of keys
+    execute(sign(
+        &[&env.user1, &key2],
+        SetThreshold { threshold: 4 }.into(),
+    ))
+    .await
+    .unwrap_err();
+
+    // one of the keys can be removed while the threshold is reachable
+    execute(sign(
+        &[&env.user1, &key2],
+        RemovePublicKey {
+            public_key: public_key_of(&key3),
+        }
+        .into(),
+    ))
+    .await
+    .unwrap();
+
+    // but the account can't lock itself out
+    execute(sign(
+        &[&env.user1, &key2],
+        RemovePublicKey {
+            public_key: public_key_of(&key2),
+        }
+        .into(),
+    ))
+    .await
+    .unwrap_err();
+    assert!(env
+        .defuse
+        .has_public_key(env.user1.id(), &public_key_of(&key2))
+        .await
+        .unwrap());
+}
@@ -0,0 +1,242 @@
+use defuse::core::{
+    accounts::{Guardians, Recovery},
+    crypto::PublicKey,
+    intents::{
+        account::{ApproveRecovery, CancelRecovery, SetGuardians},
+        tokens::Transfer,
+        DefuseIntents, Intent,
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use near_sdk::{AccountId, NearToken};
+use near_workspaces::{
+    types::{KeyType, SecretKey},
+    Account,
+};
+use rand::{thread_rng, Rng};
+use serde_json::json;
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+async fn finalize_recovery(
+    caller: &Account,
+    defuse_id: &AccountId,
+    account_id: &AccountId,
+) -> anyhow::Result<PublicKey> {
+    caller
+        .call(defuse_id, "finalize_recovery")
+        .args_json(json!({
+            "account_id": account_id,
+        }))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?
+        .json()
+        .map_err(Into::into)
+}
+
+#[tokio::test]
+async fn test_social_recovery() {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    let sign = |signer: &Account, intent: Intent| {
+        signer.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [intent].into(),
+            },
+        )
+    };
+    let transfer = || -> Intent {
+        Transfer {
+            receiver_id: env.user2.id().clone(),
+            tokens: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+            memo: None,
+        }
+        .into()
+    };
+    let recovery_of = || async {
+        env.defuse
+            .view("recovery_of")
+            .args_json(json!({
+                "account_id": env.user1.id(),
+            }))
+            .await
+            .unwrap()
+            .json::<Option<Recovery>>()
+            .unwrap()
+    };
+
+    // the owner lost the key and recovers the account with a new one
+    let mut recovered = env.user1.clone();
+    recovered.set_secret_key(SecretKey::from_random(KeyType::ED25519));
+    // HACK: near_worspaces does not expose near_crypto API
+    let new_public_key: PublicKey = recovered
+        .secret_key()
+        .public_key()
+        .to_string()
+        .parse()
+        .unwrap();
+    let approve = |guardian: &Account| {
+        sign(
+            guardian,
+            ApproveRecovery {
+                account_id: env.user1.id().clone(),
+                public_key: new_public_key,
+            }
+            .into(),
+        )
+    };
+
+    // guardians must not include the account itself
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            SetGuardians {
+                guardians: Guardians {
+                    guardians: [env.user1.id().clone(), env.user2.id().clone()].into(),
+                    threshold: 1,
+                    delay_secs: Guardians::MIN_DELAY_SECS,
+                },
+            }
+            .into(),
+        )])
+        .await
+        .unwrap_err();
+
+    // delay can't be too short
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            SetGuardians {
+                guardians: Guardians {
+                    guardians: [env.user2.id().clone(), env.user3.id().clone()].into(),
+                    threshold: 2,
+                    delay_secs: 0,
+                },
+            }
+            .into(),
+        )])
+        .await
+        .unwrap_err();
+
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            SetGuardians {
+                guardians: Guardians {
+                    guardians: [env.user2.id().clone(), env.user3.id().clone()].into(),
+                    threshold: 2,
+                    delay_secs: Guardians::MIN_DELAY_SECS,
+                },
+            }
+            .into(),
+        )])
+        .await
+        .unwrap();
+
+    // not a guardian
+    env.defuse
+        .execute_intents([approve(&env.user1)])
+        .await
+        .unwrap_err();
+
+    env.defuse
+        .execute_intents([approve(&env.user2)])
+        .await
+        .unwrap();
+    // not enough approvals
+    finalize_recovery(&env.user3, env.defuse.id(), env.user1.id())
+        .await
+        .unwrap_err();
+
+    // the owner can cancel the recovery
+    env.defuse
+        .execute_intents([sign(&env.user1, CancelRecovery {}.into())])
+        .await
+        .unwrap();
+    assert_eq!(recovery_of().await, None);
+
+    env.defuse
+        .execute_intents([approve(&env.user2), approve(&env.user3)])
+        .await
+        .unwrap();
+    assert_eq!(
+        recovery_of().await.unwrap().scheduled.unwrap().public_key,
+        new_public_key
+    );
+
+    // delay hasn't passed yet
+    finalize_recovery(&env.user3, env.defuse.id(), env.user1.id())
+        .await
+        .unwrap_err();
+
+    // roughly a second per block
+    env.skip_blocks(u64::from(Guardians::MIN_DELAY_SECS) + 60)
+        .await;
+
+    // anyone can finalize the recovery once the delay has passed
+    assert_eq!(
+        finalize_recovery(&env.user3, env.defuse.id(), env.user1.id())
+            .await
+            .unwrap(),
+        new_public_key
+    );
+    assert_eq!(recovery_of().await, None);
+
+    // old key was replaced with the new one
+    env.defuse
+        .execute_intents([sign(&env.user1, transfer())])
+        .await
+        .unwrap_err();
+    env.defuse
+        .execute_intents([sign(&recovered, transfer())])
+        .await
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        900
+    );
+
+    // direct calls are still allowed for NEAR accounts
+    env.user1
+        .call(env.defuse.id(), "set_guardians")
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "guardians": Guardians::default(),
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert!(env
+        .defuse
+        .view("guardians_of")
+        .args_json(json!({
+            "account_id": env.user1.id(),
+        }))
+        .await
+        .unwrap()
+        .json::<Option<Guardians>>()
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,71 @@
+use defuse::core::{
+    intents::{
+        require::{Condition, Require},
+        tokens::Transfer,
+        DefuseIntents,
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use near_sdk::json_types::U128;
+use rand::{thread_rng, Rng};
+use rstest::rstest;
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+#[rstest]
+#[case(1000, true)]
+#[case(1001, false)]
+#[tokio::test]
+async fn test_require_min_balance(#[case] min_balance: u128, #[case] ok: bool) {
+    let env = Env::new().await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    let result = env
+        .defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [
+                    Require {
+                        conditions: [Condition::MinBalance {
+                            account_id: env.user1.id().clone(),
+                            token_id: ft1.clone(),
+                            amount: U128(min_balance),
+                        }]
+                        .into(),
+                    }
+                    .into(),
+                    Transfer {
+                        receiver_id: env.user2.id().clone(),
+                        tokens: TokenAmounts::new([(ft1.clone(), 1000)].into_iter().collect()),
+                        memo: None,
+                    }
+                    .into(),
+                ]
+                .into(),
+            },
+        )])
+        .await;
+    assert_eq!(result.is_ok(), ok);
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user2.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        if ok { 1000 } else { 0 }
+    );
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use defuse::core::{
+    accounts::PublicKeyScope,
+    crypto::PublicKey,
+    fees::FeeMode,
+    intents::{
+        account::AddPublicKey,
+        limit_order::LimitOrder,
+        token_diff::{TokenDeltas, TokenDiff},
+        tokens::FtWithdraw,
+        DefuseIntents, Intent, IntentKind,
+    },
+    tokens::{TokenAmounts, TokenId},
+    Deadline,
+};
+use near_sdk::json_types::U128;
+use near_workspaces::{
+    types::{KeyType, SecretKey},
+    Account,
+};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    tests::defuse::{env::Env, DefuseSigner},
+    utils::mt::MtExt,
+};
+
+use super::ExecuteIntentsExt;
+
+#[tokio::test]
+async fn test_public_key_scope() {
+    let env = Env::new().await;
+
+    let [ft1, ft2, ft3] = [&env.ft1, &env.ft2, &env.ft3].map(|ft| TokenId::Nep141(ft.clone()));
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    for ft in [&env.ft2, &env.ft3] {
+        env.defuse_ft_mint(ft, 1000, env.user2.id()).await.unwrap();
+    }
+
+    // HACK: near_worspaces does not expose near_crypto API
+    let public_key_of = |signer: &Account| -> PublicKey {
+        signer
+            .secret_key()
+            .public_key()
+            .to_string()
+            .parse()
+            .unwrap()
+    };
+    let sign = |signer: &Account, intent: Intent| {
+        signer.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::MAX,
+            DefuseIntents {
+                intents: [intent].into(),
+            },
+        )
+    };
+    let token_diff = |deltas: [(&TokenId, i128); 2]| -> Intent {
+        TokenDiff {
+            diff: TokenDeltas::default()
+                .with_add_deltas(deltas.map(|(token_id, delta)| (token_id.clone(), delta)))
+                .unwrap(),
+            memo: None,
+            referral: None,
+            fee_mode: FeeMode::TokenIn,
+        }
+        .into()
+    };
+    let limit_order = |token_out: &TokenId, amount_in: u128| -> Intent {
+        LimitOrder {
+            token_in: ft1.clone(),
+            amount_in: U128(amount_in),
+            token_out: token_out.clone(),
+            amount_out: U128(amount_in),
+            deadline: Deadline::timeout(Duration::from_secs(60 * 60)),
+            memo: None,
+            fee_mode: FeeMode::TokenIn,
+        }
+        .into()
+    };
+
+    // user1 adds a browser key, which can only swap ft1 for ft2
+    let mut browser = env.user1.clone();
+    browser.set_secret_key(SecretKey::from_random(KeyType::ED25519));
+    env.defuse
+        .execute_intents([sign(
+            &env.user1,
+            AddPublicKey {
+                public_key: public_key_of(&browser),
+                scope: Some(PublicKeyScope {
+                    intents: [IntentKind::TokenDiff, IntentKind::LimitOrder].into(),
+                    tokens: Some([ft1.clone(), ft2.clone()].into()),
+                    max_amounts: TokenAmounts::new([(ft1.clone(), 100)].into_iter().collect()),
+                    expires_at: Deadline::timeout(Duration::from_secs(60 * 60)),
+                }),
+            }
+            .into(),
+        )])
+        .await
+        .unwrap();
+
+    // withdrawals are not allowed
+    env.defuse
+        .execute_intents([sign(
+            &browser,
+            FtWithdraw {
+                token: env.ft1.clone(),
+                receiver_id: env.user3.id().clone(),
+                amount: U128(100),
+                memo: None,
+                msg: None,
+                storage_deposit: None,
+            }
+            .into(),
+        )])
+        .await
+        .unwrap_err();
+
+    // the key can't escalate its own access
+    env.defuse
+        .execute_intents([sign(
+            &browser,
+            AddPublicKey {
+                public_key: public_key_of(&env.user3),
+                scope: None,
+            }
+            .into(),
+        )])
+        .await
+        .unwrap_err();
+
+    // other tokens are not allowed
+    env.defuse
+        .execute_intents([
+            sign(&browser, token_diff([(&ft1, -100), (&ft3, 100)])),
+            sign(&env.user2, token_diff([(&ft1, 100), (&ft3, -100)])),
+        ])
+        .await
+        .unwrap_err();
+
+    // amounts are limited
+    env.defuse
+        .execute_intents([
+            sign(&browser, token_diff([(&ft1, -200), (&ft2, 200)])),
+            sign(&env.user2, token_diff([(&ft1, 200), (&ft2, -200)])),
+        ])
+        .await
+        .unwrap_err();
+
+    // and so are limit orders, which are filled later
+    for order in [limit_order(&ft3, 100), limit_order(&ft2, 200)] {
+        env.defuse
+            .execute_intents([sign(&browser, order)])
+            .await
+            .unwrap_err();
+    }
+
+    env.defuse
+        .execute_intents([
+            sign(&browser, token_diff([(&ft1, -100), (&ft2, 100)])),
+            sign(&env.user2, token_diff([(&ft1, 100), (&ft2, -100)])),
+        ])
+        .await
+        .unwrap();
+    env.defuse
+        .execute_intents([sign(&browser, limit_order(&ft2, 100))])
+        .await
+        .unwrap();
+
+    for (token_id, balance) in [(&ft1, 900), (&ft2, 100), (&ft3, 0)] {
+        assert_eq!(
+            env.defuse
+                .mt_balance_of(env.user1.id(), &token_id.to_string())
+                .await
+                .unwrap(),
+            balance
+        );
+    }
+}
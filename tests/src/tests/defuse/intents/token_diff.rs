@@ -1,23 +1,27 @@
 use std::{collections::BTreeMap, time::Duration};
 
-use defuse::core::{
-    fees::Pips,
-    intents::{
-        token_diff::{TokenDeltas, TokenDiff},
-        DefuseIntents,
+use defuse::{
+    contract::Role,
+    core::{
+        fees::{FeeMode, Pips},
+        intents::{
+            token_diff::{TokenDeltas, TokenDiff},
+            DefuseIntents,
+        },
+        payload::multi::MultiPayload,
+        tokens::TokenId,
+        Deadline,
     },
-    payload::multi::MultiPayload,
-    tokens::TokenId,
-    Deadline,
 };
-use near_sdk::AccountId;
+use near_sdk::{json_types::U128, AccountId, NearToken};
 use near_workspaces::Account;
 use rand::{thread_rng, Rng};
 use rstest::rstest;
+use serde_json::json;
 
 use crate::{
     tests::defuse::{env::Env, DefuseSigner},
-    utils::mt::MtExt,
+    utils::{acl::AclExt, mt::MtExt, storage_management::StorageManagementExt},
 };
 
 use super::ExecuteIntentsExt;
@@ -41,14 +45,15 @@ async fn test_swap_p2p(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)] f
                         (ft1_token_id.clone(), -100),
                         (
                             ft2_token_id.clone(),
-                            TokenDiff::closure_delta(&ft2_token_id, -200, fee).unwrap(),
+                            TokenDiff::closure_delta(&ft2_token_id, -200, fee, FeeMode::TokenIn)
+                                .unwrap(),
                         ),
                     ])
                     .unwrap()]
                 .into(),
                 result_balances: [(
                     &env.ft2,
-                    TokenDiff::closure_delta(&ft2_token_id, -200, fee).unwrap(),
+                    TokenDiff::closure_delta(&ft2_token_id, -200, fee, FeeMode::TokenIn).unwrap(),
                 )]
                 .into_iter()
                 .collect(),
@@ -60,7 +65,8 @@ async fn test_swap_p2p(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)] f
                     .with_add_deltas([
                         (
                             ft1_token_id.clone(),
-                            TokenDiff::closure_delta(&ft1_token_id, -100, fee).unwrap(),
+                            TokenDiff::closure_delta(&ft1_token_id, -100, fee, FeeMode::TokenIn)
+                                .unwrap(),
                         ),
                         (ft2_token_id.clone(), -200),
                     ])
@@ -68,7 +74,7 @@ async fn test_swap_p2p(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)] f
                 .into(),
                 result_balances: [(
                     &env.ft1,
-                    TokenDiff::closure_delta(&ft1_token_id, -100, fee).unwrap(),
+                    TokenDiff::closure_delta(&ft1_token_id, -100, fee, FeeMode::TokenIn).unwrap(),
                 )]
                 .into_iter()
                 .collect(),
@@ -108,11 +114,18 @@ async fn test_swap_many(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)]
                         .with_add_deltas([
                             (
                                 ft1_token_id.clone(),
-                                TokenDiff::closure_delta(&ft1_token_id, -100, fee).unwrap(),
+                                TokenDiff::closure_delta(
+                                    &ft1_token_id,
+                                    -100,
+                                    fee,
+                                    FeeMode::TokenIn,
+                                )
+                                .unwrap(),
                             ),
                             (
                                 ft2_token_id.clone(),
-                                TokenDiff::closure_delta(&ft2_token_id, 200, fee).unwrap(),
+                                TokenDiff::closure_delta(&ft2_token_id, 200, fee, FeeMode::TokenIn)
+                                    .unwrap(),
                             ),
                         ])
                         .unwrap(),
@@ -120,11 +133,18 @@ async fn test_swap_many(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)]
                         .with_add_deltas([
                             (
                                 ft2_token_id.clone(),
-                                TokenDiff::closure_delta(&ft2_token_id, 300, fee).unwrap(),
+                                TokenDiff::closure_delta(&ft2_token_id, 300, fee, FeeMode::TokenIn)
+                                    .unwrap(),
                             ),
                             (
                                 ft3_token_id.clone(),
-                                TokenDiff::closure_delta(&ft3_token_id, -500, fee).unwrap(),
+                                TokenDiff::closure_delta(
+                                    &ft3_token_id,
+                                    -500,
+                                    fee,
+                                    FeeMode::TokenIn,
+                                )
+                                .unwrap(),
                             ),
                         ])
                         .unwrap(),
@@ -133,16 +153,20 @@ async fn test_swap_many(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)]
                 result_balances: [
                     (
                         &env.ft1,
-                        TokenDiff::closure_delta(&ft1_token_id, -100, fee).unwrap(),
+                        TokenDiff::closure_delta(&ft1_token_id, -100, fee, FeeMode::TokenIn)
+                            .unwrap(),
                     ),
                     (
                         &env.ft2,
-                        1000 + TokenDiff::closure_delta(&ft2_token_id, 200, fee).unwrap()
-                            + TokenDiff::closure_delta(&ft2_token_id, 300, fee).unwrap(),
+                        1000 + TokenDiff::closure_delta(&ft2_token_id, 200, fee, FeeMode::TokenIn)
+                            .unwrap()
+                            + TokenDiff::closure_delta(&ft2_token_id, 300, fee, FeeMode::TokenIn)
+                                .unwrap(),
                     ),
                     (
                         &env.ft3,
-                        TokenDiff::closure_delta(&ft3_token_id, -500, fee).unwrap(),
+                        TokenDiff::closure_delta(&ft3_token_id, -500, fee, FeeMode::TokenIn)
+                            .unwrap(),
                     ),
                 ]
                 .into_iter()
@@ -163,6 +187,637 @@ async fn test_swap_many(#[values(Pips::ZERO, Pips::ONE_BIP, Pips::ONE_PERCENT)]
     .await;
 }
 
+#[tokio::test]
+async fn test_referral_fee() {
+    let fee_collector: AccountId = "fee-collector.near".parse().unwrap();
+    let referral: AccountId = "referral.near".parse().unwrap();
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .fee_collector(fee_collector.clone())
+        .referral_fee(Pips::from_percent(50).unwrap())
+        .build()
+        .await;
+
+    env.user1
+        .storage_deposit(
+            env.defuse.id(),
+            Some(&referral),
+            NearToken::from_millinear(100),
+        )
+        .await
+        .unwrap();
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft2, 2000, env.user2.id())
+        .await
+        .unwrap();
+
+    env.defuse
+        .execute_intents([
+            env.user1.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([
+                                (ft1.clone(), -1000),
+                                (
+                                    ft2.clone(),
+                                    TokenDiff::closure_delta(
+                                        &ft2,
+                                        -2000,
+                                        Pips::ONE_PERCENT,
+                                        FeeMode::TokenIn,
+                                    )
+                                    .unwrap(),
+                                ),
+                            ])
+                            .unwrap(),
+                        memo: None,
+                        referral: Some(referral.clone()),
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+            env.user2.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([
+                                (
+                                    ft1.clone(),
+                                    TokenDiff::closure_delta(
+                                        &ft1,
+                                        -1000,
+                                        Pips::ONE_PERCENT,
+                                        FeeMode::TokenIn,
+                                    )
+                                    .unwrap(),
+                                ),
+                                (ft2.clone(), -2000),
+                            ])
+                            .unwrap(),
+                        memo: None,
+                        referral: None,
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+        ])
+        .await
+        .unwrap();
+
+    // referral gets half of the fee only from intents referred by it
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &referral,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [5, 0]
+    );
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &fee_collector,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [5, 20]
+    );
+
+    // cumulative statistics include referral shares
+    let fees_collected: Vec<(TokenId, U128)> = env
+        .defuse
+        .view("fees_collected")
+        .args_json(json!({}))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(
+        fees_collected.into_iter().collect::<BTreeMap<_, _>>(),
+        [(ft1.clone(), U128(10)), (ft2.clone(), U128(20))].into(),
+    );
+    let referral_fees_collected: Vec<(TokenId, U128)> = env
+        .defuse
+        .view("referral_fees_collected")
+        .args_json(json!({
+            "referral": referral,
+            "limit": 10,
+        }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(referral_fees_collected, [(ft1, U128(5))]);
+}
+
+#[tokio::test]
+async fn test_unregistered_referral() {
+    let fee_collector: AccountId = "fee-collector.near".parse().unwrap();
+    let referral: AccountId = "referral.near".parse().unwrap();
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .fee_collector(fee_collector.clone())
+        .referral_fee(Pips::from_percent(50).unwrap())
+        .build()
+        .await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft2, 2000, env.user2.id())
+        .await
+        .unwrap();
+
+    env.defuse
+        .execute_intents([
+            env.user1.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([
+                                (ft1.clone(), -1000),
+                                (
+                                    ft2.clone(),
+                                    TokenDiff::closure_delta(
+                                        &ft2,
+                                        -2000,
+                                        Pips::ONE_PERCENT,
+                                        FeeMode::TokenIn,
+                                    )
+                                    .unwrap(),
+                                ),
+                            ])
+                            .unwrap(),
+                        memo: None,
+                        referral: Some(referral.clone()),
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+            env.user2.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([
+                                (
+                                    ft1.clone(),
+                                    TokenDiff::closure_delta(
+                                        &ft1,
+                                        -1000,
+                                        Pips::ONE_PERCENT,
+                                        FeeMode::TokenIn,
+                                    )
+                                    .unwrap(),
+                                ),
+                                (ft2.clone(), -2000),
+                            ])
+                            .unwrap(),
+                        memo: None,
+                        referral: None,
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+        ])
+        .await
+        .unwrap();
+
+    // unregistered referral doesn't fail the batch, its share goes
+    // to fee collector instead
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &referral,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [0, 0]
+    );
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &fee_collector,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [10, 20]
+    );
+}
+
+#[tokio::test]
+async fn test_fee_discount() {
+    let fee_collector: AccountId = "fee-collector.near".parse().unwrap();
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .fee_collector(fee_collector.clone())
+        .deployer_as_super_admin()
+        .build()
+        .await;
+
+    env.acl_grant_role(env.defuse.id(), Role::FeeDiscountsManager, env.user3.id())
+        .await
+        .unwrap();
+
+    // exempt user1 from fees completely
+    env.user3
+        .call(env.defuse.id(), "set_fee_discount")
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "account_id": env.user1.id(),
+            "discount": Pips::MAX,
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    test_ft_diffs(
+        &env,
+        [
+            AccountFtDiff {
+                account: &env.user1,
+                init_balances: [(&env.ft1, 1000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([
+                        (ft1.clone(), -1000),
+                        (
+                            ft2.clone(),
+                            TokenDiff::closure_delta(
+                                &ft2,
+                                -2000,
+                                Pips::ONE_PERCENT,
+                                FeeMode::TokenIn,
+                            )
+                            .unwrap(),
+                        ),
+                    ])
+                    .unwrap()]
+                .into(),
+                result_balances: [(
+                    &env.ft2,
+                    TokenDiff::closure_delta(&ft2, -2000, Pips::ONE_PERCENT, FeeMode::TokenIn)
+                        .unwrap(),
+                )]
+                .into_iter()
+                .collect(),
+            },
+            AccountFtDiff {
+                account: &env.user2,
+                init_balances: [(&env.ft2, 2000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([
+                        (
+                            ft1.clone(),
+                            TokenDiff::closure_delta(&ft1, -1000, Pips::ZERO, FeeMode::TokenIn)
+                                .unwrap(),
+                        ),
+                        (ft2.clone(), -2000),
+                    ])
+                    .unwrap()]
+                .into(),
+                result_balances: [(&env.ft1, 1000)].into_iter().collect(),
+            },
+        ]
+        .into(),
+    )
+    .await;
+
+    // fees are taken only from user2
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &fee_collector,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [0, 20]
+    );
+}
+
+#[tokio::test]
+async fn test_pair_fee() {
+    let fee_collector: AccountId = "fee-collector.near".parse().unwrap();
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .fee_collector(fee_collector.clone())
+        .deployer_as_super_admin()
+        .build()
+        .await;
+
+    env.acl_grant_role(env.defuse.id(), Role::FeesManager, env.user3.id())
+        .await
+        .unwrap();
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+    let pair_fee = Pips::ONE_BIP * 10;
+
+    env.user3
+        .call(env.defuse.id(), "set_pair_fee")
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "token_a": ft2,
+            "token_b": ft1,
+            "fee": pair_fee,
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    test_ft_diffs(
+        &env,
+        [
+            AccountFtDiff {
+                account: &env.user1,
+                init_balances: [(&env.ft1, 10_000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([
+                        (ft1.clone(), -10_000),
+                        (
+                            ft2.clone(),
+                            TokenDiff::closure_delta(&ft2, -20_000, pair_fee, FeeMode::TokenIn)
+                                .unwrap(),
+                        ),
+                    ])
+                    .unwrap()]
+                .into(),
+                result_balances: [(
+                    &env.ft2,
+                    TokenDiff::closure_delta(&ft2, -20_000, pair_fee, FeeMode::TokenIn).unwrap(),
+                )]
+                .into_iter()
+                .collect(),
+            },
+            AccountFtDiff {
+                account: &env.user2,
+                init_balances: [(&env.ft2, 20_000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([
+                        (
+                            ft1.clone(),
+                            TokenDiff::closure_delta(&ft1, -10_000, pair_fee, FeeMode::TokenIn)
+                                .unwrap(),
+                        ),
+                        (ft2.clone(), -20_000),
+                    ])
+                    .unwrap()]
+                .into(),
+                result_balances: [(
+                    &env.ft1,
+                    TokenDiff::closure_delta(&ft1, -10_000, pair_fee, FeeMode::TokenIn).unwrap(),
+                )]
+                .into_iter()
+                .collect(),
+            },
+        ]
+        .into(),
+    )
+    .await;
+
+    // pair fee is charged instead of the default one
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &fee_collector,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [10, 20]
+    );
+}
+
+#[tokio::test]
+async fn test_fee_on_token_out() {
+    let fee_collector: AccountId = "fee-collector.near".parse().unwrap();
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .fee_collector(fee_collector.clone())
+        .fee_mode(FeeMode::TokenOut)
+        .build()
+        .await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    // no fees on token_in, so closures are exact opposites
+    assert_eq!(
+        TokenDiff::closure_delta(&ft1, -1000, Pips::ONE_PERCENT, FeeMode::TokenOut).unwrap(),
+        1000
+    );
+
+    test_ft_diffs(
+        &env,
+        [
+            AccountFtDiff {
+                account: &env.user1,
+                init_balances: [(&env.ft1, 1000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([(ft1.clone(), -1000), (ft2.clone(), 2000)])
+                    .unwrap()]
+                .into(),
+                result_balances: [(
+                    &env.ft2,
+                    TokenDiff::net_amount_out(&ft2, 2000, Pips::ONE_PERCENT)
+                        .try_into()
+                        .unwrap(),
+                )]
+                .into_iter()
+                .collect(),
+            },
+            AccountFtDiff {
+                account: &env.user2,
+                init_balances: [(&env.ft2, 2000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([(ft1.clone(), 1000), (ft2.clone(), -2000)])
+                    .unwrap()]
+                .into(),
+                result_balances: [(&env.ft1, 990)].into_iter().collect(),
+            },
+        ]
+        .into(),
+    )
+    .await;
+
+    // fees are deducted from token_out of both
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            &fee_collector,
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [10, 20]
+    );
+
+    // intents signed for another fee mode are rejected even if
+    // they would match each other
+    env.defuse
+        .execute_intents([
+            env.user1.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([(ft1.clone(), 100), (ft2.clone(), -100)])
+                            .unwrap(),
+                        memo: None,
+                        referral: None,
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+            env.user2.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([(ft1.clone(), -100), (ft2.clone(), 100)])
+                            .unwrap(),
+                        memo: None,
+                        referral: None,
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+        ])
+        .await
+        .unwrap_err();
+}
+
+#[tokio::test]
+async fn test_fee_collector_shares() {
+    let fee_collector: AccountId = "fee-collector.near".parse().unwrap();
+    let insurance: AccountId = "insurance.near".parse().unwrap();
+    let ops: AccountId = "ops.near".parse().unwrap();
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .fee_collector(fee_collector.clone())
+        .deployer_as_super_admin()
+        .build()
+        .await;
+
+    env.acl_grant_role(env.defuse.id(), Role::FeesManager, env.user3.id())
+        .await
+        .unwrap();
+
+    env.user3
+        .call(env.defuse.id(), "set_fee_collector_shares")
+        .deposit(NearToken::from_yoctonear(1))
+        .args_json(json!({
+            "shares": {
+                insurance.as_str(): Pips::from_percent(30).unwrap(),
+                ops.as_str(): Pips::from_percent(20).unwrap(),
+            },
+        }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    test_ft_diffs(
+        &env,
+        [
+            AccountFtDiff {
+                account: &env.user1,
+                init_balances: [(&env.ft1, 1000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([(ft1.clone(), -1000), (ft2.clone(), 1980)])
+                    .unwrap()]
+                .into(),
+                result_balances: [(&env.ft2, 1980)].into_iter().collect(),
+            },
+            AccountFtDiff {
+                account: &env.user2,
+                init_balances: [(&env.ft2, 2000)].into_iter().collect(),
+                diff: [TokenDeltas::default()
+                    .with_add_deltas([(ft1.clone(), 990), (ft2.clone(), -2000)])
+                    .unwrap()]
+                .into(),
+                result_balances: [(&env.ft1, 990)].into_iter().collect(),
+            },
+        ]
+        .into(),
+    )
+    .await;
+
+    for (collector, balances) in [
+        (&fee_collector, [5, 10]),
+        (&insurance, [3, 6]),
+        (&ops, [2, 4]),
+    ] {
+        assert_eq!(
+            env.mt_contract_batch_balance_of(
+                env.defuse.id(),
+                collector,
+                [&ft1.to_string(), &ft2.to_string()]
+            )
+            .await
+            .unwrap(),
+            balances,
+            "{collector}"
+        );
+    }
+}
+
 type FtBalances<'a> = BTreeMap<&'a AccountId, i128>;
 
 #[derive(Debug)]
@@ -187,10 +842,20 @@ async fn test_ft_diffs(env: &Env, accounts: Vec<AccountFtDiff<'_>>) {
         }
     }
 
+    // sign for the current fee mode of the contract
+    let fee_mode: FeeMode = env
+        .defuse
+        .view("fee_mode")
+        .args_json(json!({}))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+
     let signed: Vec<MultiPayload> = accounts
         .iter()
         .flat_map(move |account| {
-            account.diff.iter().cloned().map(|diff| {
+            account.diff.iter().cloned().map(move |diff| {
                 account.account.sign_defuse_message(
                     env.defuse.id(),
                     thread_rng().gen(),
@@ -200,6 +865,7 @@ async fn test_ft_diffs(env: &Env, accounts: Vec<AccountFtDiff<'_>>) {
                             diff,
                             memo: None,
                             referral: None,
+                            fee_mode,
                         }
                         .into()]
                         .into(),
@@ -268,6 +934,7 @@ async fn test_invariant_violated() {
                         .unwrap(),
                     memo: None,
                     referral: None,
+                    fee_mode: FeeMode::TokenIn,
                 }
                 .into()]
                 .into(),
@@ -284,6 +951,7 @@ async fn test_invariant_violated() {
                         .unwrap(),
                     memo: None,
                     referral: None,
+                    fee_mode: FeeMode::TokenIn,
                 }
                 .into()]
                 .into(),
@@ -357,7 +1025,8 @@ async fn test_solver_user_closure(
 
     dbg!(USER_DELTA_IN);
     // propagate RFQ to solver with adjusted amount_in
-    let solver_delta_in = TokenDiff::closure_delta(&token_in, USER_DELTA_IN, fee).unwrap();
+    let solver_delta_in =
+        TokenDiff::closure_delta(&token_in, USER_DELTA_IN, fee, FeeMode::TokenIn).unwrap();
 
     // assume solver trades 1:2
     let solver_delta_out = solver_delta_in * -2;
@@ -380,6 +1049,7 @@ async fn test_solver_user_closure(
                 ),
                 memo: None,
                 referral: None,
+                fee_mode: FeeMode::TokenIn,
             }
             .into()]
             .into(),
@@ -409,16 +1079,20 @@ async fn test_solver_user_closure(
 
     // expect unmatched delta on token_in to be fully covered by user_in
     let expected_unmatched_delta_token_in =
-        TokenDiff::closure_delta(&token_in, USER_DELTA_IN, fee).unwrap();
+        TokenDiff::closure_delta(&token_in, USER_DELTA_IN, fee, FeeMode::TokenIn).unwrap();
     assert_eq!(
         unmatched_deltas.balance_of(&token_in),
         expected_unmatched_delta_token_in
     );
 
     // calculate user_delta_out to return to the user
-    let user_delta_out =
-        TokenDiff::closure_supply_delta(&token_out, unmatched_deltas.balance_of(&token_out), fee)
-            .unwrap();
+    let user_delta_out = TokenDiff::closure_supply_delta(
+        &token_out,
+        unmatched_deltas.balance_of(&token_out),
+        fee,
+        FeeMode::TokenIn,
+    )
+    .unwrap();
     dbg!(user_delta_out);
 
     // user signs the message
@@ -438,6 +1112,7 @@ async fn test_solver_user_closure(
                 ),
                 memo: None,
                 referral: None,
+                fee_mode: FeeMode::TokenIn,
             }
             .into()]
             .into(),
@@ -486,3 +1161,98 @@ async fn test_solver_user_closure(
         ]
     );
 }
+
+#[tokio::test]
+async fn test_self_referral() {
+    let env = Env::builder()
+        .fee(Pips::ONE_PERCENT)
+        .referral_fee(Pips::from_percent(50).unwrap())
+        .build()
+        .await;
+
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let ft2 = TokenId::Nep141(env.ft2.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft2, 2000, env.user2.id())
+        .await
+        .unwrap();
+
+    // signer can't refer itself to get a discount
+    env.defuse
+        .execute_intents([
+            env.user1.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([
+                                (ft1.clone(), -1000),
+                                (
+                                    ft2.clone(),
+                                    TokenDiff::closure_delta(
+                                        &ft2,
+                                        -2000,
+                                        Pips::ONE_PERCENT,
+                                        FeeMode::TokenIn,
+                                    )
+                                    .unwrap(),
+                                ),
+                            ])
+                            .unwrap(),
+                        memo: None,
+                        referral: Some(env.user1.id().clone()),
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+            env.user2.sign_defuse_message(
+                env.defuse.id(),
+                thread_rng().gen(),
+                Deadline::MAX,
+                DefuseIntents {
+                    intents: [TokenDiff {
+                        diff: TokenDeltas::default()
+                            .with_add_deltas([
+                                (
+                                    ft1.clone(),
+                                    TokenDiff::closure_delta(
+                                        &ft1,
+                                        -1000,
+                                        Pips::ONE_PERCENT,
+                                        FeeMode::TokenIn,
+                                    )
+                                    .unwrap(),
+                                ),
+                                (ft2.clone(), -2000),
+                            ])
+                            .unwrap(),
+                        memo: None,
+                        referral: None,
+                        fee_mode: FeeMode::TokenIn,
+                    }
+                    .into()]
+                    .into(),
+                },
+            ),
+        ])
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        env.mt_contract_batch_balance_of(
+            env.defuse.id(),
+            env.user1.id(),
+            [&ft1.to_string(), &ft2.to_string()]
+        )
+        .await
+        .unwrap(),
+        [1000, 0]
+    );
+}
@@ -1,6 +1,7 @@
 pub mod accounts;
 mod env;
 mod intents;
+mod storage;
 mod tokens;
 mod upgrade;
 
@@ -10,7 +11,7 @@ use defuse::{
     contract::config::DefuseConfig,
     core::{
         nep413::Nep413Payload,
-        payload::{multi::MultiPayload, nep413::Nep413DefuseMessage},
+        payload::{multi::MultiPayload, nep413::Nep413DefuseMessage, PayloadVersion},
         Deadline, Nonce,
     },
 };
@@ -76,6 +77,7 @@ impl DefuseSigner for near_workspaces::Account {
         self.sign_nep413(
             Nep413Payload::new(
                 serde_json::to_string(&Nep413DefuseMessage {
+                    version: PayloadVersion::LATEST,
                     signer_id: self.id().clone(),
                     deadline,
                     message,
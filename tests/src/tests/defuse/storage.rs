@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+use defuse::core::{
+    crypto::PublicKey,
+    intents::{account::CloseAccount, tokens::StorageDeposit, DefuseIntents},
+    tokens::TokenId,
+    Deadline,
+};
+use near_contract_standards::storage_management::{StorageBalance, StorageBalanceBounds};
+use near_sdk::{AccountId, NearToken};
+use rand::{thread_rng, Rng};
+use serde_json::json;
+
+use crate::utils::{mt::MtExt, storage_management::StorageManagementExt};
+
+use super::{accounts::AccountManagerExt, env::Env, intents::ExecuteIntentsExt, DefuseSigner};
+
+async fn storage_balance_of(env: &Env, account_id: &AccountId) -> Option<StorageBalance> {
+    env.defuse
+        .view("storage_balance_of")
+        .args_json(json!({
+            "account_id": account_id,
+        }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_storage_deposit() {
+    let env = Env::new().await;
+    let new_user: AccountId = "new-user.near".parse().unwrap();
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    // deposits to unregistered accounts are refunded
+    env.defuse_ft_mint(&env.ft1, 1000, &new_user)
+        .await
+        .unwrap_err();
+    assert!(storage_balance_of(&env, &new_user).await.is_none());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // transfers to unregistered accounts fail
+    env.user1
+        .mt_transfer(
+            env.defuse.id(),
+            &new_user,
+            &ft1.to_string(),
+            100,
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+
+    let bounds: StorageBalanceBounds = env
+        .defuse
+        .view("storage_balance_bounds")
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+
+    // registration requires minimum storage deposit
+    env.user1
+        .storage_deposit(
+            env.defuse.id(),
+            Some(&new_user),
+            bounds.min.saturating_sub(NearToken::from_yoctonear(1)),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        env.user1
+            .storage_deposit(env.defuse.id(), Some(&new_user), bounds.min)
+            .await
+            .unwrap()
+            .total,
+        bounds.min
+    );
+
+    env.user1
+        .mt_transfer(
+            env.defuse.id(),
+            &new_user,
+            &ft1.to_string(),
+            100,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft1, 1000, &new_user).await.unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(&new_user, &ft1.to_string())
+            .await
+            .unwrap(),
+        1100
+    );
+
+    // only storage deposit exceeding the cost of storage can be withdrawn
+    let balance = env
+        .user1
+        .storage_deposit(env.defuse.id(), None, NearToken::from_near(1))
+        .await
+        .unwrap();
+    assert!(!balance.available.is_zero());
+    env.user1
+        .call(env.defuse.id(), "storage_withdraw")
+        .args_json(json!({
+            "amount": balance.available.saturating_add(NearToken::from_yoctonear(1)),
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap_err();
+    let balance: StorageBalance = env
+        .user1
+        .call(env.defuse.id(), "storage_withdraw")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(balance.available.is_zero());
+}
+
+#[tokio::test]
+async fn test_storage_usage_covered() {
+    let env = Env::new().await;
+
+    // leave just enough to cover current storage usage
+    env.user1
+        .call(env.defuse.id(), "storage_withdraw")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    let public_key: PublicKey = env
+        .user2
+        .secret_key()
+        .public_key()
+        .to_string()
+        .parse()
+        .unwrap();
+
+    // storage usage can't grow beyond storage deposit
+    env.user1
+        .add_public_key(env.defuse.id(), public_key)
+        .await
+        .unwrap_err();
+
+    env.user1
+        .storage_deposit(env.defuse.id(), None, NearToken::from_millinear(10))
+        .await
+        .unwrap();
+    env.user1
+        .add_public_key(env.defuse.id(), public_key)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_storage_deposit_intent() {
+    let env = Env::new().await;
+    let new_user: AccountId = "new-user.near".parse().unwrap();
+    let wnear = TokenId::Nep141(env.wnear.id().clone());
+    let amount = NearToken::from_millinear(100);
+
+    env.defuse_ft_mint(env.wnear.id(), amount.as_yoctonear(), env.user1.id())
+        .await
+        .unwrap();
+
+    env.defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::timeout(Duration::from_secs(120)),
+            DefuseIntents {
+                intents: [StorageDeposit {
+                    account_id: new_user.clone(),
+                    amount,
+                }
+                .into()]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &wnear.to_string())
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        storage_balance_of(&env, &new_user)
+            .await
+            .map(|balance| balance.total),
+        Some(amount)
+    );
+}
+
+#[tokio::test]
+async fn test_close_account() {
+    let env = Env::new().await;
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+    let public_key: PublicKey = env
+        .user2
+        .secret_key()
+        .public_key()
+        .to_string()
+        .parse()
+        .unwrap();
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // accounts with non-zero balances can't be closed
+    env.user1
+        .storage_unregister(env.defuse.id(), None)
+        .await
+        .unwrap_err();
+
+    env.user2
+        .storage_deposit(env.defuse.id(), None, NearToken::from_near(1))
+        .await
+        .unwrap();
+    let close = env.user2.sign_defuse_message(
+        env.defuse.id(),
+        thread_rng().gen(),
+        Deadline::timeout(Duration::from_secs(120)),
+        DefuseIntents {
+            intents: [CloseAccount { receiver_id: None }.into()].into(),
+        },
+    );
+    env.defuse.execute_intents([close.clone()]).await.unwrap();
+    assert!(storage_balance_of(&env, env.user2.id()).await.is_none());
+    assert!(!env
+        .defuse
+        .has_public_key(env.user2.id(), &public_key)
+        .await
+        .unwrap());
+
+    // closed account can't receive transfers until registered again
+    env.user1
+        .mt_transfer(
+            env.defuse.id(),
+            env.user2.id(),
+            &ft1.to_string(),
+            100,
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+
+    // re-created account starts from scratch
+    env.user2
+        .storage_deposit(env.defuse.id(), None, NearToken::from_near(1))
+        .await
+        .unwrap();
+    assert!(!env
+        .defuse
+        .has_public_key(env.user2.id(), &public_key)
+        .await
+        .unwrap());
+
+    // but intents signed before closing can't be replayed
+    env.user2
+        .add_public_key(env.defuse.id(), public_key)
+        .await
+        .unwrap();
+    env.defuse.execute_intents([close]).await.unwrap_err();
+    assert!(storage_balance_of(&env, env.user2.id()).await.is_some());
+
+    assert!(env
+        .user3
+        .storage_unregister(env.defuse.id(), None)
+        .await
+        .unwrap());
+    assert!(storage_balance_of(&env, env.user3.id()).await.is_none());
+    assert!(!env
+        .user3
+        .storage_unregister(env.defuse.id(), None)
+        .await
+        .unwrap());
+}
@@ -1 +1,2 @@
 pub mod nep141;
+pub mod nep245;
@@ -3,7 +3,7 @@ use std::time::Duration;
 use defuse::{
     contract::Role,
     core::{
-        intents::{tokens::FtWithdraw, DefuseIntents},
+        intents::{account::CloseAccount, tokens::FtWithdraw, DefuseIntents},
         tokens::TokenId,
         Deadline,
     },
@@ -15,7 +15,7 @@ use serde_json::json;
 
 use crate::{
     tests::{
-        defuse::{env::Env, DefuseSigner},
+        defuse::{env::Env, intents::ExecuteIntentsExt, DefuseSigner},
         poa::factory::PoAFactoryExt,
     },
     utils::{acl::AclExt, ft::FtExt, mt::MtExt},
@@ -237,6 +237,55 @@ async fn test_deposit_withdraw_intent_refund() {
     );
 }
 
+#[tokio::test]
+async fn test_withdraw_refund_to_closed_account() {
+    let env = Env::new().await;
+    let ft1 = TokenId::Nep141(env.ft1.clone());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // account is closed before the withdrawal gets refunded
+    env.defuse
+        .execute_intents([env.user1.sign_defuse_message(
+            env.defuse.id(),
+            thread_rng().gen(),
+            Deadline::timeout(Duration::from_secs(120)),
+            DefuseIntents {
+                intents: [
+                    FtWithdraw {
+                        token: env.ft1.clone(),
+                        // not registered on the token
+                        receiver_id: "unregistered.near".parse().unwrap(),
+                        amount: U128(1000),
+                        memo: None,
+                        msg: None,
+                        storage_deposit: None,
+                    }
+                    .into(),
+                    CloseAccount { receiver_id: None }.into(),
+                ]
+                .into(),
+            },
+        )])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.mt_contract_balance_of(env.defuse.id(), env.user1.id(), &ft1.to_string())
+            .await
+            .unwrap(),
+        1000
+    );
+    assert_eq!(
+        env.ft_token_balance_of(&env.ft1, env.defuse.id())
+            .await
+            .unwrap(),
+        1000
+    );
+}
+
 #[tokio::test]
 async fn test_ft_force_withdraw() {
     let env = Env::builder().deployer_as_super_admin().build().await;
@@ -0,0 +1,394 @@
+use defuse::{
+    contract::Role,
+    core::tokens::TokenId,
+    nep245::{approval::Approval, metadata::MTBaseTokenMetadata, Token},
+};
+use near_sdk::{json_types::U128, AccountId, NearToken};
+use serde_json::json;
+
+use crate::{
+    tests::defuse::env::Env,
+    utils::{acl::AclExt, mt::MtExt},
+};
+
+async fn mt_approve(
+    owner: &near_workspaces::Account,
+    defuse_id: &AccountId,
+    token_id: &str,
+    amount: u128,
+    account_id: &AccountId,
+) -> anyhow::Result<()> {
+    owner
+        .call(defuse_id, "mt_approve")
+        .args_json(json!({
+            "token_ids": [token_id],
+            "amounts": [U128(amount)],
+            "account_id": account_id,
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+async fn mt_approval_of(
+    env: &Env,
+    owner_id: &AccountId,
+    token_id: &str,
+    approved_account_id: &AccountId,
+) -> Option<Approval> {
+    env.defuse
+        .view("mt_approval_of")
+        .args_json(json!({
+            "owner_id": owner_id,
+            "token_id": token_id,
+            "approved_account_id": approved_account_id,
+        }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_mt_approve() {
+    let env = Env::new().await;
+    let ft1 = TokenId::Nep141(env.ft1.clone()).to_string();
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    // transfers on behalf of others require approval
+    env.user2
+        .mt_transfer(
+            env.defuse.id(),
+            env.user3.id(),
+            &ft1,
+            100,
+            Some((env.user1.id().clone(), 0)),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+    mt_approve(&env.user1, env.defuse.id(), &ft1, 300, env.user2.id())
+        .await
+        .unwrap();
+    let approval = mt_approval_of(&env, env.user1.id(), &ft1, env.user2.id())
+        .await
+        .unwrap();
+    assert_eq!(approval.amount, U128(300));
+
+    assert!(env
+        .defuse
+        .view("mt_is_approved")
+        .args_json(json!({
+            "owner_id": env.user1.id(),
+            "token_ids": [ft1],
+            "approved_account_id": env.user2.id(),
+            "amounts": [U128(300)],
+            "approval_ids": [approval.approval_id],
+        }))
+        .await
+        .unwrap()
+        .json::<bool>()
+        .unwrap());
+
+    env.user2
+        .mt_transfer(
+            env.defuse.id(),
+            env.user3.id(),
+            &ft1,
+            200,
+            Some((env.user1.id().clone(), approval.approval_id)),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1)
+            .await
+            .unwrap(),
+        800
+    );
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user3.id(), &ft1)
+            .await
+            .unwrap(),
+        200
+    );
+    assert_eq!(
+        mt_approval_of(&env, env.user1.id(), &ft1, env.user2.id())
+            .await
+            .unwrap()
+            .amount,
+        U128(100)
+    );
+
+    // approvals spent on refunded transfer calls are restored,
+    // user3 has no contract deployed, so the call fails
+    env.user2
+        .call(env.defuse.id(), "mt_transfer_call")
+        .args_json(json!({
+            "receiver_id": env.user3.id(),
+            "token_id": ft1,
+            "amount": U128(100),
+            "approval": (env.user1.id(), approval.approval_id),
+            "msg": "",
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(
+        env.defuse
+            .mt_balance_of(env.user1.id(), &ft1)
+            .await
+            .unwrap(),
+        800
+    );
+    assert_eq!(
+        mt_approval_of(&env, env.user1.id(), &ft1, env.user2.id())
+            .await
+            .unwrap()
+            .amount,
+        U128(100)
+    );
+
+    // can't exceed remaining approval
+    env.user2
+        .mt_transfer(
+            env.defuse.id(),
+            env.user3.id(),
+            &ft1,
+            101,
+            Some((env.user1.id().clone(), approval.approval_id)),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+    env.user1
+        .call(env.defuse.id(), "mt_revoke")
+        .args_json(json!({
+            "token_ids": [ft1],
+            "account_id": env.user2.id(),
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert!(mt_approval_of(&env, env.user1.id(), &ft1, env.user2.id())
+        .await
+        .is_none());
+    env.user2
+        .mt_transfer(
+            env.defuse.id(),
+            env.user3.id(),
+            &ft1,
+            100,
+            Some((env.user1.id().clone(), approval.approval_id)),
+            None,
+        )
+        .await
+        .unwrap_err();
+}
+
+async fn mt_tokens_for_owner(
+    env: &Env,
+    account_id: &AccountId,
+    from_index: Option<u128>,
+    limit: Option<u32>,
+) -> Vec<String> {
+    env.defuse
+        .view("mt_tokens_for_owner")
+        .args_json(json!({
+            "account_id": account_id,
+            "from_index": from_index.map(U128),
+            "limit": limit,
+        }))
+        .await
+        .unwrap()
+        .json::<Vec<Token>>()
+        .unwrap()
+        .into_iter()
+        .map(|token| token.token_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_mt_tokens_for_owner() {
+    let env = Env::new().await;
+    let ft1 = TokenId::Nep141(env.ft1.clone()).to_string();
+    let ft2 = TokenId::Nep141(env.ft2.clone()).to_string();
+
+    assert!(mt_tokens_for_owner(&env, env.user1.id(), None, None)
+        .await
+        .is_empty());
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    env.defuse_ft_mint(&env.ft2, 1000, env.user1.id())
+        .await
+        .unwrap();
+
+    let mut tokens = mt_tokens_for_owner(&env, env.user1.id(), None, None).await;
+    tokens.sort();
+    let mut expected = vec![ft1.clone(), ft2.clone()];
+    expected.sort();
+    assert_eq!(tokens, expected);
+    assert_eq!(
+        mt_tokens_for_owner(&env, env.user1.id(), Some(1), Some(1))
+            .await
+            .len(),
+        1
+    );
+
+    let tokens: Vec<Token> = env
+        .defuse
+        .view("mt_tokens")
+        .args_json(json!({}))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!([&ft1, &ft2]
+        .into_iter()
+        .all(|token_id| tokens.iter().any(|token| token.token_id == *token_id)));
+
+    // tokens with zero balance are not listed
+    env.user1
+        .mt_transfer(env.defuse.id(), env.user2.id(), &ft1, 1000, None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        mt_tokens_for_owner(&env, env.user1.id(), None, None).await,
+        [ft2]
+    );
+}
+
+async fn mt_metadata_base_by_token_id(env: &Env, token_id: &str) -> Option<MTBaseTokenMetadata> {
+    env.defuse
+        .view("mt_metadata_base_by_token_id")
+        .args_json(json!({
+            "token_ids": [token_id],
+        }))
+        .await
+        .unwrap()
+        .json::<Vec<Option<MTBaseTokenMetadata>>>()
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+async fn mt_fetch_metadata(
+    caller: &near_workspaces::Account,
+    defuse_id: &AccountId,
+    method: &str,
+    token_id: &str,
+    deposit: NearToken,
+) -> anyhow::Result<bool> {
+    caller
+        .call(defuse_id, method)
+        .args_json(json!({
+            "token_id": token_id,
+        }))
+        .deposit(deposit)
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?
+        .json()
+        .map_err(Into::into)
+}
+
+#[tokio::test]
+async fn test_mt_fetch_metadata() {
+    let env = Env::builder().deployer_as_super_admin().build().await;
+    let ft1 = TokenId::Nep141(env.ft1.clone()).to_string();
+
+    // only tokens held on the contract are supported
+    mt_fetch_metadata(
+        &env.user1,
+        env.defuse.id(),
+        "mt_fetch_metadata",
+        &ft1,
+        NearToken::from_near(1),
+    )
+    .await
+    .unwrap_err();
+
+    env.defuse_ft_mint(&env.ft1, 1000, env.user1.id())
+        .await
+        .unwrap();
+    assert!(mt_metadata_base_by_token_id(&env, &ft1).await.is_none());
+
+    // storage has to be paid for
+    mt_fetch_metadata(
+        &env.user1,
+        env.defuse.id(),
+        "mt_fetch_metadata",
+        &ft1,
+        NearToken::from_yoctonear(0),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(mt_fetch_metadata(
+        &env.user1,
+        env.defuse.id(),
+        "mt_fetch_metadata",
+        &ft1,
+        NearToken::from_near(1),
+    )
+    .await
+    .unwrap());
+    let base = mt_metadata_base_by_token_id(&env, &ft1).await.unwrap();
+    assert_eq!(base.id, ft1);
+    assert!(base.decimals.is_some());
+
+    // cached metadata can only be refetched by metadata managers
+    mt_fetch_metadata(
+        &env.user1,
+        env.defuse.id(),
+        "mt_fetch_metadata",
+        &ft1,
+        NearToken::from_near(1),
+    )
+    .await
+    .unwrap_err();
+    mt_fetch_metadata(
+        &env.user1,
+        env.defuse.id(),
+        "mt_force_fetch_metadata",
+        &ft1,
+        NearToken::from_yoctonear(1),
+    )
+    .await
+    .unwrap_err();
+
+    env.acl_grant_role(env.defuse.id(), Role::MetadataManager, env.user2.id())
+        .await
+        .unwrap();
+    assert!(mt_fetch_metadata(
+        &env.user2,
+        env.defuse.id(),
+        "mt_force_fetch_metadata",
+        &ft1,
+        NearToken::from_yoctonear(1),
+    )
+    .await
+    .unwrap());
+}